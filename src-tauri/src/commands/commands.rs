@@ -1,67 +1,112 @@
-use std::{ env, fs::{ self, create_dir_all }, path::PathBuf };
+use std::{ collections::HashMap, fs, path::Path, sync::Arc };
 
 use serde::Serialize;
 use tauri::{ async_runtime::Mutex, Manager, State };
 use tauri_plugin_clipboard_manager::ClipboardExt;
 use tokio::time;
 
-use crate::vault::vault::{ attempt_unlock, delete_vault, init, Unlocked, Vault };
+use crate::store::store::VaultStore;
+use crate::vault::attachment::Attachment;
+use crate::vault::backup::BackupInfo;
+use crate::vault::breach::BreachedSecret;
+use crate::vault::card::PaymentCard;
+use crate::vault::date::Date;
+use crate::vault::derived::DerivedProfile;
+use crate::vault::entry::{ Entry, EntryField, EntrySummary, EntryWarning, ExpiringEntry, Expiry };
+use crate::vault::generator::{ GenerateTarget, PasswordPolicy };
+use crate::vault::health::HealthReport;
+use crate::vault::identity::Identity;
+use crate::vault::lock::Access;
+use crate::vault::metadata::{ MetadataSettings, VaultHeader };
+use crate::vault::passkey::{ decode_b64, Assertion, Passkey, PasskeySummary, Registration };
+use crate::vault::passphrase::{ Passphrase, PassphraseOptions };
+use crate::vault::search::SearchHit;
+use crate::vault::ssh::SshKey;
+use crate::vault::strength::{ self, Strength };
+use crate::vault::template::{ CustomFieldView, Template, TemplateField };
+use crate::vault::url_match::{ UrlMatch, UrlMatchHit };
+use crate::vault::username::UsernameStyle;
+use crate::vault::verify::{ RepairOutcome, VerifyReport };
+use crate::vault::vault::{ self, attempt_unlock, delete_vault, init, Unlocked, Vault };
 
 #[derive(Serialize, Clone)]
 pub struct VaultSurfaceData {
     name: String,
     id: u32,
+    /// Whatever the vault exposes in its header, shown without unlocking it.
+    #[serde(flatten)]
+    header: VaultHeader,
 }
 
-#[derive(Clone)]
 struct OpenVault {
     vault: Vault<Unlocked>,
     name: String,
+    access: Access,
 }
 
 type Error = String;
 
-#[derive(Default)]
 pub struct VaultCollection {
     pub vaults: Vec<VaultSurfaceData>,
     open_vault: Option<OpenVault>,
+    store: Arc<dyn VaultStore>,
+    /// Things found while starting up that the user hasn't been shown yet.
+    notices: Vec<String>,
+}
+
+/// Locks a vault that isn't open here for a one off change.
+fn lock_for_change(store: &dyn VaultStore, vault_name: &str) -> Result<Access, String> {
+    match store.lock(vault_name)? {
+        Access::ReadOnly { holder } => Err(format!("{} has this vault open", holder)),
+        access => Ok(access),
+    }
+}
+
+impl VaultCollection {
+    pub fn new(store: Arc<dyn VaultStore>, notices: Vec<String>) -> Self {
+        VaultCollection {
+            vaults: vec![],
+            open_vault: None,
+            store,
+            notices,
+        }
+    }
+
+    /// Refuses changes while another process has the open vault open for writing.
+    fn check_writable(&self) -> Result<(), String> {
+        match self.open_vault.as_ref().map(|open| &open.access) {
+            Some(Access::ReadOnly { holder }) => {
+                Err(format!("This vault is read only while {} has it open", holder))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Hands over the startup notices once, later calls get none.
+#[tauri::command]
+pub async fn request_notices(
+    state: State<'_, Mutex<VaultCollection>>
+) -> Result<Vec<String>, Error> {
+    Ok(std::mem::take(&mut state.lock().await.notices))
 }
 
 #[tauri::command]
 pub async fn request_vaults(
     state: State<'_, Mutex<VaultCollection>>
 ) -> Result<Vec<VaultSurfaceData>, Error> {
-    let appdata = env
-        ::var("APPDATA")
-        .or_else(|_| env::var("HOME"))
-        .unwrap();
-
-    let path: PathBuf = [appdata, "Ancrypt".to_string(), "Vaults".to_string()].iter().collect();
-
-    create_dir_all(&path).map_err(|e| e.to_string())?;
-
     let mut state = state.lock().await;
 
-    let mut vaults = Vec::new();
-    let mut id = 0;
-    if let Ok(entries) = fs::read_dir(&path) {
-        for entry in entries.flatten() {
-            let dir = entry.path();
-            if dir.is_file() {
-                if let Some(extension) = dir.extension() {
-                    if extension == "ANCRYPT" {
-                        if let Some(file_name) = dir.file_stem().and_then(|n| n.to_str()) {
-                            vaults.push(VaultSurfaceData {
-                                name: file_name.to_string(),
-                                id,
-                            });
-                            id += 1;
-                        }
-                    }
-                }
-            }
-        }
-    }
+    let vaults: Vec<VaultSurfaceData> = state.store
+        .list()?
+        .into_iter()
+        .enumerate()
+        .map(|(id, name)| VaultSurfaceData {
+            header: vault::read_header(&*state.store, &name),
+            name,
+            id: id as u32,
+        })
+        .collect();
 
     state.vaults = vaults.clone();
 
@@ -72,6 +117,7 @@ pub async fn request_vaults(
 pub struct VaultResult {
     success: bool,
     message: Option<String>,
+    read_only: bool,
 }
 
 #[tauri::command(rename_all = "snake_case")]
@@ -84,22 +130,44 @@ pub async fn create_vault(
         return Ok(VaultResult {
             success: false,
             message: Some(String::from("Invalid vault name and/or password. Try again!")),
+            read_only: false,
         });
     }
 
     let (name, password) = { (vault_name.trim(), vault_password.trim()) };
+    let store = state.lock().await.store.clone();
+
+    let access = match store.lock(name) {
+        Ok(Access::ReadOnly { holder }) => {
+            return Ok(VaultResult {
+                success: false,
+                message: Some(format!("A vault of that name is open in {}", holder)),
+                read_only: false,
+            });
+        }
+        Ok(access) => access,
+        Err(e) => {
+            return Ok(VaultResult { success: false, message: Some(e), read_only: false });
+        }
+    };
 
-    let new = Vault::new();
-    let vault = new.create_new(password, name);
+    let new = Vault::new(store);
+    let vault = match new.create_new(password, name) {
+        Ok(vault) => vault,
+        Err(e) => {
+            return Ok(VaultResult { success: false, message: Some(e), read_only: false });
+        }
+    };
 
     let open_vault = OpenVault {
         vault,
         name: vault_name,
+        access,
     };
 
     state.lock().await.open_vault = Some(open_vault);
 
-    Ok(VaultResult { success: true, message: None })
+    Ok(VaultResult { success: true, message: None, read_only: false })
 }
 
 #[tauri::command(rename_all = "snake_case")]
@@ -116,44 +184,207 @@ pub async fn open_vault(
             return Ok(VaultResult {
                 success: false,
                 message: Some(String::from("Something went wrong")),
+                read_only: false,
             });
         }
     };
 
-    let pending = match init(&vault.name) {
-        Some(ok) => ok,
-        None => {
+    let name = vault.name.clone();
+
+    // The vault is taken before it is read, so a save another process left unfinished is
+    // settled first. Reopening the vault this window already has open keeps its access.
+    let access = match &lock.open_vault {
+        Some(open) if open.name == name => None,
+        _ => match lock.store.lock(&name) {
+            Ok(access) => Some(access),
+            Err(e) => {
+                return Ok(VaultResult { success: false, message: Some(e), read_only: false });
+            }
+        },
+    };
+
+    let pending = match init(lock.store.clone(), &name) {
+        Ok(Some(ok)) => ok,
+        Err(e) => {
+            return Ok(VaultResult { success: false, message: Some(e), read_only: false });
+        }
+        Ok(None) => {
             return Ok(VaultResult {
                 success: false,
-                message: Some(String::from("Something went wrong")),
+                message: Some(String::from("This vault could not be read, verify it to find out why")),
+                read_only: false,
             });
         }
     };
 
-    let unlocked = match attempt_unlock(pending, &password) {
+    let mut unlocked = match attempt_unlock(pending, &password) {
         Ok(ok) => ok,
-        Err(_) => {
-            return Ok(VaultResult {
-                success: false,
-                message: Some(String::from("Incorrect Password")),
-            });
+        Err(e) => {
+            return Ok(VaultResult { success: false, message: Some(e), read_only: false });
+        }
+    };
+
+    let access = match access {
+        Some(access) => access,
+        None => lock.open_vault.take().unwrap().access,
+    };
+
+    let mut notes: Vec<String> = match &access {
+        Access::ReadOnly { holder } => {
+            vec![format!("Opened read only, {} has this vault open", holder)]
+        }
+        Access::Writable { recovered: Some(holder), .. } => {
+            vec![format!("Recovered the vault from {}, which did not close it", holder)]
         }
+        Access::Writable { recovered: None, .. } => vec![],
     };
+    let read_only = matches!(access, Access::ReadOnly { .. });
+
+    // Changes a previous session left in the journal are written out whole, and backed up.
+    if !read_only {
+        if let Err(e) = unlocked.compact(&name) {
+            notes.push(format!("Could not write out earlier changes: {}", e));
+        }
+    }
+    let damaged = unlocked.damaged_changes();
+    if damaged > 0 {
+        notes.push(format!(
+            "{} changes could not be read, verify this vault to find out why",
+            damaged
+        ));
+    }
+    let message = (!notes.is_empty()).then(|| notes.join(". "));
 
     let open_vault = OpenVault {
         vault: unlocked,
-        name: vault.name.clone(),
+        name,
+        access,
     };
 
     lock.open_vault = Some(open_vault);
 
-    Ok(VaultResult { success: true, message: None })
+    Ok(VaultResult { success: true, message, read_only })
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn list_backups(
+    state: State<'_, Mutex<VaultCollection>>,
+    id: u32
+) -> Result<Vec<BackupInfo>, String> {
+    let lock = state.lock().await;
+    let surface = lock.vaults
+        .iter()
+        .find(|v| v.id == id)
+        .ok_or_else(|| String::from("No vault of that id found"))?;
+
+    Ok(lock.store.backups(&surface.name))
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn restore_backup(
+    state: State<'_, Mutex<VaultCollection>>,
+    id: u32,
+    backup_id: String,
+    master_password: String
+) -> Result<(), String> {
+    let mut lock = state.lock().await;
+    let vault_name = lock.vaults
+        .iter()
+        .find(|v| v.id == id)
+        .map(|v| v.name.clone())
+        .ok_or_else(|| String::from("No vault of that id found"))?;
+
+    // The vault open here already holds its lock, any other one is locked for the restore.
+    let _access = if lock.open_vault.as_ref().is_some_and(|open| open.name == vault_name) {
+        lock.check_writable()?;
+        None
+    } else {
+        Some(lock_for_change(&*lock.store, &vault_name)?)
+    };
+    let restored = vault::restore_backup(
+        lock.store.clone(),
+        &vault_name,
+        &backup_id,
+        &master_password
+    )?;
+
+    if let Some(open_vault) = lock.open_vault.as_mut().filter(|open| open.name == vault_name) {
+        open_vault.vault = restored;
+    }
+
+    Ok(())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn verify_vault(
+    state: State<'_, Mutex<VaultCollection>>,
+    id: u32,
+    master_password: String
+) -> Result<VerifyReport, String> {
+    let lock = state.lock().await;
+    let surface = lock.vaults
+        .iter()
+        .find(|v| v.id == id)
+        .ok_or_else(|| String::from("No vault of that id found"))?;
+
+    vault::verify_vault(&*lock.store, &surface.name, &master_password)
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn repair_vault(
+    state: State<'_, Mutex<VaultCollection>>,
+    id: u32,
+    master_password: String
+) -> Result<RepairOutcome, String> {
+    let mut lock = state.lock().await;
+    let vault_name = lock.vaults
+        .iter()
+        .find(|v| v.id == id)
+        .map(|v| v.name.clone())
+        .ok_or_else(|| String::from("No vault of that id found"))?;
+
+    let _access = if lock.open_vault.as_ref().is_some_and(|open| open.name == vault_name) {
+        lock.check_writable()?;
+        None
+    } else {
+        Some(lock_for_change(&*lock.store, &vault_name)?)
+    };
+    let (outcome, repaired) = vault::repair_vault(lock.store.clone(), &vault_name, &master_password)?;
+
+    if let Some(open_vault) = lock.open_vault.as_mut().filter(|open| open.name == vault_name) {
+        open_vault.vault = repaired;
+    }
+
+    Ok(outcome)
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_vault_metadata(
+    state: State<'_, Mutex<VaultCollection>>
+) -> Result<MetadataSettings, String> {
+    let lock = state.lock().await;
+    let vault = &lock.open_vault.as_ref().unwrap().vault;
+
+    Ok(vault.metadata_settings())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn set_vault_metadata(
+    state: State<'_, Mutex<VaultCollection>>,
+    settings: MetadataSettings
+) -> Result<(), String> {
+    let mut lock = state.lock().await;
+    lock.check_writable()?;
+    let vault_name = lock.open_vault.as_ref().unwrap().name.clone();
+    let vault = &mut lock.open_vault.as_mut().unwrap().vault;
+
+    vault.set_metadata(settings, &vault_name)
 }
 
 #[tauri::command(rename_all = "snake_case")]
 pub async fn retrieve_password_list(
     state: State<'_, Mutex<VaultCollection>>
-) -> Result<Vec<String>, ()> {
+) -> Result<Vec<EntrySummary>, ()> {
     let lock = state.lock().await;
     let vault = &lock.open_vault.as_ref().unwrap().vault;
 
@@ -173,6 +404,13 @@ pub async fn copy_to_clipboard(
 
     let content = vault.retrieve_password(&password).unwrap();
 
+    write_to_clipboard(&app, content);
+
+    Ok(())
+}
+
+/// Copies a secret and clears the clipboard again after 30 seconds.
+fn write_to_clipboard(app: &tauri::AppHandle, content: String) {
     app.clipboard().write_text(content).unwrap();
 
     let app_handle = app.app_handle().clone();
@@ -181,6 +419,21 @@ pub async fn copy_to_clipboard(
         tokio::time::sleep(time::Duration::from_secs(30)).await;
         app_handle.clipboard().clear().unwrap();
     });
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn copy_entry_field(
+    app: tauri::AppHandle,
+    state: State<'_, Mutex<VaultCollection>>,
+    name: String,
+    field: EntryField
+) -> Result<(), String> {
+    let lock = state.lock().await;
+    let vault = &lock.open_vault.as_ref().unwrap().vault;
+
+    let content = vault.retrieve_field(&name, field).map_err(String::from)?;
+
+    write_to_clipboard(&app, content);
 
     Ok(())
 }
@@ -200,6 +453,7 @@ pub async fn add_password(
     }
 
     let mut lock = state.lock().await;
+    lock.check_writable()?;
     let vault_name = lock.open_vault.as_ref().unwrap().name.clone();
     let vault = &mut lock.open_vault.as_mut().unwrap().vault;
 
@@ -210,52 +464,819 @@ pub async fn add_password(
     Ok(())
 }
 
+/// Generates a password from `policy`. Given a `target` the password is written straight into
+/// that entry and nothing is returned, so it never passes through the webview. An existing
+/// entry's password rule narrows the policy to what its site accepts.
 #[tauri::command(rename_all = "snake_case")]
-pub async fn delete_password(
+pub async fn generate_password(
     state: State<'_, Mutex<VaultCollection>>,
-    name: String
+    policy: Option<PasswordPolicy>,
+    target: Option<GenerateTarget>
+) -> Result<Option<String>, String> {
+    let policy = policy.unwrap_or_default();
+    let Some(target) = target else {
+        return Ok(Some(policy.generate()?));
+    };
+
+    let mut lock = state.lock().await;
+    lock.check_writable()?;
+    let vault_name = lock.open_vault.as_ref().unwrap().name.clone();
+    let vault = &mut lock.open_vault.as_mut().unwrap().vault;
+
+    match target {
+        GenerateTarget::NewEntry { name } => {
+            if name.trim().is_empty() {
+                return Err(String::from("You need a password name"));
+            }
+            let password = policy.generate()?;
+            vault.insert_password(name, password, &vault_name)?;
+        }
+        GenerateTarget::ExistingEntry { name } => {
+            let password = match vault.password_rules(&name)? {
+                Some(rules) => rules.apply(&policy).generate()?,
+                None => policy.generate()?,
+            };
+            vault.update_password(&name, password, &vault_name)?;
+        }
+    }
+
+    Ok(None)
+}
+
+/// Generates a username or email alias. Usernames are not secret so it is always returned, and
+/// given a `target` it is also written into that entry. A new entry is created as a login with
+/// a password from the default policy.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn generate_username(
+    state: State<'_, Mutex<VaultCollection>>,
+    style: Option<UsernameStyle>,
+    target: Option<GenerateTarget>
+) -> Result<String, String> {
+    let username = style.unwrap_or_default().generate()?;
+    let Some(target) = target else {
+        return Ok(username);
+    };
+
+    let mut lock = state.lock().await;
+    lock.check_writable()?;
+    let vault_name = lock.open_vault.as_ref().unwrap().name.clone();
+    let vault = &mut lock.open_vault.as_mut().unwrap().vault;
+
+    match target {
+        GenerateTarget::NewEntry { name } => {
+            if name.trim().is_empty() {
+                return Err(String::from("You need a password name"));
+            }
+            let mut entry = Entry::login(PasswordPolicy::default().generate()?);
+            entry.username = username.clone();
+            vault.insert_entry(name, entry, &vault_name)?;
+        }
+        GenerateTarget::ExistingEntry { name } => {
+            vault.set_username(&name, &username, &vault_name)?;
+        }
+    }
+
+    Ok(username)
+}
+
+/// Stores the site's password rule used when generating a password for the entry, in Apple's
+/// `passwordrules` syntax. Passing nothing removes it.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn set_password_rule(
+    state: State<'_, Mutex<VaultCollection>>,
+    name: String,
+    rule: Option<String>
+) -> Result<(), String> {
+    let mut lock = state.lock().await;
+    lock.check_writable()?;
+    let vault_name = lock.open_vault.as_ref().unwrap().name.clone();
+    let vault = &mut lock.open_vault.as_mut().unwrap().vault;
+
+    vault.set_password_rule(&name, rule, &vault_name)
+}
+
+/// Generates a diceware style passphrase, optionally written straight into an entry like
+/// `generate_password`.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn generate_passphrase(
+    state: State<'_, Mutex<VaultCollection>>,
+    options: Option<PassphraseOptions>,
+    target: Option<GenerateTarget>
+) -> Result<Passphrase, String> {
+    let (passphrase, entropy_bits, word_list_size) = options.unwrap_or_default().generate()?;
+    let Some(target) = target else {
+        return Ok(Passphrase { passphrase: Some(passphrase), entropy_bits, word_list_size });
+    };
+
+    let mut lock = state.lock().await;
+    lock.check_writable()?;
+    let vault_name = lock.open_vault.as_ref().unwrap().name.clone();
+    let vault = &mut lock.open_vault.as_mut().unwrap().vault;
+
+    match target {
+        GenerateTarget::NewEntry { name } => {
+            if name.trim().is_empty() {
+                return Err(String::from("You need a password name"));
+            }
+            vault.insert_password(name, passphrase, &vault_name)?;
+        }
+        GenerateTarget::ExistingEntry { name } => {
+            if vault.password_rules(&name)?.is_some_and(|rules| !rules.check(&passphrase)) {
+                return Err(String::from("A passphrase does not meet this site's password rule"));
+            }
+            vault.update_password(&name, passphrase, &vault_name)?;
+        }
+    }
+
+    Ok(Passphrase { passphrase: None, entropy_bits, word_list_size })
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn update_password(
+    state: State<'_, Mutex<VaultCollection>>,
+    name: String,
+    password: String
 ) -> Result<(), String> {
+    if password.is_empty() {
+        return Err(String::from("You need a password"));
+    }
+
     let mut lock = state.lock().await;
+    lock.check_writable()?;
     let vault_name = lock.open_vault.as_ref().unwrap().name.clone();
     let vault = &mut lock.open_vault.as_mut().unwrap().vault;
 
-    vault.delete_password(name, &vault_name).map_err(|e| String::from(e))?;
+    vault.update_password(&name, password, &vault_name)?;
 
     Ok(())
 }
 
+/// Sets either a fixed expiry date (`YYYY-MM-DD`) or a rotation interval, passing neither
+/// clears it.
 #[tauri::command(rename_all = "snake_case")]
-pub async fn lock_vault(
-    app: tauri::AppHandle,
-    state: tauri::State<'_, Mutex<VaultCollection>>
-) -> Result<(), ()> {
+pub async fn set_entry_expiry(
+    state: State<'_, Mutex<VaultCollection>>,
+    name: String,
+    expires_on: Option<String>,
+    rotate_every_days: Option<u32>
+) -> Result<(), String> {
+    let expiry = match (expires_on, rotate_every_days) {
+        (Some(_), Some(_)) => {
+            return Err(String::from("Choose either an expiry date or a rotation interval"));
+        }
+        (Some(date), None) => Some(Expiry::On(Date::parse(&date).map_err(String::from)?)),
+        (None, Some(0)) => {
+            return Err(String::from("Rotation interval must be at least one day"));
+        }
+        (None, Some(days)) => Some(Expiry::Every { days }),
+        (None, None) => None,
+    };
+
     let mut lock = state.lock().await;
-    app.app_handle().clipboard().clear().unwrap();
-    if let Some(open_vault) = lock.open_vault.take() {
-        open_vault.vault.lock();
-    }
+    lock.check_writable()?;
+    let vault_name = lock.open_vault.as_ref().unwrap().name.clone();
+    let vault = &mut lock.open_vault.as_mut().unwrap().vault;
+
+    vault.set_expiry(&name, expiry, &vault_name)?;
+
     Ok(())
 }
 
 #[tauri::command(rename_all = "snake_case")]
-pub async fn request_delete_vault(
+pub async fn retrieve_expiring_entries(
     state: State<'_, Mutex<VaultCollection>>,
-    vault: u32
-) -> Result<(), ()> {
+    within_days: u32
+) -> Result<Vec<ExpiringEntry>, ()> {
     let lock = state.lock().await;
+    let vault = &lock.open_vault.as_ref().unwrap().vault;
 
-    let vault = lock.vaults.iter().find(|&x| x.id == vault);
+    Ok(vault.list_expiring(within_days))
+}
 
-    match vault {
-        Some(x) => {
-            let name = &x.name;
-            delete_vault(name).unwrap();
-            return Ok(())
-        },
-        None => {
-            return Err(());
-        }
-    }
+#[tauri::command(rename_all = "snake_case")]
+pub async fn update_entry_details(
+    state: State<'_, Mutex<VaultCollection>>,
+    name: String,
+    username: String,
+    urls: Vec<String>,
+    notes: String,
+    tags: Vec<String>
+) -> Result<(), String> {
+    let mut lock = state.lock().await;
+    lock.check_writable()?;
+    let vault_name = lock.open_vault.as_ref().unwrap().name.clone();
+    let vault = &mut lock.open_vault.as_mut().unwrap().vault;
+
+    vault.update_details(&name, &username, urls, &notes, tags, &vault_name)?;
+
+    Ok(())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn set_url_match_mode(
+    state: State<'_, Mutex<VaultCollection>>,
+    name: String,
+    mode: UrlMatch
+) -> Result<(), String> {
+    let mut lock = state.lock().await;
+    lock.check_writable()?;
+    let vault_name = lock.open_vault.as_ref().unwrap().name.clone();
+    let vault = &mut lock.open_vault.as_mut().unwrap().vault;
+
+    vault.set_url_match(&name, mode, &vault_name)?;
+
+    Ok(())
+}
+
+/// Entries that belong to `url` under their own match mode, best match first.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn match_url(
+    state: State<'_, Mutex<VaultCollection>>,
+    url: String
+) -> Result<Vec<UrlMatchHit>, String> {
+    let lock = state.lock().await;
+    let vault = &lock.open_vault.as_ref().unwrap().vault;
+
+    vault.match_url(&url)
+}
+
+/// Fuzzy searches entry titles, usernames, URLs, notes and tags, best match first.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn search_entries(
+    state: State<'_, Mutex<VaultCollection>>,
+    query: String,
+    limit: Option<usize>
+) -> Result<Vec<SearchHit>, ()> {
+    let lock = state.lock().await;
+    let vault = &lock.open_vault.as_ref().unwrap().vault;
+
+    Ok(vault.search_entries(&query, limit.unwrap_or(50)))
+}
+
+/// Scores a password before it is saved. Pass the entry or vault name and username as
+/// `user_inputs` so passwords built from them are caught.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn estimate_password_strength(
+    password: String,
+    user_inputs: Option<Vec<String>>
+) -> Result<Strength, ()> {
+    let user_inputs = user_inputs.unwrap_or_default();
+    let user_inputs: Vec<&str> = user_inputs.iter().map(String::as_str).collect();
+
+    Ok(strength::estimate(&password, &user_inputs))
+}
+
+/// Looks every stored password up in a downloaded Have I Been Pwned SHA-1 or NTLM file
+/// ordered by hash. Nothing is sent over the network.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn check_breaches(
+    state: State<'_, Mutex<VaultCollection>>,
+    hash_file: String
+) -> Result<Vec<BreachedSecret>, String> {
+    let lock = state.lock().await;
+    let vault = &lock.open_vault.as_ref().unwrap().vault;
+
+    vault.check_breaches(Path::new(&hash_file))
+}
+
+/// Reused, weak, old, missing 2FA and duplicate entries for the security dashboard.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn vault_health_report(
+    state: State<'_, Mutex<VaultCollection>>
+) -> Result<HealthReport, ()> {
+    let lock = state.lock().await;
+    let vault = &lock.open_vault.as_ref().unwrap().vault;
+
+    Ok(vault.health_report())
+}
+
+/// Adds a login whose password is recomputed from the master password whenever it is needed.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn add_derived_entry(
+    state: State<'_, Mutex<VaultCollection>>,
+    name: String,
+    profile: DerivedProfile
+) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err(String::from("You need a password name"));
+    }
+    profile.validate()?;
+
+    let mut lock = state.lock().await;
+    lock.check_writable()?;
+    let vault_name = lock.open_vault.as_ref().unwrap().name.clone();
+    let vault = &mut lock.open_vault.as_mut().unwrap().vault;
+
+    vault.insert_entry(name, Entry::derived(profile), &vault_name)
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn copy_derived_password(
+    app: tauri::AppHandle,
+    state: State<'_, Mutex<VaultCollection>>,
+    name: String,
+    master_password: String
+) -> Result<(), String> {
+    let lock = state.lock().await;
+    let vault = &lock.open_vault.as_ref().unwrap().vault;
+
+    let content = vault.derive_password(&name, &master_password)?;
+
+    write_to_clipboard(&app, content);
+
+    Ok(())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn rotate_derived_password(
+    state: State<'_, Mutex<VaultCollection>>,
+    name: String
+) -> Result<u32, String> {
+    let mut lock = state.lock().await;
+    lock.check_writable()?;
+    let vault_name = lock.open_vault.as_ref().unwrap().name.clone();
+    let vault = &mut lock.open_vault.as_mut().unwrap().vault;
+
+    vault.rotate_derived_password(&name, &vault_name)
+}
+
+/// Recomputes a derived password from a profile the user types in, with no vault open. This is
+/// the way back to a site's password after the vault file is lost.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn derive_site_password(
+    app: tauri::AppHandle,
+    profile: DerivedProfile,
+    master_password: String
+) -> Result<(), String> {
+    let content = profile.derive(&master_password)?;
+
+    write_to_clipboard(&app, content);
+
+    Ok(())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn add_secure_note(
+    state: State<'_, Mutex<VaultCollection>>,
+    name: String,
+    text: String
+) -> Result<(), String> {
+    if name.is_empty() {
+        return Err(String::from("You need a note name"));
+    }
+
+    let mut lock = state.lock().await;
+    lock.check_writable()?;
+    let vault_name = lock.open_vault.as_ref().unwrap().name.clone();
+    let vault = &mut lock.open_vault.as_mut().unwrap().vault;
+
+    vault
+        .insert_entry(name, Entry::secure_note(text), &vault_name)?;
+
+    Ok(())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn update_secure_note(
+    state: State<'_, Mutex<VaultCollection>>,
+    name: String,
+    text: String
+) -> Result<(), String> {
+    let mut lock = state.lock().await;
+    lock.check_writable()?;
+    let vault_name = lock.open_vault.as_ref().unwrap().name.clone();
+    let vault = &mut lock.open_vault.as_mut().unwrap().vault;
+
+    vault.update_secure_note(&name, text, &vault_name)?;
+
+    Ok(())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn reveal_secure_note(
+    state: State<'_, Mutex<VaultCollection>>,
+    name: String
+) -> Result<String, String> {
+    let lock = state.lock().await;
+    let vault = &lock.open_vault.as_ref().unwrap().vault;
+
+    vault.retrieve_secure_note(&name).map_err(String::from)
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn add_payment_card(
+    state: State<'_, Mutex<VaultCollection>>,
+    name: String,
+    number: String,
+    holder: String,
+    expiry: String,
+    cvv: String,
+    pin: Option<String>
+) -> Result<(), String> {
+    if name.is_empty() {
+        return Err(String::from("You need a card name"));
+    }
+
+    let card = PaymentCard::new(&number, &holder, &expiry, &cvv, pin.as_deref()).map_err(
+        String::from
+    )?;
+
+    let mut lock = state.lock().await;
+    lock.check_writable()?;
+    let vault_name = lock.open_vault.as_ref().unwrap().name.clone();
+    let vault = &mut lock.open_vault.as_mut().unwrap().vault;
+
+    vault.insert_entry(name, Entry::payment_card(card), &vault_name)?;
+
+    Ok(())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn add_identity(
+    state: State<'_, Mutex<VaultCollection>>,
+    name: String,
+    full_name: String,
+    address: Option<String>,
+    phone: Option<String>,
+    passport_number: Option<String>,
+    licence_number: Option<String>
+) -> Result<(), String> {
+    if name.is_empty() {
+        return Err(String::from("You need an identity name"));
+    }
+
+    let identity = Identity::new(
+        &full_name,
+        address.as_deref(),
+        phone.as_deref(),
+        passport_number.as_deref(),
+        licence_number.as_deref()
+    ).map_err(String::from)?;
+
+    let mut lock = state.lock().await;
+    lock.check_writable()?;
+    let vault_name = lock.open_vault.as_ref().unwrap().name.clone();
+    let vault = &mut lock.open_vault.as_mut().unwrap().vault;
+
+    vault.insert_entry(name, Entry::identity(identity), &vault_name)?;
+
+    Ok(())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn generate_ssh_key(
+    state: State<'_, Mutex<VaultCollection>>,
+    name: String,
+    comment: String
+) -> Result<String, String> {
+    if name.is_empty() {
+        return Err(String::from("You need a key name"));
+    }
+
+    let key = SshKey::generate_ed25519(&comment)?;
+    let public_key = key.public_key.clone();
+
+    let mut lock = state.lock().await;
+    lock.check_writable()?;
+    let vault_name = lock.open_vault.as_ref().unwrap().name.clone();
+    let vault = &mut lock.open_vault.as_mut().unwrap().vault;
+
+    vault.insert_entry(name, Entry::ssh_key(key), &vault_name)?;
+
+    Ok(public_key)
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn import_ssh_key(
+    state: State<'_, Mutex<VaultCollection>>,
+    name: String,
+    path: String,
+    passphrase: Option<String>
+) -> Result<String, String> {
+    if name.is_empty() {
+        return Err(String::from("You need a key name"));
+    }
+
+    let pem = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    let key = SshKey::import_openssh(&pem, passphrase.as_deref())?;
+    let public_key = key.public_key.clone();
+
+    let mut lock = state.lock().await;
+    lock.check_writable()?;
+    let vault_name = lock.open_vault.as_ref().unwrap().name.clone();
+    let vault = &mut lock.open_vault.as_mut().unwrap().vault;
+
+    vault.insert_entry(name, Entry::ssh_key(key), &vault_name)?;
+
+    Ok(public_key)
+}
+
+/// Public keys are not secret, so they are handed to the frontend and optionally written out
+/// as a `.pub` file.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn export_ssh_public_key(
+    state: State<'_, Mutex<VaultCollection>>,
+    name: String,
+    path: Option<String>
+) -> Result<String, String> {
+    let lock = state.lock().await;
+    let vault = &lock.open_vault.as_ref().unwrap().vault;
+
+    let public_key = vault.retrieve_field(&name, EntryField::PublicKey).map_err(String::from)?;
+
+    if let Some(path) = path {
+        fs::write(path, format!("{}\n", public_key)).map_err(|e| e.to_string())?;
+    }
+
+    Ok(public_key)
+}
+
+/// Software authenticator registration, binary arguments are base64url encoded.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn register_passkey(
+    state: State<'_, Mutex<VaultCollection>>,
+    name: String,
+    rp_id: String,
+    user_handle: String,
+    user_name: String,
+    client_data_hash: String,
+    user_verified: bool
+) -> Result<Registration, String> {
+    if name.is_empty() {
+        return Err(String::from("You need a passkey name"));
+    }
+
+    let (passkey, registration) = Passkey::register(
+        &rp_id,
+        decode_b64(&user_handle)?,
+        &user_name,
+        &decode_b64(&client_data_hash)?,
+        user_verified
+    )?;
+
+    let mut lock = state.lock().await;
+    lock.check_writable()?;
+    let vault_name = lock.open_vault.as_ref().unwrap().name.clone();
+    let vault = &mut lock.open_vault.as_mut().unwrap().vault;
+
+    vault.insert_entry(name, Entry::passkey(passkey), &vault_name)?;
+
+    Ok(registration)
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn list_passkeys(
+    state: State<'_, Mutex<VaultCollection>>,
+    rp_id: Option<String>
+) -> Result<Vec<PasskeySummary>, ()> {
+    let lock = state.lock().await;
+    let vault = &lock.open_vault.as_ref().unwrap().vault;
+
+    Ok(vault.list_passkeys(rp_id.as_deref()))
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn sign_passkey_assertion(
+    state: State<'_, Mutex<VaultCollection>>,
+    name: String,
+    rp_id: String,
+    client_data_hash: String,
+    user_verified: bool
+) -> Result<Assertion, String> {
+    let client_data_hash = decode_b64(&client_data_hash)?;
+
+    let mut lock = state.lock().await;
+    lock.check_writable()?;
+    let vault_name = lock.open_vault.as_ref().unwrap().name.clone();
+    let vault = &mut lock.open_vault.as_mut().unwrap().vault;
+
+    vault.sign_with_passkey(&name, &rp_id, &client_data_hash, user_verified, &vault_name)
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn list_templates(
+    state: State<'_, Mutex<VaultCollection>>
+) -> Result<Vec<Template>, ()> {
+    let lock = state.lock().await;
+    let vault = &lock.open_vault.as_ref().unwrap().vault;
+
+    Ok(vault.list_templates())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn save_template(
+    state: State<'_, Mutex<VaultCollection>>,
+    template_name: String,
+    fields: Vec<TemplateField>
+) -> Result<(), String> {
+    let template = Template::new(&template_name, fields)?;
+
+    let mut lock = state.lock().await;
+    lock.check_writable()?;
+    let vault_name = lock.open_vault.as_ref().unwrap().name.clone();
+    let vault = &mut lock.open_vault.as_mut().unwrap().vault;
+
+    vault.save_template(template, &vault_name)
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn delete_template(
+    state: State<'_, Mutex<VaultCollection>>,
+    template_name: String
+) -> Result<(), String> {
+    let mut lock = state.lock().await;
+    lock.check_writable()?;
+    let vault_name = lock.open_vault.as_ref().unwrap().name.clone();
+    let vault = &mut lock.open_vault.as_mut().unwrap().vault;
+
+    vault.delete_template(&template_name, &vault_name)
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn add_entry_from_template(
+    state: State<'_, Mutex<VaultCollection>>,
+    name: String,
+    template_name: String,
+    values: HashMap<String, String>
+) -> Result<(), String> {
+    if name.is_empty() {
+        return Err(String::from("You need an entry name"));
+    }
+
+    let mut lock = state.lock().await;
+    lock.check_writable()?;
+    let vault_name = lock.open_vault.as_ref().unwrap().name.clone();
+    let vault = &mut lock.open_vault.as_mut().unwrap().vault;
+
+    vault.insert_from_template(name, &template_name, values, &vault_name)
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn update_template_entry(
+    state: State<'_, Mutex<VaultCollection>>,
+    name: String,
+    values: HashMap<String, String>
+) -> Result<(), String> {
+    let mut lock = state.lock().await;
+    lock.check_writable()?;
+    let vault_name = lock.open_vault.as_ref().unwrap().name.clone();
+    let vault = &mut lock.open_vault.as_mut().unwrap().vault;
+
+    vault.update_template_entry(&name, values, &vault_name)
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn view_template_entry(
+    state: State<'_, Mutex<VaultCollection>>,
+    name: String
+) -> Result<Vec<CustomFieldView>, String> {
+    let lock = state.lock().await;
+    let vault = &lock.open_vault.as_ref().unwrap().vault;
+
+    vault.view_template_entry(&name).map_err(String::from)
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn copy_template_field(
+    app: tauri::AppHandle,
+    state: State<'_, Mutex<VaultCollection>>,
+    name: String,
+    field: String
+) -> Result<(), String> {
+    let lock = state.lock().await;
+    let vault = &lock.open_vault.as_ref().unwrap().vault;
+
+    let content = vault.retrieve_custom_field(&name, &field).map_err(String::from)?;
+
+    write_to_clipboard(&app, content);
+
+    Ok(())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn retrieve_entry_warnings(
+    state: State<'_, Mutex<VaultCollection>>
+) -> Result<Vec<EntryWarning>, ()> {
+    let lock = state.lock().await;
+    let vault = &lock.open_vault.as_ref().unwrap().vault;
+
+    Ok(vault.list_warnings())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn list_attachments(
+    state: State<'_, Mutex<VaultCollection>>,
+    name: String
+) -> Result<Vec<Attachment>, String> {
+    let lock = state.lock().await;
+    let vault = &lock.open_vault.as_ref().unwrap().vault;
+
+    vault.list_attachments(&name).map_err(String::from)
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn attach_file(
+    state: State<'_, Mutex<VaultCollection>>,
+    name: String,
+    path: String
+) -> Result<Attachment, String> {
+    let mut lock = state.lock().await;
+    lock.check_writable()?;
+    let vault_name = lock.open_vault.as_ref().unwrap().name.clone();
+    let vault = &mut lock.open_vault.as_mut().unwrap().vault;
+
+    vault.attach_file(&name, Path::new(&path), &vault_name)
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn export_attachment(
+    state: State<'_, Mutex<VaultCollection>>,
+    name: String,
+    attachment_id: String,
+    path: String
+) -> Result<(), String> {
+    let lock = state.lock().await;
+    let open_vault = lock.open_vault.as_ref().unwrap();
+
+    open_vault.vault.export_attachment(&name, &attachment_id, Path::new(&path), &open_vault.name)
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn delete_attachment(
+    state: State<'_, Mutex<VaultCollection>>,
+    name: String,
+    attachment_id: String
+) -> Result<(), String> {
+    let mut lock = state.lock().await;
+    lock.check_writable()?;
+    let vault_name = lock.open_vault.as_ref().unwrap().name.clone();
+    let vault = &mut lock.open_vault.as_mut().unwrap().vault;
+
+    vault.delete_attachment(&name, &attachment_id, &vault_name)
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn delete_password(
+    state: State<'_, Mutex<VaultCollection>>,
+    name: String
+) -> Result<(), String> {
+    let mut lock = state.lock().await;
+    lock.check_writable()?;
+    let vault_name = lock.open_vault.as_ref().unwrap().name.clone();
+    let vault = &mut lock.open_vault.as_mut().unwrap().vault;
+
+    vault.delete_password(name, &vault_name)?;
+
+    Ok(())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn lock_vault(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, Mutex<VaultCollection>>
+) -> Result<Option<String>, ()> {
+    let mut lock = state.lock().await;
+    app.app_handle().clipboard().clear().unwrap();
+
+    // The vault is locked either way, a journal that can't be written out is replayed on the
+    // next open.
+    let mut warning = None;
+    if let Some(mut open_vault) = lock.open_vault.take() {
+        if let Access::Writable { .. } = open_vault.access {
+            if let Err(e) = open_vault.vault.compact(&open_vault.name) {
+                warning = Some(format!("Your latest changes are saved but not backed up: {}", e));
+            }
+        }
+        open_vault.vault.lock();
+    }
+    Ok(warning)
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn request_delete_vault(
+    state: State<'_, Mutex<VaultCollection>>,
+    vault: u32
+) -> Result<(), Error> {
+    let mut lock = state.lock().await;
+
+    let Some(name) = lock.vaults.iter().find(|&x| x.id == vault).map(|x| x.name.clone()) else {
+        return Err(String::from("No vault of that name found"));
+    };
+
+    // The lock this window holds on its open vault covers the delete, the vault is closed first.
+    let _access = match lock.open_vault.take_if(|open| open.name == name) {
+        Some(open_vault) => {
+            if let Access::ReadOnly { holder } = &open_vault.access {
+                let holder = format!("{} has this vault open", holder);
+                lock.open_vault = Some(open_vault);
+                return Err(holder);
+            }
+            open_vault.vault.lock();
+            open_vault.access
+        }
+        None => lock_for_change(&*lock.store, &name)?,
+    };
+    delete_vault(&*lock.store, &name)
 }
 
 #[tauri::command(rename_all = "snake_case")]
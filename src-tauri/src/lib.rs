@@ -1,5 +1,7 @@
 pub mod commands;
 pub mod set_up;
+pub mod storage;
+pub mod store;
 pub mod vault;
 use tauri::async_runtime::Mutex;
 
@@ -13,27 +15,75 @@ fn greet(name: &str) -> String {
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    // Settles which folder vaults are kept in, so it has to come before opening the store.
+    let notices = set_up::set_up();
+    let store = store::store::open(storage::store_kind()).expect("Failed to open the vault store");
+
     tauri::Builder::default()
         .plugin(tauri_plugin_clipboard_manager::init())
-        .manage(Mutex::new(VaultCollection::default()))
-        .setup(|_| {
-            set_up::set_up();
-
-            Ok(())
-        })
+        .manage(Mutex::new(VaultCollection::new(store, notices)))
         .plugin(tauri_plugin_opener::init())
         .invoke_handler(tauri::generate_handler![
             greet,
             request_vaults,
+            request_notices,
             create_vault,
             open_vault,
+            list_backups,
+            restore_backup,
+            verify_vault,
+            repair_vault,
+            get_vault_metadata,
+            set_vault_metadata,
             retrieve_password_list,
             copy_to_clipboard,
             add_password,
+            update_password,
+            set_entry_expiry,
+            retrieve_expiring_entries,
+            update_entry_details,
+            search_entries,
+            set_url_match_mode,
+            match_url,
+            estimate_password_strength,
+            check_breaches,
+            vault_health_report,
+            add_secure_note,
+            update_secure_note,
+            reveal_secure_note,
+            add_payment_card,
+            add_identity,
+            copy_entry_field,
+            retrieve_entry_warnings,
+            generate_ssh_key,
+            import_ssh_key,
+            export_ssh_public_key,
+            register_passkey,
+            list_passkeys,
+            sign_passkey_assertion,
+            list_templates,
+            save_template,
+            delete_template,
+            add_entry_from_template,
+            update_template_entry,
+            view_template_entry,
+            copy_template_field,
+            list_attachments,
+            attach_file,
+            export_attachment,
+            delete_attachment,
             lock_vault,
             delete_password,
             request_delete_vault,
             five_number_rng,
+            generate_password,
+            generate_passphrase,
+            generate_username,
+            set_password_rule,
+            add_derived_entry,
+            copy_derived_password,
+            rotate_derived_password,
+            derive_site_password,
             clear_clipboard
         ])
         .run(tauri::generate_context!())
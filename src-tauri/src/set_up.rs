@@ -1,15 +1,8 @@
-use std::env;
-use std::fs;
-use std::path::PathBuf;
+use crate::storage;
 
-pub fn set_up() {
-    let appdata = env::var("APPDATA").or_else(|_| env::var("HOME")).unwrap();
-
-    let folder_path: PathBuf = [appdata, "Ancrypt".to_string(), "Vaults".to_string()]
-        .iter()
-        .collect();
-
-    if !folder_path.exists() {
-        fs::create_dir_all(&folder_path).expect("Failed to create folder")
-    }
+/// Returns what the user should be told about the vaults moved on the way.
+pub fn set_up() -> Vec<String> {
+    let notices = storage::migrate_legacy_vaults();
+    storage::ensure_vault_dir().expect("Failed to create folder");
+    notices
 }
@@ -0,0 +1,496 @@
+use std::env;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+use crate::store::store::StoreKind;
+use crate::vault::vault::is_vault_file;
+
+/// Points at a different vault folder, taking priority over the config file.
+pub const VAULT_DIR_VAR: &str = "ANCRYPT_VAULT_DIR";
+pub const VAULT_EXTENSION: &str = "ANCRYPT";
+
+// Linux follows the XDG convention of lowercase application folders, Windows keeps the folder
+// earlier versions created under %APPDATA%.
+#[cfg(target_os = "linux")]
+const APP_FOLDER: &str = "ancrypt";
+#[cfg(target_os = "linux")]
+const VAULT_FOLDER: &str = "vaults";
+#[cfg(not(target_os = "linux"))]
+const APP_FOLDER: &str = "Ancrypt";
+#[cfg(not(target_os = "linux"))]
+const VAULT_FOLDER: &str = "Vaults";
+
+const CONFIG_FILE: &str = "config.json";
+const DATABASE_FILE: &str = "vaults.sqlite3";
+
+/// How many backups are kept: the newest `keep_last` saves, plus the newest save of each day
+/// for `keep_days` days.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct BackupPolicy {
+    pub keep_last: usize,
+    pub keep_days: u64,
+}
+
+impl Default for BackupPolicy {
+    fn default() -> Self {
+        BackupPolicy {
+            keep_last: 10,
+            keep_days: 30,
+        }
+    }
+}
+
+/// Optional settings read from `config.json` in the platform's config folder, e.g.
+/// `{ "vault_dir": "/mnt/secure/vaults", "store": "sqlite", "backups": { "keep_last": 5 } }`.
+/// A relative `vault_dir` is taken from the config folder.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct StorageConfig {
+    vault_dir: Option<PathBuf>,
+    store: StoreKind,
+    backups: BackupPolicy,
+}
+
+/// Where `config.json` is looked for: `$XDG_CONFIG_HOME/ancrypt` on Linux, `Application
+/// Support/Ancrypt` on macOS and `%APPDATA%\Ancrypt` on Windows.
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(APP_FOLDER))
+}
+
+/// The config file, read once per run. A missing or malformed file leaves every default, as
+/// do tests, which never read the user's settings.
+fn config() -> &'static StorageConfig {
+    static CONFIG: OnceLock<StorageConfig> = OnceLock::new();
+    CONFIG.get_or_init(|| {
+        if cfg!(test) {
+            return StorageConfig::default();
+        }
+        config_dir()
+            .and_then(|dir| fs::read_to_string(dir.join(CONFIG_FILE)).ok())
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    })
+}
+
+pub fn backup_policy() -> &'static BackupPolicy {
+    &config().backups
+}
+
+pub fn store_kind() -> StoreKind {
+    config().store
+}
+
+static VAULT_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+fn resolve_vault_dir() -> PathBuf {
+    if cfg!(test) {
+        return test_vault_dir();
+    }
+    vault_dir_from(
+        env::var_os(VAULT_DIR_VAR),
+        config(),
+        config_dir(),
+        dirs::data_dir(),
+    )
+}
+
+/// The environment variable wins over `vault_dir` in the config file, which wins over the
+/// platform's data folder.
+fn vault_dir_from(
+    env_dir: Option<OsString>,
+    config: &StorageConfig,
+    config_dir: Option<PathBuf>,
+    data_dir: Option<PathBuf>,
+) -> PathBuf {
+    if let Some(dir) = env_dir.filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(dir);
+    }
+    if let (Some(dir), Some(config_dir)) = (&config.vault_dir, config_dir) {
+        return config_dir.join(dir);
+    }
+
+    // Only reached without a home folder, where the working directory is all there is.
+    let data_dir = data_dir.unwrap_or_else(|| PathBuf::from("."));
+    data_dir.join(APP_FOLDER).join(VAULT_FOLDER)
+}
+
+/// The folder holding every vault file, resolved once per run.
+pub fn vault_dir() -> &'static Path {
+    VAULT_DIR.get_or_init(resolve_vault_dir)
+}
+
+pub fn ensure_vault_dir() -> Result<&'static Path, String> {
+    let dir = vault_dir();
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    Ok(dir)
+}
+
+pub fn vault_path(vault_name: &str) -> PathBuf {
+    vault_dir().join(format!("{}.{}", vault_name, VAULT_EXTENSION))
+}
+
+/// Attachment blobs live in a folder beside the vault file they belong to.
+pub fn attachment_dir(vault_name: &str) -> PathBuf {
+    vault_path(vault_name).with_extension("attachments")
+}
+
+/// The database the SQLite store keeps every vault in.
+pub fn database_path() -> PathBuf {
+    vault_dir().join(DATABASE_FILE)
+}
+
+/// Changes made since the vault file was last written, see `vault::journal`.
+pub fn journal_path(vault_name: &str) -> PathBuf {
+    vault_path(vault_name).with_extension("journal")
+}
+
+/// Held by the process that has the vault open for writing.
+pub fn lock_path(vault_name: &str) -> PathBuf {
+    vault_path(vault_name).with_extension("lock")
+}
+
+/// Earlier versions of a vault, one file per save named by when it was made.
+pub fn backup_dir(vault_name: &str) -> PathBuf {
+    vault_path(vault_name).with_extension("backups")
+}
+
+/// Where a new version of `path` is written before it replaces the old one.
+pub fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+/// Makes files created or renamed in `dir` survive a crash.
+#[cfg(unix)]
+pub fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+// Windows can't open a folder as a file, NTFS journals the rename itself.
+#[cfg(not(unix))]
+pub fn sync_dir(_: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Replaces `path` so that a crash leaves either the old or the new contents, never a mix.
+/// The data is flushed to disk before the rename and the rename before returning.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    replace_with(path, |file| file.write_all(contents))
+}
+
+/// Like `write_atomic` for contents too large to hold at once, `write` fills the new file.
+pub fn replace_with<T>(
+    path: &Path,
+    write: impl FnOnce(&mut File) -> io::Result<T>,
+) -> io::Result<T> {
+    let temp = temp_path(path);
+    let written = File::create(&temp).and_then(|mut file| {
+        let value = write(&mut file)?;
+        file.sync_all()?;
+        Ok(value)
+    });
+    let value = match written.and_then(|value| fs::rename(&temp, path).map(|_| value)) {
+        Ok(value) => value,
+        Err(e) => {
+            let _ = fs::remove_file(&temp);
+            return Err(e);
+        }
+    };
+
+    sync_dir(path.parent().unwrap_or(Path::new(".")))?;
+    Ok(value)
+}
+
+/// Names of the vaults in the vault folder, sorted.
+pub fn list_vaults() -> Result<Vec<String>, String> {
+    let mut names = vec![];
+    for entry in fs::read_dir(ensure_vault_dir()?)
+        .map_err(|e| e.to_string())?
+        .flatten()
+    {
+        let path = entry.path();
+        if !path.is_file() || path.extension().is_none_or(|e| e != VAULT_EXTENSION) {
+            continue;
+        }
+        if let Some(name) = path.file_stem().and_then(|n| n.to_str()) {
+            names.push(name.to_string());
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Moves vaults out of where earlier versions kept them and returns what the user should be
+/// told about it. Has to run before anything asks for `vault_dir`.
+///
+/// `$HOME/Ancrypt/Vaults` is where they were looked for on systems without %APPDATA%, its
+/// vaults are moved unless one of the same name already exists. When they can't be moved they
+/// are left where they were and that folder stays the vault folder for this run. Those systems
+/// actually saved every vault to `Vault.pass` in the working directory, which is moved in as
+/// the vault named `Vault`.
+pub fn migrate_legacy_vaults() -> Vec<String> {
+    let mut notes = vec![];
+    let target = resolve_vault_dir();
+    let legacy = dirs::home_dir().map(|home| home.join("Ancrypt").join("Vaults"));
+
+    if let Some(legacy) = legacy.filter(|legacy| legacy.is_dir() && *legacy != target) {
+        let moved = fs::create_dir_all(&target)
+            .map_err(|e| e.to_string())
+            .and_then(|_| move_vaults(&legacy, &target));
+        match moved {
+            Ok(left) => notes.extend(left.iter().map(|path| {
+                format!(
+                    "{} was left where it is, a vault of that name is already in {}",
+                    path.display(),
+                    target.display()
+                )
+            })),
+            Err(e) => {
+                notes.push(format!(
+                    "Could not move your vaults to {}, they are used from {} for now: {}",
+                    target.display(),
+                    legacy.display(),
+                    e
+                ));
+                let _ = VAULT_DIR.set(legacy);
+            }
+        }
+    }
+
+    let sources = [env::current_dir().ok(), dirs::home_dir()];
+    let mut sources: Vec<PathBuf> = sources
+        .into_iter()
+        .flatten()
+        .map(|dir| dir.join(LEGACY_FILE))
+        .collect();
+    sources.dedup();
+    match ensure_vault_dir() {
+        Ok(target) => notes.extend(move_vault_pass(&sources, target)),
+        Err(e) => notes.push(format!("Could not create {}: {}", vault_dir().display(), e)),
+    }
+    notes
+}
+
+/// The single vault file earlier versions wrote on systems without %APPDATA%, relative to
+/// wherever the app was started.
+const LEGACY_FILE: &str = "Vault.pass";
+const LEGACY_NAME: &str = "Vault";
+
+/// Moves each `Vault.pass` in `sources` into `target` as the vault named `Vault`. Whatever
+/// can't be moved is left in place and reported.
+fn move_vault_pass(sources: &[PathBuf], target: &Path) -> Vec<String> {
+    let mut notes = vec![];
+    for from in sources.iter().filter(|path| path.is_file()) {
+        let to = target.join(format!("{}.{}", LEGACY_NAME, VAULT_EXTENSION));
+        let note = if !fs::read(from).is_ok_and(|file| is_vault_file(&file)) {
+            format!(
+                "{} could not be read as a vault and was left where it is",
+                from.display()
+            )
+        } else if to.exists() {
+            format!(
+                "{} was left where it is, a vault named {} already exists",
+                from.display(),
+                LEGACY_NAME
+            )
+        } else {
+            match fs::rename(from, &to) {
+                Ok(()) => format!(
+                    "Your vault from {} is now the vault named {}",
+                    from.display(),
+                    LEGACY_NAME
+                ),
+                Err(e) => format!("Could not move {}: {}", from.display(), e),
+            }
+        };
+        notes.push(note);
+    }
+    notes
+}
+
+/// Moves the vault files in `source` to `target`, leaving anything else behind, and returns the
+/// vaults that stayed because `target` already has one of the same name. Either every other
+/// vault is moved or, after putting back the ones that were, none are.
+fn move_vaults(source: &Path, target: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut moved = vec![];
+    let mut left = vec![];
+    for entry in fs::read_dir(source).map_err(|e| e.to_string())?.flatten() {
+        let from = entry.path();
+        if !from.is_file() || from.extension().is_none_or(|e| e != VAULT_EXTENSION) {
+            continue;
+        }
+        let Some(file_name) = from.file_name() else {
+            continue;
+        };
+        let to = target.join(file_name);
+        if to.exists() {
+            left.push(from);
+            continue;
+        }
+
+        if let Err(e) = fs::rename(&from, &to) {
+            for (from, to) in moved.iter().rev() {
+                let _ = fs::rename(to, from);
+            }
+            return Err(format!("Could not move {}: {}", from.display(), e));
+        }
+        moved.push((from, to));
+    }
+    Ok(left)
+}
+
+/// The vault folder of a test run, a fresh temporary folder so tests never touch the user's
+/// vaults whichever of them asks for it first.
+fn test_vault_dir() -> PathBuf {
+    let dir = env::temp_dir().join(format!("ancrypt-vaults-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_config(contents: &str) -> StorageConfig {
+        serde_json::from_str(contents).unwrap()
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("ancrypt-storage-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn environment_variable_overrides_the_config_file() {
+        let config = parse_config(r#"{ "vault_dir": "/from/config" }"#);
+        let dir = vault_dir_from(
+            Some(OsString::from("/from/env")),
+            &config,
+            Some(PathBuf::from("/config")),
+            Some(PathBuf::from("/data")),
+        );
+        assert_eq!(dir, PathBuf::from("/from/env"));
+
+        let dir = vault_dir_from(
+            Some(OsString::new()),
+            &config,
+            Some(PathBuf::from("/config")),
+            Some(PathBuf::from("/data")),
+        );
+        assert_eq!(dir, PathBuf::from("/from/config"));
+    }
+
+    #[test]
+    fn config_file_paths_are_taken_from_the_config_folder() {
+        let resolve = |contents| {
+            vault_dir_from(
+                None,
+                &parse_config(contents),
+                Some(PathBuf::from("/config/ancrypt")),
+                Some(PathBuf::from("/data")),
+            )
+        };
+
+        assert_eq!(
+            resolve(r#"{ "vault_dir": "vaults" }"#),
+            PathBuf::from("/config/ancrypt/vaults")
+        );
+        assert_eq!(
+            resolve(r#"{ "vault_dir": "/mnt/secure" }"#),
+            PathBuf::from("/mnt/secure")
+        );
+        assert_eq!(
+            resolve("{}"),
+            PathBuf::from("/data").join(APP_FOLDER).join(VAULT_FOLDER)
+        );
+    }
+
+    #[test]
+    fn config_file_sets_the_store_and_backups() {
+        let config = parse_config(r#"{ "store": "sqlite", "backups": { "keep_last": 5 } }"#);
+        assert_eq!(config.store, StoreKind::Sqlite);
+        assert_eq!(config.backups.keep_last, 5);
+        assert_eq!(config.backups.keep_days, BackupPolicy::default().keep_days);
+
+        let config = parse_config("{}");
+        assert_eq!(config.store, StoreKind::Files);
+        assert!(config.vault_dir.is_none());
+    }
+
+    #[test]
+    fn migration_only_moves_vault_files() {
+        let legacy = temp_dir("legacy");
+        let target = temp_dir("target");
+        fs::write(legacy.join("personal.ANCRYPT"), "old").unwrap();
+        fs::write(legacy.join("work.ANCRYPT"), "old").unwrap();
+        fs::write(legacy.join("notes.txt"), "keep").unwrap();
+        fs::write(target.join("work.ANCRYPT"), "new").unwrap();
+
+        let left = move_vaults(&legacy, &target).unwrap();
+        assert_eq!(left, [legacy.join("work.ANCRYPT")]);
+        assert_eq!(fs::read(target.join("personal.ANCRYPT")).unwrap(), b"old");
+        assert_eq!(fs::read(target.join("work.ANCRYPT")).unwrap(), b"new");
+        assert!(legacy.join("work.ANCRYPT").exists());
+        assert!(legacy.join("notes.txt").exists());
+        assert!(!target.join("notes.txt").exists());
+
+        fs::remove_dir_all(&legacy).unwrap();
+        fs::remove_dir_all(&target).unwrap();
+    }
+
+    #[test]
+    fn vault_pass_becomes_the_vault_named_vault() {
+        use crate::store::memory::MemoryStore;
+        use crate::store::store::VaultStore;
+        use crate::vault::vault::Vault;
+
+        let store = std::sync::Arc::new(MemoryStore::default());
+        Vault::new(store.clone())
+            .create_new("master password", "old")
+            .unwrap();
+        let vault = store.read("old").unwrap().unwrap();
+
+        let started_in = temp_dir("started-in");
+        let home = temp_dir("home");
+        let target = temp_dir("vault-pass-target");
+        let empty = temp_dir("empty");
+        fs::write(started_in.join(LEGACY_FILE), &vault).unwrap();
+        fs::write(home.join(LEGACY_FILE), b"not a vault").unwrap();
+        let sources = [
+            started_in.join(LEGACY_FILE),
+            home.join(LEGACY_FILE),
+            empty.join(LEGACY_FILE),
+        ];
+
+        let notes = move_vault_pass(&sources, &target);
+        assert_eq!(notes.len(), 2);
+        assert!(notes[1].contains("could not be read as a vault"));
+        assert_eq!(fs::read(target.join("Vault.ANCRYPT")).unwrap(), vault);
+        assert!(!started_in.join(LEGACY_FILE).exists());
+        assert!(home.join(LEGACY_FILE).exists());
+
+        fs::write(started_in.join(LEGACY_FILE), &vault).unwrap();
+        let notes = move_vault_pass(&sources[..1], &target);
+        assert!(notes[0].contains("already exists"));
+        assert!(started_in.join(LEGACY_FILE).exists());
+
+        for dir in [started_in, home, target, empty] {
+            fs::remove_dir_all(dir).unwrap();
+        }
+    }
+
+    #[test]
+    fn tests_never_use_the_users_vault_folder() {
+        assert!(vault_dir().starts_with(env::temp_dir()));
+        assert!(config().vault_dir.is_none());
+    }
+}
@@ -0,0 +1,276 @@
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+
+use crate::storage;
+use crate::store::store::VaultStore;
+use crate::vault::backup::{self, BackupInfo};
+use crate::vault::lock::{self, Access};
+use crate::vault::vault::is_vault_file;
+
+/// One `{vault}.ANCRYPT` file per vault in the vault folder, with its journal, backups and lock
+/// file beside it. Every `write` adds a backup, pruned with the configured policy.
+#[derive(Debug, Default)]
+pub struct FileStore;
+
+fn decodes(file: Result<Vec<u8>, std::io::Error>) -> bool {
+    file.is_ok_and(|file| is_vault_file(&file))
+}
+
+/// Settles a save that stopped before its rename. The save never finished, so the temporary
+/// file is only kept when the vault itself is missing or unreadable and the temporary file
+/// decodes, otherwise it is thrown away. Like `truncate_torn_append` this is only for whoever
+/// holds the lock, anyone else could be looking at a save still being written.
+fn recover_interrupted_save(vault_name: &str) {
+    let path = storage::vault_path(vault_name);
+    let temp = storage::temp_path(&path);
+    if !temp.exists() {
+        return;
+    }
+
+    if !decodes(fs::read(&path)) && decodes(fs::read(&temp)) {
+        let _ = fs::rename(&temp, &path);
+    } else {
+        let _ = fs::remove_file(&temp);
+    }
+}
+
+/// Journal records are stored one after another, each behind its length as a little endian
+/// `u32`. Returns the complete records and how many bytes they take up, anything after that
+/// is an append that never finished.
+fn frames(journal: &[u8]) -> (Vec<Vec<u8>>, usize) {
+    let mut records = vec![];
+    let mut end = 0;
+    while let Some(header) = journal.get(end..end + 4) {
+        let len = u32::from_le_bytes(header.try_into().unwrap()) as usize;
+        let Some(record) = journal.get(end + 4..end + 4 + len) else {
+            break;
+        };
+        records.push(record.to_vec());
+        end += 4 + len;
+    }
+    (records, end)
+}
+
+fn read_journal(vault_name: &str) -> Result<Vec<u8>, String> {
+    match fs::read(storage::journal_path(vault_name)) {
+        Ok(journal) => Ok(journal),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Cuts away an append that a crash left unfinished. Only whoever holds the lock may do this,
+/// anyone else could be looking at an append still being written.
+fn truncate_torn_append(vault_name: &str) -> Result<(), String> {
+    let journal = read_journal(vault_name)?;
+    let (_, end) = frames(&journal);
+    if end == journal.len() {
+        return Ok(());
+    }
+
+    let file = OpenOptions::new()
+        .write(true)
+        .open(storage::journal_path(vault_name))
+        .map_err(|e| e.to_string())?;
+    file.set_len(end as u64).map_err(|e| e.to_string())?;
+    file.sync_all().map_err(|e| e.to_string())
+}
+
+fn remove_journal(vault_name: &str) -> Result<(), String> {
+    match fs::remove_file(storage::journal_path(vault_name)) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e.to_string()),
+        _ => Ok(()),
+    }
+}
+
+impl VaultStore for FileStore {
+    fn list(&self) -> Result<Vec<String>, String> {
+        storage::list_vaults()
+    }
+
+    fn read(&self, vault_name: &str) -> Result<Option<Vec<u8>>, String> {
+        match fs::read(storage::vault_path(vault_name)) {
+            Ok(file) => Ok(Some(file)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    fn write(&self, vault_name: &str, contents: &[u8]) -> Result<(), String> {
+        storage::ensure_vault_dir()?;
+        let path = storage::vault_path(vault_name);
+
+        // Vaults last saved before backups existed have none of the version being replaced.
+        if backup::list(vault_name).is_empty() {
+            if let Ok(current) = fs::read(&path) {
+                backup::record(vault_name, &current)?;
+            }
+        }
+
+        // The new version is backed up before it replaces the old one, so a backup that can't
+        // be made fails the save and leaves the vault as it was. A save that fails takes its
+        // backup with it, and older backups are only pruned once the save has landed.
+        let backup = backup::record(vault_name, contents)?;
+        if let Err(e) = storage::write_atomic(&path, contents) {
+            let _ = fs::remove_file(backup);
+            return Err(e.to_string());
+        }
+        remove_journal(vault_name)?;
+        backup::prune(vault_name, storage::backup_policy())
+    }
+
+    fn append(&self, vault_name: &str, record: &[u8]) -> Result<(), String> {
+        let path = storage::journal_path(vault_name);
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| e.to_string())?;
+        let start = file.metadata().map_err(|e| e.to_string())?.len();
+
+        let mut frame = (record.len() as u32).to_le_bytes().to_vec();
+        frame.extend(record);
+        if let Err(e) = file.write_all(&frame).and_then(|_| file.sync_data()) {
+            let _ = file.set_len(start);
+            return Err(e.to_string());
+        }
+
+        if start == 0 {
+            storage::sync_dir(storage::vault_dir()).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn journal(&self, vault_name: &str) -> Result<Vec<Vec<u8>>, String> {
+        let (records, _) = frames(&read_journal(vault_name)?);
+        Ok(records)
+    }
+
+    fn delete(&self, vault_name: &str) -> Result<(), String> {
+        fs::remove_file(storage::vault_path(vault_name)).map_err(|e| e.to_string())?;
+
+        remove_journal(vault_name)?;
+        let _ = fs::remove_file(storage::lock_path(vault_name));
+        let backups = storage::backup_dir(vault_name);
+        if backups.exists() {
+            fs::remove_dir_all(backups).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn lock(&self, vault_name: &str) -> Result<Access, String> {
+        let access = lock::acquire(vault_name)?;
+        if let Access::Writable { .. } = access {
+            recover_interrupted_save(vault_name);
+            truncate_torn_append(vault_name)?;
+        }
+        Ok(access)
+    }
+
+    fn backups(&self, vault_name: &str) -> Vec<BackupInfo> {
+        backup::list(vault_name)
+    }
+
+    fn read_backup(&self, vault_name: &str, id: &str) -> Result<Vec<u8>, String> {
+        fs::read(backup::path(vault_name, id)?).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use crate::vault::vault::Vault;
+
+    fn create(vault_name: &str) -> Vec<u8> {
+        Vault::new(Arc::new(FileStore))
+            .create_new("master password", vault_name)
+            .unwrap();
+        FileStore.read(vault_name).unwrap().unwrap()
+    }
+
+    #[test]
+    fn only_the_lock_holder_settles_an_interrupted_save() {
+        let good = create("interrupted");
+        let path = storage::vault_path("interrupted");
+        let temp = storage::temp_path(&path);
+
+        // A leftover beside a good vault is thrown away, but reading alone leaves it be.
+        fs::write(&temp, b"junk").unwrap();
+        assert_eq!(FileStore.read("interrupted").unwrap().unwrap(), good);
+        assert!(temp.exists());
+        drop(FileStore.lock("interrupted").unwrap());
+        assert!(!temp.exists());
+
+        // A complete leftover beside a torn vault replaces it.
+        fs::write(&temp, &good).unwrap();
+        fs::write(&path, &good[..good.len() / 2]).unwrap();
+        drop(FileStore.lock("interrupted").unwrap());
+        assert!(!temp.exists());
+        assert_eq!(FileStore.read("interrupted").unwrap().unwrap(), good);
+    }
+
+    #[test]
+    fn read_only_access_leaves_an_interrupted_save() {
+        let good = create("read-only");
+        let temp = storage::temp_path(&storage::vault_path("read-only"));
+        fs::write(&temp, &good).unwrap();
+
+        // A second handle on the lock file is refused, just as another process would be.
+        let held = FileStore.lock("read-only").unwrap();
+        assert!(!temp.exists());
+        fs::write(&temp, &good).unwrap();
+        assert!(matches!(
+            FileStore.lock("read-only").unwrap(),
+            Access::ReadOnly { .. }
+        ));
+        assert!(temp.exists());
+        drop(held);
+    }
+
+    #[test]
+    fn a_failed_backup_fails_the_save() {
+        let good = create("backup-fails");
+        assert_eq!(FileStore.backups("backup-fails").len(), 1);
+
+        // A file where the backups folder should be keeps any backup from being made.
+        let backups = storage::backup_dir("backup-fails");
+        fs::remove_dir_all(&backups).unwrap();
+        fs::write(&backups, b"not a folder").unwrap();
+        assert!(FileStore.write("backup-fails", b"newer").is_err());
+        assert_eq!(FileStore.read("backup-fails").unwrap().unwrap(), good);
+
+        fs::remove_file(&backups).unwrap();
+        FileStore.write("backup-fails", b"newer").unwrap();
+        let newest = &FileStore.backups("backup-fails")[0];
+        assert_eq!(
+            FileStore.read_backup("backup-fails", &newest.id).unwrap(),
+            b"newer"
+        );
+    }
+
+    #[test]
+    fn a_failed_save_leaves_no_backup_of_itself() {
+        let good = create("save-fails");
+        let before = FileStore.backups("save-fails");
+
+        // A folder where the vault file should be keeps the new version from landing.
+        let path = storage::vault_path("save-fails");
+        fs::remove_file(&path).unwrap();
+        fs::create_dir(&path).unwrap();
+        fs::write(path.join("in the way"), b"").unwrap();
+        assert!(FileStore.write("save-fails", b"never saved").is_err());
+
+        let after = FileStore.backups("save-fails");
+        assert_eq!(after.len(), before.len());
+        for backup in &after {
+            let contents = FileStore.read_backup("save-fails", &backup.id).unwrap();
+            assert_ne!(contents, b"never saved");
+        }
+        assert_eq!(
+            FileStore.read_backup("save-fails", &after[0].id).unwrap(),
+            good
+        );
+    }
+}
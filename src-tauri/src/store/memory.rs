@@ -0,0 +1,140 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::store::store::VaultStore;
+use crate::vault::backup::BackupInfo;
+use crate::vault::lock::{Access, VaultLock};
+
+/// Keeps vaults in memory for the life of the store, with every version written kept as a
+/// backup. Locks only keep out other users of the same store.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    vaults: Mutex<HashMap<String, Vec<u8>>>,
+    journals: Mutex<HashMap<String, Vec<Vec<u8>>>>,
+    /// Every version of each vault, oldest first. A backup's id is its position.
+    backups: Mutex<HashMap<String, Vec<Version>>>,
+    held: Arc<Mutex<HashSet<String>>>,
+}
+
+#[derive(Debug)]
+struct Version {
+    created: u64,
+    contents: Vec<u8>,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+struct Held {
+    held: Arc<Mutex<HashSet<String>>>,
+    vault_name: String,
+}
+
+impl Drop for Held {
+    fn drop(&mut self) {
+        self.held.lock().unwrap().remove(&self.vault_name);
+    }
+}
+
+impl VaultStore for MemoryStore {
+    fn list(&self) -> Result<Vec<String>, String> {
+        let mut names: Vec<String> = self.vaults.lock().unwrap().keys().cloned().collect();
+        names.sort();
+        Ok(names)
+    }
+
+    fn read(&self, vault_name: &str) -> Result<Option<Vec<u8>>, String> {
+        Ok(self.vaults.lock().unwrap().get(vault_name).cloned())
+    }
+
+    fn write(&self, vault_name: &str, contents: &[u8]) -> Result<(), String> {
+        self.vaults
+            .lock()
+            .unwrap()
+            .insert(vault_name.to_string(), contents.to_vec());
+        self.backups
+            .lock()
+            .unwrap()
+            .entry(vault_name.to_string())
+            .or_default()
+            .push(Version {
+                created: now_secs(),
+                contents: contents.to_vec(),
+            });
+        self.journals.lock().unwrap().remove(vault_name);
+        Ok(())
+    }
+
+    fn append(&self, vault_name: &str, record: &[u8]) -> Result<(), String> {
+        self.journals
+            .lock()
+            .unwrap()
+            .entry(vault_name.to_string())
+            .or_default()
+            .push(record.to_vec());
+        Ok(())
+    }
+
+    fn journal(&self, vault_name: &str) -> Result<Vec<Vec<u8>>, String> {
+        let journals = self.journals.lock().unwrap();
+        Ok(journals.get(vault_name).cloned().unwrap_or_default())
+    }
+
+    fn delete(&self, vault_name: &str) -> Result<(), String> {
+        self.journals.lock().unwrap().remove(vault_name);
+        self.backups.lock().unwrap().remove(vault_name);
+        match self.vaults.lock().unwrap().remove(vault_name) {
+            Some(_) => Ok(()),
+            None => Err(String::from("No vault of that name found")),
+        }
+    }
+
+    fn lock(&self, vault_name: &str) -> Result<Access, String> {
+        if !self.held.lock().unwrap().insert(vault_name.to_string()) {
+            return Ok(Access::ReadOnly {
+                holder: String::from("another session"),
+            });
+        }
+
+        let held = Held {
+            held: Arc::clone(&self.held),
+            vault_name: vault_name.to_string(),
+        };
+        Ok(Access::Writable {
+            lock: VaultLock::new(held),
+            recovered: None,
+        })
+    }
+
+    fn backups(&self, vault_name: &str) -> Vec<BackupInfo> {
+        let backups = self.backups.lock().unwrap();
+        let versions = backups
+            .get(vault_name)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        versions
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(id, version)| BackupInfo {
+                id: id.to_string(),
+                created: version.created,
+                size: version.contents.len() as u64,
+            })
+            .collect()
+    }
+
+    fn read_backup(&self, vault_name: &str, id: &str) -> Result<Vec<u8>, String> {
+        let backups = self.backups.lock().unwrap();
+        id.parse::<usize>()
+            .ok()
+            .and_then(|id| backups.get(vault_name)?.get(id))
+            .map(|version| version.contents.clone())
+            .ok_or_else(|| String::from("No backup of that id found"))
+    }
+}
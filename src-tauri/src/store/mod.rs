@@ -0,0 +1,5 @@
+pub mod file;
+pub mod memory;
+pub mod sqlite;
+#[allow(clippy::module_inception)]
+pub mod store;
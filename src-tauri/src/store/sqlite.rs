@@ -0,0 +1,135 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::storage;
+use crate::store::store::VaultStore;
+use crate::vault::lock::{self, Access};
+
+/// Every vault as a row of one SQLite database, with its journal as rows of another table.
+/// SQLite commits each write as a whole, which gives the same all or nothing saves as the file
+/// store. Vaults are locked with the same lock files the file store uses, so two processes
+/// sharing a database still can't both write. No backups are kept, the database is one file to
+/// back up as a whole.
+#[derive(Debug)]
+pub struct SqliteStore {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        storage::ensure_vault_dir()?;
+        let connection = Connection::open(path).map_err(|e| e.to_string())?;
+        connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS vaults (
+                    name TEXT PRIMARY KEY NOT NULL,
+                    contents BLOB NOT NULL
+                );
+                CREATE TABLE IF NOT EXISTS journal (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    vault TEXT NOT NULL,
+                    record BLOB NOT NULL
+                );
+                CREATE INDEX IF NOT EXISTS journal_vault ON journal (vault, id);",
+            )
+            .map_err(|e| e.to_string())?;
+
+        Ok(SqliteStore {
+            connection: Mutex::new(connection),
+        })
+    }
+}
+
+impl VaultStore for SqliteStore {
+    fn list(&self) -> Result<Vec<String>, String> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection
+            .prepare("SELECT name FROM vaults ORDER BY name")
+            .map_err(|e| e.to_string())?;
+        let names = statement
+            .query_map([], |row| row.get(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<String>, _>>()
+            .map_err(|e| e.to_string())?;
+        Ok(names)
+    }
+
+    fn read(&self, vault_name: &str) -> Result<Option<Vec<u8>>, String> {
+        self.connection
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT contents FROM vaults WHERE name = ?1",
+                params![vault_name],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())
+    }
+
+    fn write(&self, vault_name: &str, contents: &[u8]) -> Result<(), String> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction().map_err(|e| e.to_string())?;
+        transaction
+            .execute(
+                "INSERT INTO vaults (name, contents) VALUES (?1, ?2)
+                 ON CONFLICT (name) DO UPDATE SET contents = excluded.contents",
+                params![vault_name, contents],
+            )
+            .map_err(|e| e.to_string())?;
+        transaction
+            .execute("DELETE FROM journal WHERE vault = ?1", params![vault_name])
+            .map_err(|e| e.to_string())?;
+        transaction.commit().map_err(|e| e.to_string())
+    }
+
+    fn append(&self, vault_name: &str, record: &[u8]) -> Result<(), String> {
+        self.connection
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT INTO journal (vault, record) VALUES (?1, ?2)",
+                params![vault_name, record],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    fn journal(&self, vault_name: &str) -> Result<Vec<Vec<u8>>, String> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection
+            .prepare("SELECT record FROM journal WHERE vault = ?1 ORDER BY id")
+            .map_err(|e| e.to_string())?;
+        let records = statement
+            .query_map(params![vault_name], |row| row.get(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<Vec<u8>>, _>>()
+            .map_err(|e| e.to_string())?;
+        Ok(records)
+    }
+
+    fn delete(&self, vault_name: &str) -> Result<(), String> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction().map_err(|e| e.to_string())?;
+        let deleted = transaction
+            .execute("DELETE FROM vaults WHERE name = ?1", params![vault_name])
+            .map_err(|e| e.to_string())?;
+        if deleted == 0 {
+            return Err(String::from("No vault of that name found"));
+        }
+        transaction
+            .execute("DELETE FROM journal WHERE vault = ?1", params![vault_name])
+            .map_err(|e| e.to_string())?;
+        transaction.commit().map_err(|e| e.to_string())?;
+
+        let _ = fs::remove_file(storage::lock_path(vault_name));
+        Ok(())
+    }
+
+    fn lock(&self, vault_name: &str) -> Result<Access, String> {
+        lock::acquire(vault_name)
+    }
+}
@@ -0,0 +1,156 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use crate::storage;
+use crate::store::{file::FileStore, memory::MemoryStore, sqlite::SqliteStore};
+use crate::vault::backup::BackupInfo;
+use crate::vault::lock::Access;
+
+/// Keeps encrypted vault files by name. A store only ever sees the file as it is written to
+/// disk, never the decrypted vault.
+pub trait VaultStore: Debug + Send + Sync {
+    /// Names of the stored vaults, sorted.
+    fn list(&self) -> Result<Vec<String>, String>;
+
+    /// The vault's file, `None` when there is no vault of that name.
+    fn read(&self, vault_name: &str) -> Result<Option<Vec<u8>>, String>;
+
+    /// Replaces the vault's file and empties its journal. A crash leaves either the old or the
+    /// new contents, though the emptied journal may come back.
+    fn write(&self, vault_name: &str, contents: &[u8]) -> Result<(), String>;
+
+    /// Adds a record to the end of the vault's journal, on disk before returning. A record cut
+    /// short by a crash is never returned by `journal`.
+    fn append(&self, vault_name: &str, record: &[u8]) -> Result<(), String>;
+
+    /// Every complete record in the vault's journal, oldest first.
+    fn journal(&self, vault_name: &str) -> Result<Vec<Vec<u8>>, String>;
+
+    /// Removes the vault and anything else the store keeps for it.
+    fn delete(&self, vault_name: &str) -> Result<(), String>;
+
+    /// Takes the vault for writing, or says who already has it.
+    fn lock(&self, vault_name: &str) -> Result<Access, String>;
+
+    /// Earlier versions of the vault, newest first. The SQLite store keeps none, the memory
+    /// store keeps every version until it is dropped.
+    fn backups(&self, _vault_name: &str) -> Vec<BackupInfo> {
+        vec![]
+    }
+
+    fn read_backup(&self, _vault_name: &str, _id: &str) -> Result<Vec<u8>, String> {
+        Err(String::from("No backup of that id found"))
+    }
+}
+
+/// Which store vaults are kept in, set with `"store"` in `config.json`.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreKind {
+    /// One file per vault in the vault folder.
+    #[default]
+    Files,
+    /// Every vault in one SQLite database in the vault folder.
+    Sqlite,
+    /// Nothing outlives the run, for trying the app out and for tests.
+    Memory,
+}
+
+pub fn open(kind: StoreKind) -> Result<Arc<dyn VaultStore>, String> {
+    Ok(match kind {
+        StoreKind::Files => Arc::new(FileStore),
+        StoreKind::Sqlite => Arc::new(SqliteStore::open(&storage::database_path())?),
+        StoreKind::Memory => Arc::new(MemoryStore::default()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Every backend, each with vault names of its own so the file based ones can share the
+    /// test vault folder.
+    fn backends(test: &str) -> Vec<(Arc<dyn VaultStore>, String)> {
+        let database = storage::ensure_vault_dir().unwrap().join(format!("{}.sqlite3", test));
+        let _ = fs::remove_file(&database);
+        vec![
+            (Arc::new(FileStore), format!("{}-file", test)),
+            (
+                Arc::new(SqliteStore::open(&database).unwrap()),
+                format!("{}-sqlite", test),
+            ),
+            (Arc::new(MemoryStore::default()), format!("{}-memory", test)),
+        ]
+    }
+
+    #[test]
+    fn vaults_round_trip() {
+        for (store, name) in backends("round-trip") {
+            assert_eq!(store.read(&name).unwrap(), None, "{name}");
+            assert!(!store.list().unwrap().contains(&name), "{name}");
+
+            store.write(&name, b"first").unwrap();
+            store.write(&name, b"second").unwrap();
+            assert_eq!(store.read(&name).unwrap().as_deref(), Some(&b"second"[..]));
+            assert!(store.list().unwrap().contains(&name), "{name}");
+
+            store.delete(&name).unwrap();
+            assert_eq!(store.read(&name).unwrap(), None, "{name}");
+            assert!(!store.list().unwrap().contains(&name), "{name}");
+            assert!(store.delete(&name).is_err(), "{name}");
+        }
+    }
+
+    #[test]
+    fn journals_keep_order_until_the_next_write() {
+        for (store, name) in backends("journal") {
+            let other = format!("{}-other", name);
+            store.write(&name, b"vault").unwrap();
+            store.write(&other, b"vault").unwrap();
+            assert!(store.journal(&name).unwrap().is_empty(), "{name}");
+
+            let records = [b"one".to_vec(), vec![], b"three".to_vec()];
+            for record in &records {
+                store.append(&name, record).unwrap();
+            }
+            assert_eq!(store.journal(&name).unwrap(), records, "{name}");
+            assert!(store.journal(&other).unwrap().is_empty(), "{name}");
+
+            store.write(&name, b"compacted").unwrap();
+            assert!(store.journal(&name).unwrap().is_empty(), "{name}");
+            store.append(&name, b"after").unwrap();
+            assert_eq!(store.journal(&name).unwrap(), [b"after".to_vec()], "{name}");
+
+            store.delete(&name).unwrap();
+            store.delete(&other).unwrap();
+        }
+    }
+
+    #[test]
+    fn a_held_vault_opens_read_only() {
+        for (store, name) in backends("lock") {
+            let held = store.lock(&name).unwrap();
+            assert!(matches!(held, Access::Writable { .. }), "{name}");
+            assert!(
+                matches!(store.lock(&name).unwrap(), Access::ReadOnly { .. }),
+                "{name}"
+            );
+            assert!(
+                matches!(
+                    store.lock(&format!("{}-other", name)).unwrap(),
+                    Access::Writable { .. }
+                ),
+                "{name}"
+            );
+
+            drop(held);
+            assert!(
+                matches!(store.lock(&name).unwrap(), Access::Writable { .. }),
+                "{name}"
+            );
+        }
+    }
+}
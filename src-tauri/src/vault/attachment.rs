@@ -0,0 +1,243 @@
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use bincode::{Decode, Encode};
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN},
+    hkdf::{Salt, HKDF_SHA256},
+    rand::{generate, SystemRandom},
+};
+use serde::Serialize;
+
+use crate::storage;
+
+const MAGIC: &[u8; 4] = b"ANAT";
+const VERSION: u8 = 1;
+const SALT_LEN: usize = 32;
+const HEADER_LEN: usize = MAGIC.len() + 1 + SALT_LEN;
+const CHUNK_LEN: usize = 64 * 1024;
+const TAG_LEN: usize = 16;
+const KEY_INFO: &[u8] = b"ancrypt attachment v1";
+
+/// Reference kept in an entry to a blob stored beside the vault file.
+#[derive(Clone, Encode, Decode, Serialize, Debug)]
+pub struct Attachment {
+    pub id: String,
+    pub file_name: String,
+    pub size: u64,
+}
+
+/// Each blob gets its own key from the vault key and a random salt, so chunk nonces only
+/// have to be unique within one file.
+fn blob_key(vault_key: &[u8; 32], salt: &[u8]) -> LessSafeKey {
+    let prk = Salt::new(HKDF_SHA256, salt).extract(vault_key);
+    let okm = prk.expand(&[KEY_INFO], &CHACHA20_POLY1305).unwrap();
+    LessSafeKey::new(UnboundKey::from(okm))
+}
+
+/// STREAM style nonce, the chunk counter followed by a flag marking the final chunk so a
+/// truncated blob fails to authenticate instead of decrypting to a shorter file.
+fn chunk_nonce(counter: u32, last: bool) -> Nonce {
+    let mut nonce = [0u8; NONCE_LEN];
+    nonce[7..11].copy_from_slice(&counter.to_be_bytes());
+    nonce[11] = last as u8;
+    Nonce::assume_unique_for_key(nonce)
+}
+
+fn read_chunk(reader: &mut impl Read, buffer: &mut Vec<u8>, len: usize) -> std::io::Result<()> {
+    buffer.clear();
+    reader.take(len as u64).read_to_end(buffer)?;
+    Ok(())
+}
+
+pub fn new_attachment_id() -> String {
+    let rng = SystemRandom::new();
+    let bytes = generate::<[u8; 16]>(&rng).unwrap().expose();
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn blob_path(dir: &Path, id: &str) -> PathBuf {
+    dir.join(format!("{}.blob", id))
+}
+
+/// Encrypts `source` into `destination` one chunk at a time and returns the plaintext size.
+/// The blob is written like a vault file, so a crash never leaves half of one behind.
+pub fn seal_file(vault_key: &[u8; 32], source: &Path, destination: &Path) -> Result<u64, String> {
+    let reader = BufReader::new(File::open(source).map_err(|e| e.to_string())?);
+    storage::replace_with(destination, |file| {
+        let mut writer = BufWriter::new(file);
+        let size = seal(vault_key, reader, &mut writer)?;
+        writer.flush()?;
+        Ok(size)
+    })
+    .map_err(|e| e.to_string())
+}
+
+fn seal(vault_key: &[u8; 32], mut reader: impl BufRead, mut writer: impl Write) -> io::Result<u64> {
+    let rng = SystemRandom::new();
+    let salt = generate::<[u8; SALT_LEN]>(&rng).unwrap().expose();
+    let key = blob_key(vault_key, &salt);
+
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.extend(MAGIC);
+    header.push(VERSION);
+    header.extend(salt);
+    writer.write_all(&header)?;
+
+    let mut buffer = Vec::with_capacity(CHUNK_LEN + TAG_LEN);
+    let mut counter: u32 = 0;
+    let mut size: u64 = 0;
+    loop {
+        read_chunk(&mut reader, &mut buffer, CHUNK_LEN)?;
+        let last = reader.fill_buf()?.is_empty();
+        size += buffer.len() as u64;
+
+        key.seal_in_place_append_tag(chunk_nonce(counter, last), Aad::from(&header), &mut buffer)
+            .map_err(|_| io::Error::other("Failed to encrypt attachment"))?;
+        writer.write_all(&buffer)?;
+
+        if last {
+            break;
+        }
+        counter = counter
+            .checked_add(1)
+            .ok_or_else(|| io::Error::other("Attachment is too large"))?;
+    }
+
+    Ok(size)
+}
+
+/// Decrypts a blob into `destination`, stopping at the first chunk that fails to authenticate.
+pub fn open_file(vault_key: &[u8; 32], source: &Path, destination: &Path) -> Result<(), String> {
+    let reader = BufReader::new(File::open(source).map_err(|e| e.to_string())?);
+    let mut writer = BufWriter::new(File::create(destination).map_err(|e| e.to_string())?);
+
+    let result = open(vault_key, reader, &mut writer)
+        .and_then(|_| writer.flush().map_err(|e| e.to_string()));
+    if result.is_err() {
+        drop(writer);
+        let _ = fs::remove_file(destination);
+    }
+    result
+}
+
+fn open(
+    vault_key: &[u8; 32],
+    mut reader: impl BufRead,
+    mut writer: impl Write,
+) -> Result<(), String> {
+    let mut header = [0u8; HEADER_LEN];
+    reader
+        .read_exact(&mut header)
+        .map_err(|_| String::from("Attachment is not an Ancrypt attachment"))?;
+    if &header[..MAGIC.len()] != MAGIC || header[MAGIC.len()] != VERSION {
+        return Err(String::from("Attachment is not an Ancrypt attachment"));
+    }
+    let key = blob_key(vault_key, &header[MAGIC.len() + 1..]);
+
+    let mut buffer = Vec::with_capacity(CHUNK_LEN + TAG_LEN);
+    let mut counter: u32 = 0;
+    loop {
+        read_chunk(&mut reader, &mut buffer, CHUNK_LEN + TAG_LEN).map_err(|e| e.to_string())?;
+        let last = reader.fill_buf().map_err(|e| e.to_string())?.is_empty();
+
+        let plaintext = key
+            .open_in_place(chunk_nonce(counter, last), Aad::from(&header), &mut buffer)
+            .map_err(|_| String::from("Attachment is corrupted or was not made by this vault"))?;
+        writer.write_all(plaintext).map_err(|e| e.to_string())?;
+
+        if last {
+            return Ok(());
+        }
+        counter = counter.checked_add(1).ok_or("Attachment is too large")?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; 32] = [7; 32];
+    const CHUNK: usize = CHUNK_LEN + TAG_LEN;
+
+    fn sealed(len: usize) -> (Vec<u8>, Vec<u8>) {
+        let data: Vec<u8> = (0..len).map(|i| (i * 31 % 251) as u8).collect();
+        let mut blob = vec![];
+        assert_eq!(seal(&KEY, &data[..], &mut blob).unwrap(), len as u64);
+        (data, blob)
+    }
+
+    fn opened(key: &[u8; 32], blob: &[u8]) -> Result<Vec<u8>, String> {
+        let mut data = vec![];
+        open(key, blob, &mut data).map(|_| data)
+    }
+
+    #[test]
+    fn round_trips_around_the_chunk_boundary() {
+        for len in [
+            0,
+            1,
+            CHUNK_LEN - 1,
+            CHUNK_LEN,
+            CHUNK_LEN + 1,
+            3 * CHUNK_LEN + 17,
+        ] {
+            let (data, blob) = sealed(len);
+            let chunks = len.div_ceil(CHUNK_LEN).max(1);
+            assert_eq!(blob.len(), HEADER_LEN + len + chunks * TAG_LEN, "{len}");
+            assert_eq!(opened(&KEY, &blob).unwrap(), data, "{len}");
+        }
+    }
+
+    #[test]
+    fn dropping_the_final_chunk_fails() {
+        let (_, blob) = sealed(2 * CHUNK_LEN + 5);
+        assert!(opened(&KEY, &blob[..HEADER_LEN + 2 * CHUNK]).is_err());
+        assert!(opened(&KEY, &blob[..HEADER_LEN + CHUNK]).is_err());
+        assert!(opened(&KEY, &blob[..blob.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn reordered_chunks_fail() {
+        let (_, blob) = sealed(3 * CHUNK_LEN);
+        let chunks: Vec<&[u8]> = blob[HEADER_LEN..].chunks(CHUNK).collect();
+        let mut swapped = blob[..HEADER_LEN].to_vec();
+        for i in [1, 0, 2] {
+            swapped.extend(chunks[i]);
+        }
+        assert_eq!(swapped.len(), blob.len());
+        assert!(opened(&KEY, &swapped).is_err());
+    }
+
+    #[test]
+    fn wrong_key_or_header_fails() {
+        let (_, blob) = sealed(100);
+        assert!(opened(&[8; 32], &blob).is_err());
+
+        let mut salted = blob.clone();
+        salted[MAGIC.len() + 1] ^= 1;
+        assert!(opened(&KEY, &salted).is_err());
+        assert_eq!(
+            opened(&KEY, b"not a blob").unwrap_err(),
+            "Attachment is not an Ancrypt attachment"
+        );
+    }
+
+    #[test]
+    fn sealed_files_replace_the_destination_atomically() {
+        let dir = std::env::temp_dir().join(format!("ancrypt-attachment-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (source, blob, out) = (dir.join("source"), dir.join("blob"), dir.join("out"));
+        fs::write(&source, b"attachment").unwrap();
+
+        assert_eq!(seal_file(&KEY, &source, &blob).unwrap(), 10);
+        assert!(!storage::temp_path(&blob).exists());
+        open_file(&KEY, &blob, &out).unwrap();
+        assert_eq!(fs::read(&out).unwrap(), b"attachment");
+
+        assert!(open_file(&[8; 32], &blob, &out).is_err());
+        assert!(!out.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
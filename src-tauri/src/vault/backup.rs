@@ -0,0 +1,159 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::storage::{self, BackupPolicy, VAULT_EXTENSION};
+
+const MILLIS_PER_DAY: u64 = 86_400_000;
+
+/// A saved copy of a vault file. Attachments are not part of it, they are kept beside the
+/// vault and only ever added or removed.
+#[derive(Serialize, Clone, Debug)]
+pub struct BackupInfo {
+    pub id: String,
+    /// Unix time of the save in seconds.
+    pub created: u64,
+    pub size: u64,
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Backups are named by the millisecond they were taken, anything else in the folder is not
+/// ours.
+fn parse_id(id: &str) -> Option<u64> {
+    if id.is_empty() || !id.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    id.parse().ok()
+}
+
+/// Every backup's timestamp in milliseconds, newest first.
+fn timestamps(vault_name: &str) -> Vec<u64> {
+    let Ok(entries) = fs::read_dir(storage::backup_dir(vault_name)) else {
+        return vec![];
+    };
+
+    let mut timestamps: Vec<u64> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|e| e == VAULT_EXTENSION))
+        .filter_map(|path| parse_id(path.file_stem()?.to_str()?))
+        .collect();
+    timestamps.sort_unstable_by(|a, b| b.cmp(a));
+    timestamps
+}
+
+fn file_of(vault_name: &str, millis: u64) -> PathBuf {
+    storage::backup_dir(vault_name).join(format!("{}.{}", millis, VAULT_EXTENSION))
+}
+
+pub fn list(vault_name: &str) -> Vec<BackupInfo> {
+    timestamps(vault_name)
+        .into_iter()
+        .map(|millis| BackupInfo {
+            id: millis.to_string(),
+            created: millis / 1000,
+            size: fs::metadata(file_of(vault_name, millis))
+                .map(|m| m.len())
+                .unwrap_or(0),
+        })
+        .collect()
+}
+
+pub fn path(vault_name: &str, id: &str) -> Result<PathBuf, String> {
+    let path = parse_id(id)
+        .map(|millis| file_of(vault_name, millis))
+        .filter(|path| path.is_file());
+    path.ok_or_else(|| String::from("No backup of that id found"))
+}
+
+/// Which of the timestamps, newest first, `policy` keeps at `now`.
+fn retained(timestamps: &[u64], policy: &BackupPolicy, now: u64) -> HashSet<u64> {
+    let mut keep: HashSet<u64> = timestamps.iter().take(policy.keep_last).copied().collect();
+
+    let today = now / MILLIS_PER_DAY;
+    let mut days = HashSet::new();
+    for &millis in timestamps {
+        let day = millis / MILLIS_PER_DAY;
+        if today.saturating_sub(day) < policy.keep_days && days.insert(day) {
+            keep.insert(millis);
+        }
+    }
+    keep
+}
+
+/// Deletes the backups `policy` no longer keeps.
+pub fn prune(vault_name: &str, policy: &BackupPolicy) -> Result<(), String> {
+    let timestamps = timestamps(vault_name);
+    let keep = retained(&timestamps, policy, now_millis());
+
+    for millis in timestamps.into_iter().filter(|m| !keep.contains(m)) {
+        fs::remove_file(file_of(vault_name, millis)).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Stores `contents` as the newest backup and returns its file. Nothing is pruned, that waits
+/// until the save it backs up has landed.
+pub fn record(vault_name: &str, contents: &[u8]) -> Result<PathBuf, String> {
+    fs::create_dir_all(storage::backup_dir(vault_name)).map_err(|e| e.to_string())?;
+
+    // Saves can land in the same millisecond, staying past the newest keeps them apart and
+    // ordered.
+    let millis = now_millis().max(timestamps(vault_name).first().map_or(0, |m| m + 1));
+
+    let file = file_of(vault_name, millis);
+    storage::write_atomic(&file, contents).map_err(|e| e.to_string())?;
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_recent_saves_and_the_newest_of_each_day() {
+        let policy = BackupPolicy {
+            keep_last: 2,
+            keep_days: 3,
+        };
+        let now = 100 * MILLIS_PER_DAY + 5_000;
+        let timestamps = [
+            now - 1_000,
+            now - 2_000,
+            now - 3_000,
+            99 * MILLIS_PER_DAY + 2_000,
+            99 * MILLIS_PER_DAY + 1_000,
+            98 * MILLIS_PER_DAY,
+            97 * MILLIS_PER_DAY,
+        ];
+
+        let mut kept: Vec<u64> = retained(&timestamps, &policy, now).into_iter().collect();
+        kept.sort_unstable_by(|a, b| b.cmp(a));
+        assert_eq!(
+            kept,
+            vec![
+                now - 1_000,
+                now - 2_000,
+                99 * MILLIS_PER_DAY + 2_000,
+                98 * MILLIS_PER_DAY
+            ]
+        );
+    }
+
+    #[test]
+    fn only_digit_names_are_backups() {
+        assert_eq!(parse_id("1700000000000"), Some(1_700_000_000_000));
+        assert_eq!(parse_id("../1"), None);
+        assert_eq!(parse_id("+1"), None);
+        assert_eq!(parse_id(""), None);
+    }
+}
@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::Path;
+
+use md4::{Digest, Md4};
+use ring::digest::{digest, SHA1_FOR_LEGACY_USE_ONLY};
+use serde::Serialize;
+
+/// Which hashes a Have I Been Pwned download holds, told apart by the hash length.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashKind {
+    Sha1,
+    Ntlm,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct BreachedSecret {
+    pub name: String,
+    pub field: String,
+    pub count: u64,
+}
+
+/// A `HASH:COUNT` file ordered by hash, searched in place so multi gigabyte downloads are
+/// never read into memory.
+pub struct HashFile {
+    reader: BufReader<File>,
+    len: u64,
+    kind: HashKind,
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+fn parse_line(line: &[u8]) -> Option<(&[u8], u64)> {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    let separator = line.iter().position(|b| *b == b':')?;
+    let count = std::str::from_utf8(&line[separator + 1..])
+        .ok()?
+        .parse()
+        .ok()?;
+    Some((&line[..separator], count))
+}
+
+impl HashFile {
+    pub fn open(path: &Path) -> Result<HashFile, String> {
+        let file = File::open(path).map_err(|_| String::from("Could not open that hash file"))?;
+        let len = file.metadata().map_err(|e| e.to_string())?.len();
+        let mut reader = BufReader::new(file);
+
+        let mut first = vec![];
+        reader
+            .read_until(b'\n', &mut first)
+            .map_err(|e| e.to_string())?;
+        let kind = match parse_line(&first) {
+            Some((hash, _)) if hash.iter().all(u8::is_ascii_hexdigit) => match hash.len() {
+                40 => HashKind::Sha1,
+                32 => HashKind::Ntlm,
+                _ => return Err(String::from("That is not a SHA-1 or NTLM hash file")),
+            },
+            _ => return Err(String::from("That is not a Have I Been Pwned hash file")),
+        };
+
+        Ok(HashFile { reader, len, kind })
+    }
+
+    pub fn hash(&self, secret: &str) -> String {
+        match self.kind {
+            HashKind::Sha1 => to_hex(digest(&SHA1_FOR_LEGACY_USE_ONLY, secret.as_bytes()).as_ref()),
+            HashKind::Ntlm => {
+                let utf16: Vec<u8> = secret.encode_utf16().flat_map(u16::to_le_bytes).collect();
+                to_hex(&Md4::digest(utf16))
+            }
+        }
+    }
+
+    /// Reads the line starting at or after `offset`, returning where it starts and ends.
+    fn line_at(&mut self, offset: u64, line: &mut Vec<u8>) -> std::io::Result<(u64, u64)> {
+        let mut start = offset;
+        if offset > 0 {
+            self.reader.seek(SeekFrom::Start(offset - 1))?;
+            line.clear();
+            start = offset - 1 + self.reader.read_until(b'\n', line)? as u64;
+        } else {
+            self.reader.seek(SeekFrom::Start(0))?;
+        }
+
+        line.clear();
+        let read = self.reader.read_until(b'\n', line)? as u64;
+        Ok((start, start + read))
+    }
+
+    /// Binary searches the file by byte offset, `lo` always sits on the start of a line.
+    pub fn count(&mut self, hash: &str) -> Result<Option<u64>, String> {
+        let target = hash.to_ascii_uppercase();
+        let target = target.as_bytes();
+        let mut line = vec![];
+        let (mut lo, mut hi) = (0, self.len);
+
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let (start, end) = self.line_at(mid, &mut line).map_err(|e| e.to_string())?;
+            if start >= hi || start == end {
+                hi = mid;
+                continue;
+            }
+
+            let Some((found, count)) = parse_line(&line) else {
+                return Err(String::from("The hash file is corrupted"));
+            };
+            match found.to_ascii_uppercase().as_slice().cmp(target) {
+                std::cmp::Ordering::Equal => return Ok(Some(count)),
+                std::cmp::Ordering::Less => lo = end,
+                std::cmp::Ordering::Greater => hi = start,
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Looks up every `(name, field, secret)` once per distinct secret and returns the ones
+/// found, most exposed first.
+pub fn check<'a>(
+    hash_file: &mut HashFile,
+    secrets: impl Iterator<Item = (&'a str, &'a str, &'a str)>,
+) -> Result<Vec<BreachedSecret>, String> {
+    let mut seen: HashMap<String, Option<u64>> = HashMap::new();
+    let mut breached = vec![];
+
+    for (name, field, secret) in secrets {
+        if secret.is_empty() {
+            continue;
+        }
+        let hash = hash_file.hash(secret);
+        let count = match seen.get(&hash) {
+            Some(count) => *count,
+            None => {
+                let count = hash_file.count(&hash)?;
+                seen.insert(hash, count);
+                count
+            }
+        };
+
+        if let Some(count) = count {
+            breached.push(BreachedSecret {
+                name: name.to_string(),
+                field: field.to_string(),
+                count,
+            });
+        }
+    }
+
+    breached.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+    Ok(breached)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    const PASSWORD_SHA1: &str = "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8";
+    const PASSWORD_NTLM: &str = "8846F7EAEE8FB117AD06BDD830B7586C";
+
+    /// A sorted SHA-1 file with the given line ending and no newline after the last line.
+    fn fixture(name: &str, lines: &[String], ending: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ancrypt-breach-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, lines.join(ending)).unwrap();
+        path
+    }
+
+    fn sorted_lines() -> Vec<String> {
+        let mut lines: Vec<String> = (1..200u32)
+            .map(|i| format!("{:040X}:{}", (i as u128) << 100, i))
+            .collect();
+        lines.push(format!("{}:9659365", PASSWORD_SHA1));
+        lines.push(format!("{}:1", "F".repeat(40)));
+        lines.sort();
+        lines
+    }
+
+    fn finds_every_line(path: &Path, lines: &[String]) {
+        let mut file = HashFile::open(path).unwrap();
+        for line in lines {
+            let (hash, count) = line.split_once(':').unwrap();
+            assert_eq!(
+                file.count(hash).unwrap(),
+                Some(count.parse().unwrap()),
+                "{hash}"
+            );
+        }
+    }
+
+    #[test]
+    fn finds_first_and_last_lines() {
+        let lines = sorted_lines();
+        let path = fixture("lf.txt", &lines, "\n");
+        let mut file = HashFile::open(&path).unwrap();
+
+        let (first, _) = lines[0].split_once(':').unwrap();
+        assert_eq!(file.count(first).unwrap(), Some(1));
+        assert_eq!(file.count(&"F".repeat(40)).unwrap(), Some(1));
+        finds_every_line(&path, &lines);
+    }
+
+    #[test]
+    fn absent_hashes_are_not_found() {
+        let path = fixture("absent.txt", &sorted_lines(), "\n");
+        let mut file = HashFile::open(&path).unwrap();
+
+        assert_eq!(file.count(&"0".repeat(40)).unwrap(), None);
+        assert_eq!(file.count(&format!("{:040X}", 5u128 << 99)).unwrap(), None);
+        assert_eq!(file.count(&"E".repeat(40)).unwrap(), None);
+    }
+
+    #[test]
+    fn handles_crlf_and_lowercase_hex() {
+        let lines = sorted_lines();
+        let path = fixture("crlf.txt", &lines, "\r\n");
+        finds_every_line(&path, &lines);
+
+        let mut file = HashFile::open(&path).unwrap();
+        assert_eq!(
+            file.count(&PASSWORD_SHA1.to_lowercase()).unwrap(),
+            Some(9659365)
+        );
+
+        let lowercase: Vec<String> = lines.iter().map(|l| l.to_lowercase()).collect();
+        let path = fixture("lowercase.txt", &lowercase, "\r\n");
+        let mut file = HashFile::open(&path).unwrap();
+        assert_eq!(file.count(PASSWORD_SHA1).unwrap(), Some(9659365));
+    }
+
+    #[test]
+    fn tells_sha1_and_ntlm_files_apart() {
+        let sha1 = fixture("sha1.txt", &sorted_lines(), "\n");
+        assert_eq!(
+            HashFile::open(&sha1).unwrap().hash("password"),
+            PASSWORD_SHA1
+        );
+
+        let lines = [
+            format!("{}:100", PASSWORD_NTLM),
+            format!("{}:1", "F".repeat(32)),
+        ];
+        let ntlm = fixture("ntlm.txt", &lines, "\n");
+        let mut file = HashFile::open(&ntlm).unwrap();
+        assert_eq!(file.hash("password"), PASSWORD_NTLM);
+        assert_eq!(file.count(PASSWORD_NTLM).unwrap(), Some(100));
+
+        let other = fixture("other.txt", &[String::from("not a hash file")], "\n");
+        assert!(HashFile::open(&other).is_err());
+    }
+
+    #[test]
+    fn checks_each_distinct_secret_once() {
+        let path = fixture("check.txt", &sorted_lines(), "\n");
+        let mut file = HashFile::open(&path).unwrap();
+
+        let secrets = [
+            ("b", "password", "password"),
+            ("c", "password", "unbreached"),
+            ("a", "pin", "password"),
+            ("d", "password", ""),
+        ];
+        let breached = check(&mut file, secrets.into_iter()).unwrap();
+        let names: Vec<&str> = breached.iter().map(|b| b.name.as_str()).collect();
+        assert_eq!(names, ["a", "b"]);
+        assert_eq!(breached[0].count, 9659365);
+    }
+}
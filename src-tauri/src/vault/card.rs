@@ -0,0 +1,222 @@
+use bincode::{Decode, Encode};
+
+use crate::vault::date::{today, Date};
+
+#[derive(Clone, Copy, Encode, Decode, Debug, PartialEq, Eq)]
+pub struct CardExpiry {
+    pub month: u32,
+    pub year: i32,
+}
+
+#[derive(Clone, Encode, Decode, Debug)]
+pub struct PaymentCard {
+    pub number: String,
+    pub holder: String,
+    pub expiry: CardExpiry,
+    pub cvv: String,
+    pub pin: Option<String>,
+}
+
+impl CardExpiry {
+    /// Accepts the formats printed on cards and typed into checkout forms: `MM/YY`, `MM/YYYY`,
+    /// `MM-YY` and `MM-YYYY`.
+    pub fn parse(input: &str) -> Result<CardExpiry, &'static str> {
+        let input = input.trim();
+        let (month, year) = input
+            .split_once('/')
+            .or_else(|| input.split_once('-'))
+            .ok_or("Expiry must be written as MM/YY")?;
+
+        // `parse` would also take a sign, which no card has.
+        let month = month.trim();
+        if month.is_empty() || !all_digits(month) {
+            return Err("Invalid expiry month");
+        }
+        let month: u32 = month.parse().map_err(|_| "Invalid expiry month")?;
+        if !(1..=12).contains(&month) {
+            return Err("Invalid expiry month");
+        }
+
+        let year = year.trim();
+        if !all_digits(year) {
+            return Err("Invalid expiry year");
+        }
+        let parsed: i32 = year.parse().map_err(|_| "Invalid expiry year")?;
+        let year = match year.len() {
+            2 => 2000 + parsed,
+            4 => parsed,
+            _ => return Err("Invalid expiry year"),
+        };
+
+        Ok(CardExpiry { month, year })
+    }
+
+    /// Cards are valid until the last day of their expiry month.
+    pub fn is_expired(&self) -> bool {
+        Date::last_of_month(self.year, self.month) < today()
+    }
+}
+
+impl std::fmt::Display for CardExpiry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:02}/{:02}", self.month, self.year % 100)
+    }
+}
+
+pub fn luhn_valid(number: &str) -> bool {
+    let mut sum = 0;
+    for (i, c) in number.chars().rev().enumerate() {
+        let Some(mut digit) = c.to_digit(10) else {
+            return false;
+        };
+        if i % 2 == 1 {
+            digit *= 2;
+            if digit > 9 {
+                digit -= 9;
+            }
+        }
+        sum += digit;
+    }
+    sum % 10 == 0
+}
+
+fn all_digits(value: &str) -> bool {
+    value.chars().all(|c| c.is_ascii_digit())
+}
+
+impl PaymentCard {
+    pub fn new(
+        number: &str,
+        holder: &str,
+        expiry: &str,
+        cvv: &str,
+        pin: Option<&str>,
+    ) -> Result<PaymentCard, &'static str> {
+        let number: String = number
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '-')
+            .collect();
+        if !all_digits(&number) || !(12..=19).contains(&number.len()) {
+            return Err("Card numbers must be 12 to 19 digits");
+        }
+        if !luhn_valid(&number) {
+            return Err("That card number is not valid, check it for typos");
+        }
+
+        let holder = holder.trim();
+        if holder.is_empty() {
+            return Err("You need a card holder name");
+        }
+
+        let expiry = CardExpiry::parse(expiry)?;
+
+        let cvv = cvv.trim();
+        if !all_digits(cvv) || !(3..=4).contains(&cvv.len()) {
+            return Err("CVVs must be 3 or 4 digits");
+        }
+
+        let pin = match pin.map(str::trim).filter(|p| !p.is_empty()) {
+            Some(pin) if !all_digits(pin) || !(4..=12).contains(&pin.len()) => {
+                return Err("PINs must be 4 to 12 digits");
+            }
+            pin => pin.map(String::from),
+        };
+
+        Ok(PaymentCard {
+            number,
+            holder: holder.to_string(),
+            expiry,
+            cvv: cvv.to_string(),
+            pin,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn luhn_accepts_test_numbers_and_catches_typos() {
+        assert!(luhn_valid("4111111111111111"));
+        assert!(luhn_valid("378282246310005"));
+        assert!(luhn_valid("5555555555554444"));
+        assert!(!luhn_valid("4111111111111112"));
+        assert!(!luhn_valid("4111111111111a11"));
+    }
+
+    #[test]
+    fn new_card_checks_every_field() {
+        let card =
+            PaymentCard::new("4111 1111-1111 1111", " A Holder ", "12/29", "123", None).unwrap();
+        assert_eq!(card.number, "4111111111111111");
+        assert_eq!(card.holder, "A Holder");
+        assert_eq!(card.pin, None);
+
+        let short = PaymentCard::new("42", "A", "12/29", "123", None);
+        assert_eq!(short.unwrap_err(), "Card numbers must be 12 to 19 digits");
+        let typo = PaymentCard::new("4111111111111112", "A", "12/29", "123", None);
+        assert_eq!(
+            typo.unwrap_err(),
+            "That card number is not valid, check it for typos"
+        );
+        assert!(PaymentCard::new("4111111111111111", " ", "12/29", "123", None).is_err());
+        assert!(PaymentCard::new("4111111111111111", "A", "12/29", "12", None).is_err());
+        assert!(PaymentCard::new("4111111111111111", "A", "12/29", "12a", None).is_err());
+        assert!(PaymentCard::new("4111111111111111", "A", "12/29", "1234", None).is_ok());
+        assert!(PaymentCard::new("4111111111111111", "A", "12/29", "123", Some("123")).is_err());
+        assert!(PaymentCard::new("4111111111111111", "A", "12/29", "123", Some("12a4")).is_err());
+
+        let pin = PaymentCard::new("4111111111111111", "A", "12/29", "123", Some("1234"));
+        assert_eq!(pin.unwrap().pin.as_deref(), Some("1234"));
+        let blank = PaymentCard::new("4111111111111111", "A", "12/29", "123", Some(" "));
+        assert_eq!(blank.unwrap().pin, None);
+    }
+
+    #[test]
+    fn parses_expiry_formats() {
+        let expected = CardExpiry {
+            month: 3,
+            year: 2029,
+        };
+        for input in ["03/29", "3/29", "03/2029", "03-29", " 03 / 2029 "] {
+            assert_eq!(CardExpiry::parse(input), Ok(expected), "{input}");
+        }
+        assert_eq!(expected.to_string(), "03/29");
+
+        for input in [
+            "12/-5", "+5/29", "-1/29", "13/29", "00/29", "12/+29", "12/029", "1229", "/29", "12/",
+        ] {
+            assert!(CardExpiry::parse(input).is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn expires_after_the_last_day_of_its_month() {
+        let today = today();
+        let this_month = CardExpiry {
+            month: today.month,
+            year: today.year,
+        };
+        assert!(!this_month.is_expired());
+
+        let last_month = match today.month {
+            1 => CardExpiry {
+                month: 12,
+                year: today.year - 1,
+            },
+            month => CardExpiry {
+                month: month - 1,
+                year: today.year,
+            },
+        };
+        assert!(last_month.is_expired());
+
+        let december = CardExpiry {
+            month: 12,
+            year: 2020,
+        };
+        assert_eq!(Date::last_of_month(2020, 12).to_string(), "2020-12-31");
+        assert!(december.is_expired());
+    }
+}
@@ -0,0 +1,183 @@
+use std::num::NonZeroU32;
+
+use bincode::{Decode, Encode};
+use ring::pbkdf2;
+use serde::{Deserialize, Serialize};
+
+const ITERATIONS: u32 = 100_000;
+const ENTROPY_LEN: usize = 32;
+
+const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPERCASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &str = "0123456789";
+const SYMBOLS: &str = "!\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+
+const MIN_LENGTH: usize = 5;
+const MAX_LENGTH: usize = 35;
+
+/// Everything needed to recompute a site's password from the master password, laid out like a
+/// LessPass profile so the same password comes out of LessPass itself. Nothing secret is kept,
+/// so losing the vault only loses the profile, which the user can type again.
+#[derive(Serialize, Deserialize, Clone, Encode, Decode, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct DerivedProfile {
+    pub site: String,
+    pub login: String,
+    /// Raised by one to rotate the password.
+    pub counter: u32,
+    pub length: usize,
+    pub lowercase: bool,
+    pub uppercase: bool,
+    pub digits: bool,
+    pub symbols: bool,
+}
+
+impl Default for DerivedProfile {
+    fn default() -> Self {
+        DerivedProfile {
+            site: String::new(),
+            login: String::new(),
+            counter: 1,
+            length: 16,
+            lowercase: true,
+            uppercase: true,
+            digits: true,
+            symbols: true,
+        }
+    }
+}
+
+/// Divides a big endian number in place, returning the remainder.
+fn div_rem(number: &mut [u8; ENTROPY_LEN], divisor: usize) -> usize {
+    let mut remainder = 0;
+    for byte in number.iter_mut() {
+        let current = (remainder << 8) | *byte as usize;
+        *byte = (current / divisor) as u8;
+        remainder = current % divisor;
+    }
+    remainder
+}
+
+fn pick(entropy: &mut [u8; ENTROPY_LEN], characters: &[char]) -> char {
+    characters[div_rem(entropy, characters.len())]
+}
+
+impl DerivedProfile {
+    fn classes(&self) -> Vec<&'static str> {
+        [
+            (self.lowercase, LOWERCASE),
+            (self.uppercase, UPPERCASE),
+            (self.digits, DIGITS),
+            (self.symbols, SYMBOLS),
+        ]
+        .into_iter()
+        .filter(|(enabled, _)| *enabled)
+        .map(|(_, characters)| characters)
+        .collect()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.site.trim().is_empty() {
+            return Err(String::from("You need a site"));
+        }
+        if self.counter == 0 {
+            return Err(String::from("The counter starts at 1"));
+        }
+        if !(MIN_LENGTH..=MAX_LENGTH).contains(&self.length) {
+            return Err(format!(
+                "Length must be between {} and {}",
+                MIN_LENGTH, MAX_LENGTH
+            ));
+        }
+        if self.classes().is_empty() {
+            return Err(String::from("Enable at least one kind of character"));
+        }
+        Ok(())
+    }
+
+    /// Recomputes the password. The salt is the site, login and counter in hex, as LessPass
+    /// version 2 does.
+    pub fn derive(&self, master_password: &str) -> Result<String, String> {
+        self.validate()?;
+
+        let salt = format!("{}{}{:x}", self.site, self.login, self.counter);
+        let mut entropy = [0u8; ENTROPY_LEN];
+        pbkdf2::derive(
+            pbkdf2::PBKDF2_HMAC_SHA256,
+            NonZeroU32::new(ITERATIONS).unwrap(),
+            salt.as_bytes(),
+            master_password.as_bytes(),
+            &mut entropy,
+        );
+
+        let classes: Vec<Vec<char>> = self.classes().iter().map(|c| c.chars().collect()).collect();
+        let all: Vec<char> = classes.concat();
+
+        // Fill all but one position per class from every enabled character, then take one
+        // character from each class and slot it in at a position the entropy picks.
+        let mut password: Vec<char> = (0..self.length - classes.len())
+            .map(|_| pick(&mut entropy, &all))
+            .collect();
+        let required: Vec<char> = classes.iter().map(|c| pick(&mut entropy, c)).collect();
+        for character in required {
+            let position = div_rem(&mut entropy, password.len());
+            password.insert(position, character);
+        }
+
+        Ok(password.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(length: usize, counter: u32, classes: [bool; 4]) -> DerivedProfile {
+        DerivedProfile {
+            site: String::from("example.org"),
+            login: String::from("contact@example.org"),
+            counter,
+            length,
+            lowercase: classes[0],
+            uppercase: classes[1],
+            digits: classes[2],
+            symbols: classes[3],
+        }
+    }
+
+    #[test]
+    fn matches_lesspass_default_profile() {
+        let derived = profile(16, 1, [true; 4]).derive("password").unwrap();
+        assert_eq!(derived, "WHLpUL)e00[iHR+w");
+    }
+
+    #[test]
+    fn matches_lesspass_reduced_profiles() {
+        let cases = [
+            (14, 2, [true, true, true, false], "MBAsB7b1Prt8Sl"),
+            (6, 3, [false, false, true, false], "117843"),
+            (14, 1, [true, true, false, true], "sB>{qF}wN%/-fm"),
+        ];
+        for (length, counter, classes, expected) in cases {
+            let derived = profile(length, counter, classes)
+                .derive("password")
+                .unwrap();
+            assert_eq!(derived, expected);
+        }
+    }
+
+    #[test]
+    fn rejects_profiles_lesspass_cannot_render() {
+        assert!(profile(4, 1, [true; 4]).validate().is_err());
+        assert!(profile(36, 1, [true; 4]).validate().is_err());
+        assert!(profile(16, 0, [true; 4]).validate().is_err());
+        assert!(profile(16, 1, [false; 4]).validate().is_err());
+    }
+
+    #[test]
+    fn counter_changes_the_password() {
+        let first = profile(16, 1, [true; 4]).derive("password").unwrap();
+        let second = profile(16, 2, [true; 4]).derive("password").unwrap();
+        assert_ne!(first, second);
+    }
+}
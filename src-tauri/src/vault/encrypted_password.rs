@@ -54,6 +54,12 @@ impl NonceSequence for CounterNonce {
     }
 }
 
+impl Default for EncryptedPasswords {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl EncryptedPasswords {
     pub fn new() -> EncryptedPasswords {
         let nonce_sequence = CounterNonce::new();
@@ -68,6 +74,16 @@ impl EncryptedPasswords {
     }
 }
 
+impl EncryptedPasswords {
+    /// Tells this encryption of the vault apart from every earlier one, each save moves the
+    /// nonce counter on.
+    pub fn snapshot_id(&self) -> Vec<u8> {
+        let mut id = self.aad.to_vec();
+        id.extend(self.index.to_be_bytes());
+        id
+    }
+}
+
 impl EncryptedPasswords {
     pub fn initialise_data(self, key: [u8; CREDENTIAL_LEN], data: Vec<u8>) -> EncryptedPasswords {
         let unbound_key = UnboundKey::new(&CHACHA20_POLY1305, &key).unwrap();
@@ -83,35 +99,44 @@ impl EncryptedPasswords {
         EncryptedPasswords {
             aad: self.aad,
             nonce_sequence: self.nonce_sequence,
-            data: data,
+            data,
             index: self.nonce_sequence.index,
         }
     }
 }
 
 impl EncryptedPasswords {
-    pub fn decrypt(&mut self, key: [u8; CREDENTIAL_LEN]) -> Vec<u8> {
+    /// Decrypts the data, failing when it was encrypted under another key or has been changed
+    /// since.
+    pub fn try_decrypt(&mut self, key: [u8; CREDENTIAL_LEN]) -> Result<Vec<u8>, Unspecified> {
         let unbound_key = UnboundKey::new(&CHACHA20_POLY1305, &key).unwrap();
         let index: u64 = self.index;
         let nonce_sequence = CounterNonce {
             nonce_bytes: self.nonce_sequence.nonce_bytes,
-            index: index,
+            index,
         };
         let mut opening_key = OpeningKey::new(unbound_key, nonce_sequence);
 
         let encrypted_data = self.data.as_mut_slice();
 
-        let data = match opening_key.open_in_place(Aad::from(self.aad), encrypted_data) {
-            Ok(d) => d,
-            Err(_) => {
-                println!("Failed lol");
-                &mut [0; 0]
-            }
-        };
+        opening_key
+            .open_in_place(Aad::from(self.aad), encrypted_data)
+            .map(|data| data.to_vec())
+    }
 
-        let data = data.to_vec();
+    pub fn decrypt(&mut self, key: [u8; CREDENTIAL_LEN]) -> Result<Vec<u8>, String> {
+        self.try_decrypt(key).map_err(|_| {
+            String::from("The vault's entries could not be decrypted, verify it to find out why")
+        })
+    }
 
-        data
+    /// The nonce the data was encrypted with, a key must never see the same one twice.
+    #[cfg(test)]
+    pub(crate) fn nonce(&self) -> [u8; 12] {
+        let mut nonce = [0u8; 12];
+        nonce[0..4].copy_from_slice(&self.nonce_sequence.nonce_bytes);
+        nonce[4..].copy_from_slice(&self.index.to_be_bytes());
+        nonce
     }
 
     pub fn encrypt_data(&mut self, key: [u8; CREDENTIAL_LEN], data: Vec<u8>) -> EncryptedPasswords {
@@ -128,7 +153,7 @@ impl EncryptedPasswords {
         EncryptedPasswords {
             aad: self.aad,
             nonce_sequence: self.nonce_sequence,
-            data: data,
+            data,
             index: self.nonce_sequence.index,
         }
     }
@@ -0,0 +1,328 @@
+use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+
+use crate::vault::attachment::Attachment;
+use crate::vault::card::PaymentCard;
+use crate::vault::date::{now_secs, today, Date};
+use crate::vault::derived::DerivedProfile;
+use crate::vault::identity::Identity;
+use crate::vault::passkey::Passkey;
+use crate::vault::ssh::SshKey;
+use crate::vault::strength;
+use crate::vault::template::{CustomField, FieldType};
+use crate::vault::url_match::UrlMatch;
+
+#[derive(Clone, Encode, Decode, Debug)]
+pub enum EntryKind {
+    Login { password: String },
+    SecureNote { text: String },
+    PaymentCard(PaymentCard),
+    Identity(Identity),
+    SshKey(SshKey),
+    Passkey(Passkey),
+    Custom {
+        template: String,
+        fields: Vec<CustomField>,
+    },
+    /// A login whose password is recomputed from the master password instead of stored.
+    Derived(DerivedProfile),
+}
+
+#[derive(Clone, Encode, Decode, Debug)]
+pub struct Entry {
+    pub kind: EntryKind,
+    pub attachments: Vec<Attachment>,
+    pub created: u64,
+    pub modified: u64,
+    pub expiry: Option<Expiry>,
+    pub username: String,
+    pub urls: Vec<String>,
+    pub url_match: UrlMatch,
+    pub notes: String,
+    pub tags: Vec<String>,
+    /// Strength score from 0 to 4 of the password, if the entry has one.
+    pub strength: Option<u8>,
+    /// The site's password requirements in `passwordrules` syntax, honoured when a new
+    /// password is generated for this entry.
+    pub password_rule: Option<String>,
+}
+
+/// When an entry's secret should be replaced, either on a fixed date or a number of days
+/// after it was last changed.
+#[derive(Clone, Copy, Encode, Decode, Debug)]
+pub enum Expiry {
+    On(Date),
+    Every { days: u32 },
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ExpiringEntry {
+    pub name: String,
+    pub due: String,
+    pub days_left: i64,
+    pub expired: bool,
+}
+
+/// What the frontend is told about an entry without revealing any secret in it.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryType {
+    Login,
+    SecureNote,
+    PaymentCard,
+    Identity,
+    SshKey,
+    Passkey,
+    Custom,
+    Derived,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct EntrySummary {
+    pub name: String,
+    pub kind: EntryType,
+    pub strength: Option<u8>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct EntryWarning {
+    pub name: String,
+    pub message: String,
+}
+
+/// A single copyable value inside an entry.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryField {
+    Username,
+    Password,
+    Number,
+    Holder,
+    Expiry,
+    Cvv,
+    Pin,
+    FullName,
+    Address,
+    Phone,
+    PassportNumber,
+    LicenceNumber,
+    PrivateKey,
+    PublicKey,
+}
+
+impl Entry {
+    fn new(kind: EntryKind) -> Self {
+        let now = now_secs();
+        Entry {
+            kind,
+            attachments: vec![],
+            created: now,
+            modified: now,
+            expiry: None,
+            username: String::new(),
+            urls: vec![],
+            url_match: UrlMatch::default(),
+            notes: String::new(),
+            tags: vec![],
+            strength: None,
+            password_rule: None,
+        }
+    }
+
+    /// Rescores the password against the entry's own name and username, call it whenever
+    /// either changes.
+    pub fn rate_strength(&mut self, name: &str) {
+        self.strength = match &self.kind {
+            EntryKind::Login { password } => {
+                Some(strength::estimate(password, &[name, &self.username]).score)
+            }
+            _ => None,
+        };
+    }
+
+    /// Marks the secret as changed, which restarts any rotation interval.
+    pub fn touch(&mut self) {
+        self.modified = now_secs();
+    }
+
+    pub fn due_date(&self) -> Option<Date> {
+        match self.expiry? {
+            Expiry::On(date) => Some(date),
+            Expiry::Every { days } => Some(Date::from_days(
+                (self.modified / 86_400) as i64 + days as i64,
+            )),
+        }
+    }
+
+    /// Days until the entry is due, negative once it is overdue.
+    pub fn days_until_due(&self) -> Option<i64> {
+        Some(self.due_date()?.to_days() - today().to_days())
+    }
+
+    pub fn login(password: String) -> Self {
+        Entry::new(EntryKind::Login { password })
+    }
+
+    pub fn secure_note(text: String) -> Self {
+        Entry::new(EntryKind::SecureNote { text })
+    }
+
+    pub fn payment_card(card: PaymentCard) -> Self {
+        Entry::new(EntryKind::PaymentCard(card))
+    }
+
+    pub fn identity(identity: Identity) -> Self {
+        Entry::new(EntryKind::Identity(identity))
+    }
+
+    pub fn ssh_key(key: SshKey) -> Self {
+        Entry::new(EntryKind::SshKey(key))
+    }
+
+    pub fn passkey(passkey: Passkey) -> Self {
+        Entry::new(EntryKind::Passkey(passkey))
+    }
+
+    pub fn custom(template: String, fields: Vec<CustomField>) -> Self {
+        Entry::new(EntryKind::Custom { template, fields })
+    }
+
+    pub fn derived(profile: DerivedProfile) -> Self {
+        let username = profile.login.clone();
+        let mut entry = Entry::new(EntryKind::Derived(profile));
+        entry.username = username;
+        entry
+    }
+
+    pub fn entry_type(&self) -> EntryType {
+        match self.kind {
+            EntryKind::Login { .. } => EntryType::Login,
+            EntryKind::SecureNote { .. } => EntryType::SecureNote,
+            EntryKind::PaymentCard(_) => EntryType::PaymentCard,
+            EntryKind::Identity(_) => EntryType::Identity,
+            EntryKind::SshKey(_) => EntryType::SshKey,
+            EntryKind::Passkey(_) => EntryType::Passkey,
+            EntryKind::Custom { .. } => EntryType::Custom,
+            EntryKind::Derived(_) => EntryType::Derived,
+        }
+    }
+
+    /// Every password like value in the entry as `(field, value)`, the ones audits look at.
+    pub fn secrets(&self) -> Vec<(&str, &str)> {
+        match &self.kind {
+            EntryKind::Login { password } => vec![("password", password.as_str())],
+            EntryKind::Custom { fields, .. } => fields
+                .iter()
+                .filter(|f| f.field_type == FieldType::Protected && !f.value.is_empty())
+                .map(|f| (f.name.as_str(), f.value.as_str()))
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    pub fn has_otp(&self) -> bool {
+        match &self.kind {
+            EntryKind::Custom { fields, .. } => fields
+                .iter()
+                .any(|f| f.field_type == FieldType::Otp && !f.value.is_empty()),
+            _ => false,
+        }
+    }
+
+    pub fn field(&self, field: EntryField) -> Result<String, &'static str> {
+        let value = match (&self.kind, field) {
+            (_, EntryField::Username) => Some(self.username.clone()).filter(|u| !u.is_empty()),
+            (EntryKind::Login { password }, EntryField::Password) => Some(password.clone()),
+            (EntryKind::Derived(_), EntryField::Password) => {
+                return Err("Derived passwords need the master password")
+            }
+            (EntryKind::PaymentCard(card), EntryField::Number) => Some(card.number.clone()),
+            (EntryKind::PaymentCard(card), EntryField::Holder) => Some(card.holder.clone()),
+            (EntryKind::PaymentCard(card), EntryField::Expiry) => Some(card.expiry.to_string()),
+            (EntryKind::PaymentCard(card), EntryField::Cvv) => Some(card.cvv.clone()),
+            (EntryKind::PaymentCard(card), EntryField::Pin) => card.pin.clone(),
+            (EntryKind::Identity(identity), EntryField::FullName) => {
+                Some(identity.full_name.clone())
+            }
+            (EntryKind::Identity(identity), EntryField::Address) => identity.address.clone(),
+            (EntryKind::Identity(identity), EntryField::Phone) => identity.phone.clone(),
+            (EntryKind::Identity(identity), EntryField::PassportNumber) => {
+                identity.passport_number.clone()
+            }
+            (EntryKind::Identity(identity), EntryField::LicenceNumber) => {
+                identity.licence_number.clone()
+            }
+            (EntryKind::SshKey(key), EntryField::PrivateKey) => Some(key.private_key.clone()),
+            (EntryKind::SshKey(key), EntryField::PublicKey) => Some(key.public_key.clone()),
+            _ => return Err("That entry does not have that field"),
+        };
+
+        value.ok_or("That field is empty")
+    }
+
+    pub fn warnings(&self) -> Vec<String> {
+        match &self.kind {
+            EntryKind::PaymentCard(card) if card.expiry.is_expired() => {
+                vec![format!("Card expired at the end of {}", card.expiry)]
+            }
+            _ => vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields_follow_the_entry_kind() {
+        let mut login = Entry::login("hunter2".to_string());
+        assert_eq!(login.field(EntryField::Password), Ok("hunter2".to_string()));
+        assert_eq!(
+            login.field(EntryField::Username),
+            Err("That field is empty")
+        );
+        assert_eq!(
+            login.field(EntryField::Cvv),
+            Err("That entry does not have that field")
+        );
+
+        login.username = "alice".to_string();
+        assert_eq!(login.field(EntryField::Username), Ok("alice".to_string()));
+
+        let note = Entry::secure_note("text".to_string());
+        assert_eq!(note.entry_type(), EntryType::SecureNote);
+        assert!(note.field(EntryField::Password).is_err());
+        assert!(note.secrets().is_empty());
+    }
+
+    #[test]
+    fn strength_is_only_rated_for_logins() {
+        let mut login = Entry::login("password".to_string());
+        login.rate_strength("email");
+        assert_eq!(login.strength, Some(0));
+
+        let mut note = Entry::secure_note("password".to_string());
+        note.rate_strength("email");
+        assert_eq!(note.strength, None);
+    }
+
+    #[test]
+    fn rotation_interval_counts_from_the_last_change() {
+        let mut entry = Entry::login("hunter2".to_string());
+        assert_eq!(entry.due_date(), None);
+
+        entry.expiry = Some(Expiry::Every { days: 30 });
+        assert_eq!(entry.days_until_due(), Some(30));
+
+        entry.modified -= 40 * 86_400;
+        assert_eq!(entry.days_until_due(), Some(-10));
+
+        entry.touch();
+        assert_eq!(entry.days_until_due(), Some(30));
+
+        let date = Date::parse("2000-01-01").unwrap();
+        entry.expiry = Some(Expiry::On(date));
+        assert_eq!(entry.due_date(), Some(date));
+    }
+}
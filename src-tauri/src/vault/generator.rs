@@ -0,0 +1,343 @@
+use ring::rand::{generate, SystemRandom};
+use serde::Deserialize;
+
+const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPERCASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &str = "0123456789";
+const SYMBOLS: &str = "!@#$%^&*()-_=+[]{};:,.<>/?~";
+/// Characters easily mistaken for one another when read or typed by hand.
+const AMBIGUOUS: &str = "Il1|O0o`'\"";
+
+const MIN_LENGTH: usize = 4;
+const MAX_LENGTH: usize = 1024;
+/// Draws to try before giving up on a `max_consecutive` limit the character sets can't meet.
+const MAX_ATTEMPTS: usize = 1000;
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct CustomCharacterSet {
+    pub characters: String,
+    #[serde(default)]
+    pub min: usize,
+}
+
+/// What a generated password must look like. Minimum counts only apply to enabled classes.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct PasswordPolicy {
+    pub length: usize,
+    pub lowercase: bool,
+    pub uppercase: bool,
+    pub digits: bool,
+    pub symbols: bool,
+    pub min_lowercase: usize,
+    pub min_uppercase: usize,
+    pub min_digits: usize,
+    pub min_symbols: usize,
+    pub exclude_ambiguous: bool,
+    pub custom_sets: Vec<CustomCharacterSet>,
+    /// Longest run of one repeated character allowed.
+    pub max_consecutive: Option<usize>,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        PasswordPolicy {
+            length: 20,
+            lowercase: true,
+            uppercase: true,
+            digits: true,
+            symbols: true,
+            min_lowercase: 1,
+            min_uppercase: 1,
+            min_digits: 1,
+            min_symbols: 1,
+            exclude_ambiguous: false,
+            custom_sets: vec![],
+            max_consecutive: None,
+        }
+    }
+}
+
+/// Where a generated secret goes instead of being handed back to the webview.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum GenerateTarget {
+    NewEntry { name: String },
+    ExistingEntry { name: String },
+}
+
+/// A uniformly random index below `bound`, rejecting draws that would bias the modulo.
+pub fn random_index(rng: &SystemRandom, bound: usize) -> usize {
+    let bound = bound as u64;
+    let zone = u64::MAX - u64::MAX % bound;
+    loop {
+        let value = u64::from_le_bytes(generate::<[u8; 8]>(rng).unwrap().expose());
+        if value < zone {
+            return (value % bound) as usize;
+        }
+    }
+}
+
+/// Length of the longest run of one repeated character.
+pub fn longest_run(chars: &[char]) -> usize {
+    chars
+        .chunk_by(|a, b| a == b)
+        .map(|run| run.len())
+        .max()
+        .unwrap_or(0)
+}
+
+pub fn shuffle<T>(rng: &SystemRandom, items: &mut [T]) {
+    for i in (1..items.len()).rev() {
+        items.swap(i, random_index(rng, i + 1));
+    }
+}
+
+impl PasswordPolicy {
+    /// The enabled character classes with their minimum counts, after exclusions.
+    fn classes(&self) -> Result<Vec<(Vec<char>, usize)>, String> {
+        let mut classes = vec![];
+        let standard = [
+            (self.lowercase, LOWERCASE, self.min_lowercase),
+            (self.uppercase, UPPERCASE, self.min_uppercase),
+            (self.digits, DIGITS, self.min_digits),
+            (self.symbols, SYMBOLS, self.min_symbols),
+        ];
+        for (enabled, characters, min) in standard {
+            if enabled {
+                classes.push((characters.to_string(), min));
+            }
+        }
+        for set in &self.custom_sets {
+            classes.push((set.characters.clone(), set.min));
+        }
+
+        let mut filtered = vec![];
+        for (characters, min) in classes {
+            let mut chars: Vec<char> = characters
+                .chars()
+                .filter(|c| !c.is_control() && !c.is_whitespace())
+                .filter(|c| !self.exclude_ambiguous || !AMBIGUOUS.contains(*c))
+                .collect();
+            chars.sort_unstable();
+            chars.dedup();
+
+            if chars.is_empty() {
+                return Err(String::from(
+                    "A character set has no usable characters left",
+                ));
+            }
+            filtered.push((chars, min));
+        }
+
+        if filtered.is_empty() {
+            return Err(String::from("Enable at least one kind of character"));
+        }
+        Ok(filtered)
+    }
+
+    pub fn generate(&self) -> Result<String, String> {
+        if !(MIN_LENGTH..=MAX_LENGTH).contains(&self.length) {
+            return Err(format!(
+                "Length must be between {} and {}",
+                MIN_LENGTH, MAX_LENGTH
+            ));
+        }
+
+        let classes = self.classes()?;
+        if classes.iter().map(|(_, min)| min).sum::<usize>() > self.length {
+            return Err(String::from(
+                "The minimum counts add up to more than the length",
+            ));
+        }
+
+        let mut pool: Vec<char> = classes
+            .iter()
+            .flat_map(|(chars, _)| chars.clone())
+            .collect();
+        pool.sort_unstable();
+        pool.dedup();
+
+        if self.max_consecutive == Some(0) {
+            return Err(String::from("Max consecutive must be at least 1"));
+        }
+
+        let rng = SystemRandom::new();
+        for _ in 0..MAX_ATTEMPTS {
+            let mut password = Vec::with_capacity(self.length);
+            for (chars, min) in &classes {
+                for _ in 0..*min {
+                    password.push(chars[random_index(&rng, chars.len())]);
+                }
+            }
+            while password.len() < self.length {
+                password.push(pool[random_index(&rng, pool.len())]);
+            }
+            shuffle(&rng, &mut password);
+
+            if self
+                .max_consecutive
+                .is_none_or(|max| longest_run(&password) <= max)
+            {
+                return Ok(password.into_iter().collect());
+            }
+        }
+
+        Err(String::from(
+            "Could not generate a password without too many repeated characters",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digits_only() -> PasswordPolicy {
+        PasswordPolicy {
+            lowercase: false,
+            uppercase: false,
+            symbols: false,
+            min_lowercase: 0,
+            min_uppercase: 0,
+            min_symbols: 0,
+            ..Default::default()
+        }
+    }
+
+    fn count(password: &str, class: &str) -> usize {
+        password.chars().filter(|c| class.contains(*c)).count()
+    }
+
+    #[test]
+    fn default_policy_uses_every_class() {
+        let password = PasswordPolicy::default().generate().unwrap();
+        assert_eq!(password.chars().count(), 20);
+        for class in [LOWERCASE, UPPERCASE, DIGITS, SYMBOLS] {
+            assert!(count(&password, class) >= 1, "{password}");
+        }
+    }
+
+    #[test]
+    fn honours_minimum_counts() {
+        let policy = PasswordPolicy {
+            length: 12,
+            min_lowercase: 2,
+            min_uppercase: 3,
+            min_digits: 4,
+            min_symbols: 3,
+            ..Default::default()
+        };
+        for _ in 0..100 {
+            let password = policy.generate().unwrap();
+            assert_eq!(count(&password, LOWERCASE), 2, "{password}");
+            assert_eq!(count(&password, UPPERCASE), 3, "{password}");
+            assert_eq!(count(&password, DIGITS), 4, "{password}");
+            assert_eq!(count(&password, SYMBOLS), 3, "{password}");
+        }
+    }
+
+    #[test]
+    fn excludes_ambiguous_characters() {
+        let policy = PasswordPolicy {
+            length: 200,
+            exclude_ambiguous: true,
+            custom_sets: vec![CustomCharacterSet {
+                characters: String::from("|`x"),
+                min: 1,
+            }],
+            ..Default::default()
+        };
+        for _ in 0..20 {
+            let password = policy.generate().unwrap();
+            assert_eq!(count(&password, AMBIGUOUS), 0, "{password}");
+            assert!(password.contains('x'));
+        }
+    }
+
+    #[test]
+    fn limits_consecutive_repeats() {
+        let policy = PasswordPolicy {
+            length: 30,
+            max_consecutive: Some(1),
+            ..digits_only()
+        };
+        for _ in 0..20 {
+            let password: Vec<char> = policy.generate().unwrap().chars().collect();
+            assert_eq!(longest_run(&password), 1);
+        }
+        assert_eq!(longest_run(&['a', 'a', 'b', 'b', 'b', 'a']), 3);
+        assert_eq!(longest_run(&[]), 0);
+    }
+
+    #[test]
+    fn rejects_policies_that_cannot_be_met() {
+        let error = |policy: PasswordPolicy| policy.generate().unwrap_err();
+
+        assert_eq!(
+            error(PasswordPolicy {
+                length: 3,
+                ..Default::default()
+            }),
+            "Length must be between 4 and 1024"
+        );
+        assert_eq!(
+            error(PasswordPolicy {
+                length: 8,
+                min_digits: 9,
+                ..Default::default()
+            }),
+            "The minimum counts add up to more than the length"
+        );
+        assert_eq!(
+            error(PasswordPolicy {
+                lowercase: false,
+                uppercase: false,
+                digits: false,
+                symbols: false,
+                ..Default::default()
+            }),
+            "Enable at least one kind of character"
+        );
+        assert_eq!(
+            error(PasswordPolicy {
+                exclude_ambiguous: true,
+                custom_sets: vec![CustomCharacterSet {
+                    characters: String::from("O0 "),
+                    min: 0,
+                }],
+                ..Default::default()
+            }),
+            "A character set has no usable characters left"
+        );
+        assert_eq!(
+            error(PasswordPolicy {
+                max_consecutive: Some(0),
+                ..Default::default()
+            }),
+            "Max consecutive must be at least 1"
+        );
+        assert_eq!(
+            error(PasswordPolicy {
+                digits: false,
+                custom_sets: vec![CustomCharacterSet {
+                    characters: String::from("a"),
+                    min: 0,
+                }],
+                max_consecutive: Some(1),
+                ..digits_only()
+            }),
+            "Could not generate a password without too many repeated characters"
+        );
+    }
+
+    #[test]
+    fn random_index_stays_in_bounds() {
+        let rng = SystemRandom::new();
+        let mut seen = [false; 7];
+        for _ in 0..1000 {
+            seen[random_index(&rng, 7)] = true;
+        }
+        assert!(seen.iter().all(|s| *s));
+    }
+}
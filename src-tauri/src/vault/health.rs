@@ -0,0 +1,403 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+use serde::Serialize;
+
+use crate::vault::date::now_secs;
+use crate::vault::entry::{Entry, EntryKind, EntryType};
+use crate::vault::strength;
+use crate::vault::url_match::{registrable_domain, site_of};
+
+/// Sites known to offer authenticator app codes.
+const TWO_FACTOR_SITES: &str = include_str!("two_factor_sites.txt");
+
+/// Scores at or below this are reported as weak.
+const WEAK_SCORE: u8 = 2;
+const OLD_AFTER_DAYS: u64 = 365;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthIssueKind {
+    Reused,
+    Weak,
+    Old,
+    MissingTwoFactor,
+    Duplicate,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct HealthIssue {
+    pub name: String,
+    pub kind: HealthIssueKind,
+    pub reason: String,
+}
+
+/// Findings name entries and explain why, they never carry the secrets themselves.
+#[derive(Serialize, Clone, Debug)]
+pub struct HealthReport {
+    pub entries_checked: usize,
+    pub issues: Vec<HealthIssue>,
+}
+
+fn two_factor_sites() -> &'static HashSet<&'static str> {
+    static SITES: OnceLock<HashSet<&'static str>> = OnceLock::new();
+    SITES.get_or_init(|| TWO_FACTOR_SITES.lines().collect())
+}
+
+fn sites(entry: &Entry) -> HashSet<String> {
+    entry.urls.iter().filter_map(|url| site_of(url)).collect()
+}
+
+fn join_names(names: &[&str]) -> String {
+    match names {
+        [] => String::new(),
+        [one] => one.to_string(),
+        [first, second, third, rest @ ..] if !rest.is_empty() => {
+            format!("{}, {}, {} and {} more", first, second, third, rest.len())
+        }
+        [rest @ .., last] => format!("{} and {}", rest.join(", "), last),
+    }
+}
+
+fn reused(entries: &HashMap<String, Entry>, issues: &mut Vec<HealthIssue>) {
+    let mut by_secret: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (name, entry) in entries {
+        for (_, secret) in entry.secrets() {
+            let names = by_secret.entry(secret).or_default();
+            if !names.contains(&name.as_str()) {
+                names.push(name);
+            }
+        }
+    }
+
+    for names in by_secret.values().filter(|names| names.len() > 1) {
+        for name in names {
+            let mut others: Vec<&str> = names.iter().filter(|n| *n != name).copied().collect();
+            others.sort();
+            issues.push(HealthIssue {
+                name: name.to_string(),
+                kind: HealthIssueKind::Reused,
+                reason: format!("Same secret is also used by {}", join_names(&others)),
+            });
+        }
+    }
+}
+
+fn weak(name: &str, entry: &Entry, issues: &mut Vec<HealthIssue>) {
+    // Logins keep their score, only rerun the estimator when the reason is needed.
+    if entry.strength.is_some_and(|score| score > WEAK_SCORE) {
+        return;
+    }
+
+    for (field, secret) in entry.secrets() {
+        let estimate = strength::estimate(secret, &[name, &entry.username]);
+        if estimate.score > WEAK_SCORE {
+            continue;
+        }
+
+        let mut reason = format!("The {} scores {} out of 4", field, estimate.score);
+        if let Some(warning) = estimate.feedback.warning {
+            reason = format!("{}. {}", reason, warning);
+        }
+        issues.push(HealthIssue {
+            name: name.to_string(),
+            kind: HealthIssueKind::Weak,
+            reason,
+        });
+    }
+}
+
+fn old(name: &str, entry: &Entry, issues: &mut Vec<HealthIssue>) {
+    if entry.secrets().is_empty() {
+        return;
+    }
+
+    let age = now_secs().saturating_sub(entry.modified) / 86_400;
+    if age >= OLD_AFTER_DAYS {
+        issues.push(HealthIssue {
+            name: name.to_string(),
+            kind: HealthIssueKind::Old,
+            reason: format!("Not changed in {} days", age),
+        });
+    }
+}
+
+/// A login on a site that offers authenticator codes, where no entry for that site holds an
+/// OTP secret or a passkey.
+fn missing_two_factor(entries: &HashMap<String, Entry>, issues: &mut Vec<HealthIssue>) {
+    let mut covered: HashSet<String> = HashSet::new();
+    for entry in entries.values() {
+        match &entry.kind {
+            EntryKind::Passkey(passkey) => {
+                let rp_id = passkey.rp_id.as_str();
+                covered.insert(registrable_domain(rp_id).unwrap_or(rp_id).to_string());
+            }
+            _ if entry.has_otp() => covered.extend(sites(entry)),
+            _ => {}
+        }
+    }
+
+    for (name, entry) in entries {
+        if !matches!(entry.kind, EntryKind::Login { .. }) {
+            continue;
+        }
+        let mut missing: Vec<String> = sites(entry)
+            .into_iter()
+            .filter(|site| two_factor_sites().contains(site.as_str()) && !covered.contains(site))
+            .collect();
+        missing.sort();
+
+        if let Some(site) = missing.first() {
+            issues.push(HealthIssue {
+                name: name.clone(),
+                kind: HealthIssueKind::MissingTwoFactor,
+                reason: format!("{} supports authenticator codes but none are stored", site),
+            });
+        }
+    }
+}
+
+/// Titles that only differ in case and punctuation, or the same username on the same site.
+/// Each group is reported against its alphabetically first entry.
+fn duplicates(entries: &HashMap<String, Entry>, issues: &mut Vec<HealthIssue>) {
+    let mut groups: HashMap<(EntryType, String, String), Vec<&str>> = HashMap::new();
+
+    for (name, entry) in entries {
+        let title: String = name
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(char::to_lowercase)
+            .collect();
+        if !title.is_empty() {
+            groups
+                .entry((entry.entry_type(), title, String::new()))
+                .or_default()
+                .push(name);
+        }
+
+        if !entry.username.is_empty() {
+            for site in sites(entry) {
+                groups
+                    .entry((entry.entry_type(), entry.username.to_lowercase(), site))
+                    .or_default()
+                    .push(name);
+            }
+        }
+    }
+
+    let mut reported = HashSet::new();
+    for names in groups.values_mut().filter(|names| names.len() > 1) {
+        names.sort();
+        for name in &names[1..] {
+            if reported.insert((*name, names[0])) {
+                issues.push(HealthIssue {
+                    name: name.to_string(),
+                    kind: HealthIssueKind::Duplicate,
+                    reason: format!("Looks like a duplicate of {}", names[0]),
+                });
+            }
+        }
+    }
+}
+
+pub fn audit(entries: &HashMap<String, Entry>) -> HealthReport {
+    let mut issues = vec![];
+
+    reused(entries, &mut issues);
+    for (name, entry) in entries {
+        weak(name, entry, &mut issues);
+        old(name, entry, &mut issues);
+    }
+    missing_two_factor(entries, &mut issues);
+    duplicates(entries, &mut issues);
+
+    issues.sort_by(|a, b| (a.kind, &a.name, &a.reason).cmp(&(b.kind, &b.name, &b.reason)));
+
+    HealthReport {
+        entries_checked: entries.len(),
+        issues,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault::passkey::Passkey;
+    use crate::vault::template::{CustomField, FieldType};
+
+    fn login(password: &str, username: &str, url: &str) -> Entry {
+        let mut entry = Entry::login(password.to_string());
+        entry.username = username.to_string();
+        if !url.is_empty() {
+            entry.urls = vec![url.to_string()];
+        }
+        entry.rate_strength("entry");
+        entry
+    }
+
+    fn issues(entries: &[(&str, Entry)], kind: HealthIssueKind) -> Vec<(String, String)> {
+        let entries: HashMap<String, Entry> = entries
+            .iter()
+            .map(|(name, entry)| (name.to_string(), entry.clone()))
+            .collect();
+        audit(&entries)
+            .issues
+            .into_iter()
+            .filter(|issue| issue.kind == kind)
+            .map(|issue| (issue.name, issue.reason))
+            .collect()
+    }
+
+    #[test]
+    fn reports_reused_secrets_against_every_entry() {
+        let found = issues(
+            &[
+                ("a", login("kX9#mq2!vLp7@Zr", "", "")),
+                ("b", login("kX9#mq2!vLp7@Zr", "", "")),
+                ("c", login("Wq4$tn8^bRs1&Yh", "", "")),
+            ],
+            HealthIssueKind::Reused,
+        );
+        assert_eq!(
+            found,
+            [
+                (
+                    String::from("a"),
+                    String::from("Same secret is also used by b")
+                ),
+                (
+                    String::from("b"),
+                    String::from("Same secret is also used by a")
+                ),
+            ]
+        );
+        assert_eq!(join_names(&["a", "b", "c", "d", "e"]), "a, b, c and 2 more");
+    }
+
+    #[test]
+    fn reports_weak_secrets_with_the_reason() {
+        let found = issues(
+            &[
+                ("weak", login("password", "", "")),
+                ("strong", login("kX9#mq2!vLp7@Zr", "", "")),
+            ],
+            HealthIssueKind::Weak,
+        );
+        assert_eq!(
+            found,
+            [(
+                String::from("weak"),
+                String::from("The password scores 0 out of 4. This is a top-10 common password")
+            )]
+        );
+    }
+
+    #[test]
+    fn reports_secrets_unchanged_for_a_year() {
+        let mut old = login("kX9#mq2!vLp7@Zr", "", "");
+        old.modified -= 400 * 86_400;
+        let mut note = Entry::secure_note(String::from("text"));
+        note.modified = 0;
+
+        let found = issues(
+            &[
+                ("old", old),
+                ("new", login("Wq4$tn8^bRs1&Yh", "", "")),
+                ("note", note),
+            ],
+            HealthIssueKind::Old,
+        );
+        assert_eq!(
+            found,
+            [(String::from("old"), String::from("Not changed in 400 days"))]
+        );
+    }
+
+    #[test]
+    fn reports_missing_two_factor_unless_a_code_or_passkey_is_stored() {
+        let github = login("kX9#mq2!vLp7@Zr", "bob", "https://github.com/login");
+        let google = login("Wq4$tn8^bRs1&Yh", "bob", "https://accounts.google.com");
+        let unknown = login("Zp3@vk7!cHs9*Lm", "bob", "https://example.org");
+
+        let mut codes = Entry::custom(
+            String::from("Authenticator"),
+            vec![CustomField {
+                name: String::from("secret"),
+                field_type: FieldType::Otp,
+                value: String::from("JBSWY3DPEHPK3PXP"),
+            }],
+        );
+        codes.urls = vec![String::from("https://github.com")];
+
+        let found = issues(
+            &[
+                ("GitHub", github.clone()),
+                ("Google", google.clone()),
+                ("Example", unknown),
+            ],
+            HealthIssueKind::MissingTwoFactor,
+        );
+        assert_eq!(
+            found,
+            [
+                (
+                    String::from("GitHub"),
+                    String::from("github.com supports authenticator codes but none are stored")
+                ),
+                (
+                    String::from("Google"),
+                    String::from("google.com supports authenticator codes but none are stored")
+                ),
+            ]
+        );
+
+        let (passkey, _) =
+            Passkey::register("accounts.google.com", vec![1], "bob", &[0; 32], true).unwrap();
+        let found = issues(
+            &[
+                ("GitHub", github),
+                ("Google", google),
+                ("GitHub codes", codes),
+                ("Google passkey", Entry::passkey(passkey)),
+            ],
+            HealthIssueKind::MissingTwoFactor,
+        );
+        assert!(found.is_empty());
+    }
+
+    #[test]
+    fn reports_duplicates_against_the_first_title() {
+        let found = issues(
+            &[
+                ("GitHub", login("kX9#mq2!vLp7@Zr", "", "")),
+                ("git-hub", login("Wq4$tn8^bRs1&Yh", "", "")),
+                (
+                    "Work",
+                    login("Zp3@vk7!cHs9*Lm", "bob", "https://mail.example.com"),
+                ),
+                (
+                    "Mail",
+                    login("Hd6%qw2#xTr8+Nb", "Bob", "https://example.com/inbox"),
+                ),
+                (
+                    "Other",
+                    login("Jf5&ze1!mPk4=Vc", "alice", "https://example.com"),
+                ),
+            ],
+            HealthIssueKind::Duplicate,
+        );
+        assert_eq!(
+            found,
+            [
+                (
+                    String::from("Work"),
+                    String::from("Looks like a duplicate of Mail")
+                ),
+                (
+                    String::from("git-hub"),
+                    String::from("Looks like a duplicate of GitHub")
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,65 @@
+use bincode::{Decode, Encode};
+
+#[derive(Clone, Encode, Decode, Debug)]
+pub struct Identity {
+    pub full_name: String,
+    pub address: Option<String>,
+    pub phone: Option<String>,
+    pub passport_number: Option<String>,
+    pub licence_number: Option<String>,
+}
+
+fn optional(value: Option<&str>) -> Option<String> {
+    value
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(String::from)
+}
+
+impl Identity {
+    pub fn new(
+        full_name: &str,
+        address: Option<&str>,
+        phone: Option<&str>,
+        passport_number: Option<&str>,
+        licence_number: Option<&str>,
+    ) -> Result<Identity, &'static str> {
+        let full_name = full_name.trim();
+        if full_name.is_empty() {
+            return Err("You need a name for this identity");
+        }
+
+        let phone = optional(phone);
+        if let Some(phone) = &phone {
+            let allowed = phone
+                .chars()
+                .all(|c| c.is_ascii_digit() || " +-().".contains(c));
+            let digits = phone.chars().filter(|c| c.is_ascii_digit()).count();
+            if !allowed || !(7..=15).contains(&digits) {
+                return Err("That phone number is not valid");
+            }
+        }
+
+        let passport_number = optional(passport_number).map(|p| p.to_uppercase());
+        if let Some(passport) = &passport_number {
+            if !passport.chars().all(|c| c.is_ascii_alphanumeric()) || !(5..=20).contains(&passport.len()) {
+                return Err("Passport numbers must be 5 to 20 letters or digits");
+            }
+        }
+
+        let licence_number = optional(licence_number);
+        if let Some(licence) = &licence_number {
+            if !licence.chars().all(|c| c.is_ascii_alphanumeric() || c == ' ' || c == '-') {
+                return Err("Licence numbers may only contain letters, digits, spaces and dashes");
+            }
+        }
+
+        Ok(Identity {
+            full_name: full_name.to_string(),
+            address: optional(address),
+            phone,
+            passport_number,
+            licence_number,
+        })
+    }
+}
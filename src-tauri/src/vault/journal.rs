@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+
+use bincode::{config, decode_from_slice, encode_to_vec, Decode, Encode};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN};
+use ring::digest;
+use ring::hkdf::{Salt, HKDF_SHA256};
+use ring::rand::{SecureRandom, SystemRandom};
+
+use crate::vault::entry::Entry;
+use crate::vault::master_password::Credential;
+
+/// Changes recorded before the vault is written out whole and the journal starts over.
+pub const COMPACT_AFTER: usize = 64;
+
+/// One change to a vault. It carries the entry as it now is rather than what changed, so
+/// replaying a change that is already in the vault file does nothing.
+#[derive(Encode, Decode, Debug)]
+pub enum Change {
+    Put { name: String, entry: Box<Entry> },
+    Remove { name: String },
+}
+
+impl Change {
+    pub fn apply(self, entries: &mut HashMap<String, Entry>) {
+        match self {
+            Change::Put { name, entry } => {
+                entries.insert(name, *entry);
+            }
+            Change::Remove { name } => {
+                entries.remove(&name);
+            }
+        }
+    }
+}
+
+const KEY_INFO: &[u8] = b"ancrypt journal v1";
+const TAG_LEN: usize = digest::SHA256_OUTPUT_LEN;
+
+/// Names the vault file a record was made after, in the clear so a record left over from an
+/// earlier file can be told apart from a damaged one.
+fn snapshot_tag(snapshot: &[u8]) -> digest::Digest {
+    digest::digest(&digest::SHA256, snapshot)
+}
+
+/// Each vault file gets a journal key of its own from the vault key, so the random record
+/// nonces never share a key with the counter nonces of the vault file.
+fn key(credential: &Credential, snapshot: &[u8]) -> LessSafeKey {
+    let prk = Salt::new(HKDF_SHA256, snapshot).extract(credential);
+    let okm = prk.expand(&[KEY_INFO], &CHACHA20_POLY1305).unwrap();
+    LessSafeKey::new(UnboundKey::from(okm))
+}
+
+/// Encrypts a change for the journal, bound to `snapshot` so it only opens against the vault
+/// file it was made after. Every record gets a random nonce, it has no counter to carry.
+pub fn seal(credential: &Credential, snapshot: &[u8], change: &Change) -> Vec<u8> {
+    let mut nonce = [0u8; NONCE_LEN];
+    SystemRandom::new().fill(&mut nonce).unwrap();
+    let tag = snapshot_tag(snapshot);
+
+    let mut data = encode_to_vec(change, config::standard()).unwrap();
+    key(credential, snapshot)
+        .seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(tag.as_ref()),
+            &mut data,
+        )
+        .unwrap();
+
+    let mut record = tag.as_ref().to_vec();
+    record.extend(nonce);
+    record.extend(data);
+    record
+}
+
+/// Opens a record made after the vault file `snapshot` names. A record made after an earlier
+/// file is already part of this one and comes back as `None`, one that is damaged or was
+/// encrypted under another key is an error.
+pub fn open(
+    credential: &Credential,
+    snapshot: &[u8],
+    record: &[u8],
+) -> Result<Option<Change>, String> {
+    let damaged = || String::from("A change in the journal is damaged");
+    if record.len() < TAG_LEN + NONCE_LEN {
+        return Err(damaged());
+    }
+    let (tag, rest) = record.split_at(TAG_LEN);
+    if tag != snapshot_tag(snapshot).as_ref() {
+        return Ok(None);
+    }
+    let (nonce, sealed) = rest.split_at(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| damaged())?;
+
+    let mut sealed = sealed.to_vec();
+    let data = key(credential, snapshot)
+        .open_in_place(nonce, Aad::from(tag), &mut sealed)
+        .map_err(|_| damaged())?;
+    decode_from_slice(data, config::standard())
+        .map(|(change, _)| Some(change))
+        .map_err(|_| damaged())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn remove(name: &str) -> Change {
+        Change::Remove {
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn records_open_only_against_their_vault_file() {
+        let credential = [7u8; 32];
+        let record = seal(&credential, b"snapshot one", &remove("bank"));
+
+        let change = open(&credential, b"snapshot one", &record).unwrap();
+        assert!(matches!(change, Some(Change::Remove { name }) if name == "bank"));
+        assert!(open(&credential, b"snapshot two", &record)
+            .unwrap()
+            .is_none());
+
+        assert!(open(&[8u8; 32], b"snapshot one", &record).is_err());
+        let mut damaged = record.clone();
+        damaged[TAG_LEN] ^= 1;
+        assert!(open(&credential, b"snapshot one", &damaged).is_err());
+        assert!(open(&credential, b"snapshot one", &record[..TAG_LEN]).is_err());
+    }
+
+    #[test]
+    fn records_are_not_sealed_with_the_vault_key() {
+        let credential = [7u8; 32];
+        let record = seal(&credential, b"snapshot", &remove("bank"));
+        let (tag, rest) = record.split_at(TAG_LEN);
+        let (nonce, sealed) = rest.split_at(NONCE_LEN);
+
+        let vault_key = LessSafeKey::new(UnboundKey::new(&CHACHA20_POLY1305, &credential).unwrap());
+        let mut sealed = sealed.to_vec();
+        let nonce = Nonce::try_assume_unique_for_key(nonce).unwrap();
+        assert!(vault_key
+            .open_in_place(nonce, Aad::from(tag), &mut sealed)
+            .is_err());
+    }
+}
@@ -0,0 +1,94 @@
+use std::fmt;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use crate::storage;
+
+/// Held for as long as a vault is open for writing, dropping it lets the next writer in. What
+/// is held depends on the store, for vault files it is an OS lock on `{vault}.lock`.
+pub struct VaultLock {
+    _held: Box<dyn Send>,
+}
+
+impl VaultLock {
+    pub fn new(held: impl Send + 'static) -> Self {
+        VaultLock {
+            _held: Box::new(held),
+        }
+    }
+}
+
+impl fmt::Debug for VaultLock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VaultLock").finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug)]
+pub enum Access {
+    /// Ours to change. `recovered` describes the holder when the last process to open the
+    /// vault crashed without closing it.
+    Writable {
+        lock: VaultLock,
+        recovered: Option<String>,
+    },
+    /// Another process has the vault open for writing, it can be read but not changed.
+    ReadOnly { holder: String },
+}
+
+/// The OS lets go of the lock when the process exits for any reason, so a crash can't leave a
+/// vault locked.
+struct LockFile {
+    file: File,
+}
+
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        // An empty file marks a clean close, the lock itself goes with the handle.
+        let _ = self.file.set_len(0);
+    }
+}
+
+/// The lock file holds the pid of whoever has the vault open.
+fn describe(contents: &str) -> Option<String> {
+    let pid: u32 = contents.trim().parse().ok()?;
+    Some(format!("process {}", pid))
+}
+
+/// Locks `{vault}.lock` in the vault folder.
+pub fn acquire(vault_name: &str) -> Result<Access, String> {
+    storage::ensure_vault_dir()?;
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(storage::lock_path(vault_name))
+        .map_err(|e| e.to_string())?;
+
+    let mut contents = String::new();
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => {
+            // Windows locks keep other processes from reading the file, so the holder can be
+            // unknown.
+            let _ = file.read_to_string(&mut contents);
+            let holder = describe(&contents).unwrap_or_else(|| String::from("another process"));
+            return Ok(Access::ReadOnly { holder });
+        }
+        Err(TryLockError::Error(e)) => return Err(e.to_string()),
+    }
+
+    let _ = file.read_to_string(&mut contents);
+    let recovered = describe(&contents);
+
+    file.set_len(0).map_err(|e| e.to_string())?;
+    file.seek(SeekFrom::Start(0)).map_err(|e| e.to_string())?;
+    write!(file, "{}", std::process::id()).map_err(|e| e.to_string())?;
+    file.sync_all().map_err(|e| e.to_string())?;
+
+    Ok(Access::Writable {
+        lock: VaultLock::new(LockFile { file }),
+        recovered,
+    })
+}
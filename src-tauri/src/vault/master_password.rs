@@ -11,6 +11,10 @@ use crate::vault::encrypted_password::EncryptedPasswords;
 
 static PBKDF2_ALG: pbkdf2::Algorithm = pbkdf2::PBKDF2_HMAC_SHA512;
 const CREDENTIAL_LEN: usize = digest::SHA512_256_OUTPUT_LEN;
+/// Bounds on the iteration count a stored vault can plausibly have, vaults are made with
+/// 600,000.
+const MIN_ITERATIONS: u32 = 10_000;
+const MAX_ITERATIONS: u32 = 10_000_000;
 pub type Credential = [u8; CREDENTIAL_LEN];
 
 pub enum Error {
@@ -67,6 +71,53 @@ impl Pbkdf2Component {
         .map_err(|_| Error::WrongUsernameOrPassword)
     }
 
+    pub fn derive_key(&mut self, password: &str) {
+        let mut out: Credential = [0u8; CREDENTIAL_LEN];
+        derive(
+            PBKDF2_ALG,
+            self.pbkdf2_iterations,
+            &self.db_salt_component,
+            password.as_bytes(),
+            &mut out,
+        );
+        self.derived_key = out;
+    }
+
+    pub fn iterations(&self) -> u32 {
+        self.pbkdf2_iterations.get()
+    }
+
+    pub fn describe(&self) -> String {
+        format!("PBKDF2-HMAC-SHA512 with {} iterations", self.iterations())
+    }
+
+    /// Catches key derivation settings this app can't have written, before any time is spent
+    /// deriving with them.
+    pub fn check_parameters(&self) -> Result<(), String> {
+        let iterations = self.iterations();
+        if !(MIN_ITERATIONS..=MAX_ITERATIONS).contains(&iterations) {
+            return Err(format!(
+                "{} PBKDF2 iterations is outside the expected {} to {}",
+                iterations, MIN_ITERATIONS, MAX_ITERATIONS
+            ));
+        }
+        if self.db_salt_component.iter().all(|&b| b == 0) {
+            return Err(String::from("The key derivation salt is blank"));
+        }
+        if self.master_password.1.iter().all(|&b| b == 0) {
+            return Err(String::from("The stored master password check is blank"));
+        }
+        Ok(())
+    }
+
+    /// Copy of the component that is safe to write to disk, the derived key must never be persisted.
+    pub fn for_storage(&self) -> Pbkdf2Component {
+        Pbkdf2Component {
+            derived_key: [0u8; CREDENTIAL_LEN],
+            ..self.clone()
+        }
+    }
+
     pub fn sanitise(self) -> Pbkdf2Component {
         Pbkdf2Component {
             derived_key: [0u8; CREDENTIAL_LEN],
@@ -88,17 +139,8 @@ pub fn init_master_password(password: &str) -> Pbkdf2Component {
         encrypted_passwords: EncryptedPasswords::new(),
     };
 
-    let mut out: [u8; CREDENTIAL_LEN] = [0; 32];
-    derive(
-        PBKDF2_ALG,
-        pass.pbkdf2_iterations,
-        &pass.db_salt_component,
-        password.as_bytes(),
-        &mut out,
-    );
-
     pass.create_password(password);
-    pass.derived_key = out;
+    pass.derive_key(password);
     pass.encrypted_passwords =
         EncryptedPasswords::initialise_data(pass.encrypted_passwords, pass.derived_key, vec![]);
     pass
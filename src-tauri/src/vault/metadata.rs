@@ -0,0 +1,228 @@
+use bincode::{config, decode_from_slice, encode_to_vec, Decode, Encode};
+use serde::{Deserialize, Serialize};
+
+use crate::vault::date::now_secs;
+
+/// Starts every vault file written with a header. Files without it predate headers and are
+/// format 0.
+const HEADER_MAGIC: &[u8; 4] = b"ANCV";
+pub const FORMAT_VERSION: u8 = 1;
+
+/// What the vault picker can show without unlocking. It is written to the vault file in the
+/// clear, so only what the user chose to expose is ever set, hidden fields are not kept at all.
+#[derive(Serialize, Deserialize, Clone, Encode, Decode, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct VaultMetadata {
+    pub display_name: Option<String>,
+    pub icon: Option<String>,
+    pub colour: Option<String>,
+    /// Unix time in seconds, a copy of the creation time kept in the encrypted payload.
+    pub created: Option<u64>,
+    /// Unix time in seconds the vault file was last written, edits still in the journal don't
+    /// move it.
+    pub modified: Option<u64>,
+    /// How the key is derived from the master password.
+    pub kdf: Option<String>,
+}
+
+/// The user's choice of what to expose. Names, icons and colours are exposed by setting them,
+/// the rest are filled in by the app when shown.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct MetadataSettings {
+    pub display_name: Option<String>,
+    pub icon: Option<String>,
+    pub colour: Option<String>,
+    pub show_created: bool,
+    pub show_modified: bool,
+    pub show_kdf: bool,
+}
+
+/// The header of a vault file as read by the picker.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct VaultHeader {
+    pub format_version: u8,
+    #[serde(flatten)]
+    pub metadata: VaultMetadata,
+}
+
+fn cleaned(value: Option<String>) -> Option<String> {
+    value
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+impl VaultMetadata {
+    /// The current choices, `created` being the vault's recorded creation time.
+    pub fn settings(&self, created: Option<u64>) -> MetadataSettings {
+        MetadataSettings {
+            display_name: self.display_name.clone(),
+            icon: self.icon.clone(),
+            colour: self.colour.clone(),
+            show_created: self.created.is_some() && created.is_some(),
+            show_modified: self.modified.is_some(),
+            show_kdf: self.kdf.is_some(),
+        }
+    }
+
+    /// Applies new settings. `created` is the creation time recorded in the encrypted payload,
+    /// it can only be exposed when there is one.
+    pub fn apply(
+        &mut self,
+        settings: MetadataSettings,
+        kdf: &str,
+        created: Option<u64>,
+    ) -> Result<(), String> {
+        let colour = cleaned(settings.colour);
+        if let Some(colour) = &colour {
+            let hex = colour.strip_prefix('#').unwrap_or_default();
+            if !matches!(hex.len(), 3 | 6) || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(String::from("Colours are written as #rgb or #rrggbb"));
+            }
+        }
+        if settings.show_created && created.is_none() {
+            return Err(String::from(
+                "This vault was made before its creation time was recorded",
+            ));
+        }
+
+        self.display_name = cleaned(settings.display_name);
+        self.icon = cleaned(settings.icon);
+        self.colour = colour;
+        self.created = created.filter(|_| settings.show_created);
+        self.modified = settings.show_modified.then(now_secs);
+        self.kdf = settings.show_kdf.then(|| kdf.to_string());
+        Ok(())
+    }
+
+    /// Brings the automatic fields that are exposed up to date for a save. A created time
+    /// exposed by an older version was made up when it was exposed, it is dropped when the
+    /// vault has no recorded one.
+    pub fn refresh(&mut self, kdf: &str, created: Option<u64>) {
+        if self.created.is_some() {
+            self.created = created;
+        }
+        if self.modified.is_some() {
+            self.modified = Some(now_secs());
+        }
+        if self.kdf.is_some() {
+            self.kdf = Some(kdf.to_string());
+        }
+    }
+}
+
+/// Puts the header in front of the rest of a vault file. The header is length prefixed so
+/// readers can skip it without decoding.
+pub fn join(metadata: &VaultMetadata, body: &[u8]) -> Vec<u8> {
+    let header = encode_to_vec(metadata, config::standard()).unwrap();
+
+    let mut file = HEADER_MAGIC.to_vec();
+    file.push(FORMAT_VERSION);
+    file.extend((header.len() as u32).to_le_bytes());
+    file.extend(header);
+    file.extend(body);
+    file
+}
+
+/// Splits a vault file into its header and the rest.
+pub fn split(file: &[u8]) -> Result<(VaultHeader, &[u8]), String> {
+    let Some(rest) = file.strip_prefix(HEADER_MAGIC) else {
+        return Ok((VaultHeader::default(), file));
+    };
+
+    let (&version, rest) = rest.split_first().ok_or("The header is cut short")?;
+    if version > FORMAT_VERSION {
+        return Err(format!(
+            "Format {} is newer than this version of the app reads",
+            version
+        ));
+    }
+    let len = rest
+        .get(..4)
+        .map(|len| u32::from_le_bytes(len.try_into().unwrap()) as usize)
+        .ok_or("The header is cut short")?;
+    let header = rest.get(4..4 + len).ok_or("The header is cut short")?;
+
+    let (metadata, _) = decode_from_slice::<VaultMetadata, _>(header, config::standard())
+        .map_err(|e| format!("The header could not be decoded: {}", e))?;
+    let header = VaultHeader {
+        format_version: version,
+        metadata,
+    };
+    Ok((header, &rest[4 + len..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_round_trips_and_old_files_have_none() {
+        let metadata = VaultMetadata {
+            display_name: Some(String::from("Work")),
+            colour: Some(String::from("#336699")),
+            created: Some(1_700_000_000),
+            ..Default::default()
+        };
+        let file = join(&metadata, b"body");
+        let (header, body) = split(&file).unwrap();
+        assert_eq!(header.format_version, FORMAT_VERSION);
+        assert_eq!(header.metadata, metadata);
+        assert_eq!(body, b"body");
+
+        let (header, body) = split(b"\0old vault").unwrap();
+        assert_eq!(header.format_version, 0);
+        assert_eq!(body, b"\0old vault");
+    }
+
+    #[test]
+    fn rejects_newer_and_cut_short_headers() {
+        let mut file = join(&VaultMetadata::default(), b"body");
+        file[4] = FORMAT_VERSION + 1;
+        assert!(split(&file).is_err());
+        assert!(split(&join(&VaultMetadata::default(), b"")[..7]).is_err());
+    }
+
+    #[test]
+    fn hiding_drops_the_value() {
+        let mut metadata = VaultMetadata::default();
+        let mut settings = MetadataSettings {
+            show_created: true,
+            colour: Some(String::from(" #abc ")),
+            ..Default::default()
+        };
+        metadata.apply(settings.clone(), "kdf", Some(1)).unwrap();
+        assert_eq!(metadata.colour.as_deref(), Some("#abc"));
+        assert!(metadata.created.is_some());
+        assert!(metadata.modified.is_none());
+
+        settings.show_created = false;
+        metadata.apply(settings.clone(), "kdf", Some(1)).unwrap();
+        assert!(metadata.created.is_none());
+
+        settings.colour = Some(String::from("blue"));
+        assert!(metadata.apply(settings, "kdf", Some(1)).is_err());
+    }
+
+    #[test]
+    fn only_a_recorded_created_time_is_exposed() {
+        let mut metadata = VaultMetadata::default();
+        let settings = MetadataSettings {
+            show_created: true,
+            ..Default::default()
+        };
+        metadata
+            .apply(settings.clone(), "kdf", Some(1_700_000_000))
+            .unwrap();
+        assert_eq!(metadata.created, Some(1_700_000_000));
+        metadata.refresh("kdf", Some(1_700_000_000));
+        assert_eq!(metadata.created, Some(1_700_000_000));
+
+        assert!(metadata.apply(settings, "kdf", None).is_err());
+        assert_eq!(metadata.created, Some(1_700_000_000));
+
+        metadata.refresh("kdf", None);
+        assert!(metadata.created.is_none());
+        assert!(!metadata.settings(None).show_created);
+    }
+}
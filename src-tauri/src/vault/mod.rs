@@ -1,3 +1,26 @@
+pub mod attachment;
+pub mod backup;
+pub mod breach;
+pub mod card;
+pub mod date;
+pub mod derived;
 pub mod encrypted_password;
+pub mod entry;
+pub mod generator;
+pub mod health;
+pub mod identity;
+pub mod journal;
+pub mod lock;
 pub mod master_password;
+pub mod metadata;
+pub mod passkey;
+pub mod passphrase;
+pub mod password_rules;
+pub mod search;
+pub mod ssh;
+pub mod strength;
+pub mod template;
+pub mod url_match;
+pub mod username;
 pub mod vault;
+pub mod verify;
@@ -0,0 +1,524 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use bincode::{Decode, Encode};
+use ring::{
+    digest::{digest, SHA256},
+    rand::{generate, SystemRandom},
+    signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING},
+};
+use serde::Serialize;
+
+/// AAGUID reported by Ancrypt's software authenticator.
+const AAGUID: [u8; 16] = [
+    0x6a, 0x1d, 0x3c, 0x0e, 0x52, 0x8b, 0x4f, 0x67, 0x9d, 0x2e, 0xa7, 0x41, 0x0c, 0x85, 0x3b, 0xf2,
+];
+
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_USER_VERIFIED: u8 = 0x04;
+const FLAG_BACKUP_ELIGIBLE: u8 = 0x08;
+const FLAG_ATTESTED_CREDENTIAL: u8 = 0x40;
+
+/// DER framing of an unencrypted PKCS#8 v1 P-256 key, the private scalar goes after the prefix
+/// and the uncompressed public point after the infix.
+const PKCS8_P256_PREFIX: [u8; 36] = [
+    0x30, 0x81, 0x87, 0x02, 0x01, 0x00, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02,
+    0x01, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07, 0x04, 0x6d, 0x30, 0x6b, 0x02,
+    0x01, 0x01, 0x04, 0x20,
+];
+const PKCS8_P256_INFIX: [u8; 5] = [0xa1, 0x44, 0x03, 0x42, 0x00];
+
+/// A WebAuthn credential. The vault file can be copied between devices so every passkey is
+/// reported as backup eligible.
+#[derive(Clone, Encode, Decode, Debug)]
+pub struct Passkey {
+    pub rp_id: String,
+    pub user_handle: Vec<u8>,
+    pub user_name: String,
+    pub credential_id: Vec<u8>,
+    pub private_key: Vec<u8>,
+    pub sign_count: u32,
+}
+
+/// Response to `navigator.credentials.create()`, binary fields are base64url without padding.
+#[derive(Serialize, Clone, Debug)]
+pub struct Registration {
+    pub credential_id: String,
+    pub authenticator_data: String,
+    pub attestation_object: String,
+    pub public_key: String,
+}
+
+/// Response to `navigator.credentials.get()`, binary fields are base64url without padding.
+#[derive(Serialize, Clone, Debug)]
+pub struct Assertion {
+    pub credential_id: String,
+    pub authenticator_data: String,
+    pub signature: String,
+    pub user_handle: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PasskeySummary {
+    pub name: String,
+    pub rp_id: String,
+    pub user_name: String,
+    pub credential_id: String,
+}
+
+pub fn encode_b64(bytes: &[u8]) -> String {
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+pub fn decode_b64(input: &str) -> Result<Vec<u8>, String> {
+    URL_SAFE_NO_PAD
+        .decode(input.trim_end_matches('='))
+        .map_err(|_| String::from("Expected base64url encoded data"))
+}
+
+fn cbor_head(major: u8, len: usize, out: &mut Vec<u8>) {
+    let major = major << 5;
+    match len {
+        0..=23 => out.push(major | len as u8),
+        24..=0xff => out.extend([major | 24, len as u8]),
+        0x100..=0xffff => {
+            out.push(major | 25);
+            out.extend((len as u16).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 26);
+            out.extend((len as u32).to_be_bytes());
+        }
+    }
+}
+
+fn cbor_bytes(bytes: &[u8], out: &mut Vec<u8>) {
+    cbor_head(2, bytes.len(), out);
+    out.extend(bytes);
+}
+
+fn cbor_text(text: &str, out: &mut Vec<u8>) {
+    cbor_head(3, text.len(), out);
+    out.extend(text.as_bytes());
+}
+
+/// COSE_Key for an ES256 public key given as an uncompressed SEC1 point, keys in CTAP2
+/// canonical order.
+pub fn cose_es256_public_key(public_key: &[u8]) -> Vec<u8> {
+    let (x, y) = public_key[1..].split_at(32);
+    let mut out = vec![0xa5];
+    out.extend([0x01, 0x02]); // kty: EC2
+    out.extend([0x03, 0x26]); // alg: ES256
+    out.extend([0x20, 0x01]); // crv: P-256
+    out.push(0x21);
+    cbor_bytes(x, &mut out);
+    out.push(0x22);
+    cbor_bytes(y, &mut out);
+    out
+}
+
+pub fn attested_credential_data(
+    aaguid: &[u8; 16],
+    credential_id: &[u8],
+    cose_key: &[u8],
+) -> Vec<u8> {
+    let mut out = aaguid.to_vec();
+    out.extend((credential_id.len() as u16).to_be_bytes());
+    out.extend(credential_id);
+    out.extend(cose_key);
+    out
+}
+
+pub fn authenticator_data(
+    rp_id: &str,
+    flags: u8,
+    sign_count: u32,
+    attested: Option<&[u8]>,
+) -> Vec<u8> {
+    let mut out = digest(&SHA256, rp_id.as_bytes()).as_ref().to_vec();
+    match attested {
+        Some(attested) => {
+            out.push(flags | FLAG_ATTESTED_CREDENTIAL);
+            out.extend(sign_count.to_be_bytes());
+            out.extend(attested);
+        }
+        None => {
+            out.push(flags);
+            out.extend(sign_count.to_be_bytes());
+        }
+    }
+    out
+}
+
+fn attestation_object(auth_data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0xa3];
+    cbor_text("fmt", &mut out);
+    cbor_text("none", &mut out);
+    cbor_text("attStmt", &mut out);
+    out.push(0xa0);
+    cbor_text("authData", &mut out);
+    cbor_bytes(auth_data, &mut out);
+    out
+}
+
+/// BE is set because a vault file, and every passkey in it, can be copied to another device.
+/// BS is never set: it says the credential is backed up right now, and Ancrypt doesn't sync
+/// vaults or know whether the user has copied one off this device. Its backups live next to
+/// the vault, so losing the device loses them too. Relying parties use BS to decide whether
+/// it is safe to drop a password, so claiming it without knowing would be wrong.
+fn flags(user_verified: bool) -> u8 {
+    let mut flags = FLAG_USER_PRESENT | FLAG_BACKUP_ELIGIBLE;
+    if user_verified {
+        flags |= FLAG_USER_VERIFIED;
+    }
+    flags
+}
+
+impl Passkey {
+    fn key_pair(&self) -> Result<EcdsaKeyPair, String> {
+        let rng = SystemRandom::new();
+        EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &self.private_key, &rng)
+            .map_err(|_| String::from("Stored passkey is corrupted"))
+    }
+
+    /// Wraps an existing raw P-256 key, `public_key` is the uncompressed SEC1 point.
+    pub fn from_private_key(
+        rp_id: &str,
+        user_handle: Vec<u8>,
+        user_name: &str,
+        credential_id: Vec<u8>,
+        private_key: &[u8; 32],
+        public_key: &[u8; 65],
+    ) -> Result<Passkey, String> {
+        let mut pkcs8 = PKCS8_P256_PREFIX.to_vec();
+        pkcs8.extend(private_key);
+        pkcs8.extend(PKCS8_P256_INFIX);
+        pkcs8.extend(public_key);
+
+        let passkey = Passkey {
+            rp_id: rp_id.to_string(),
+            user_handle,
+            user_name: user_name.to_string(),
+            credential_id,
+            private_key: pkcs8,
+            sign_count: 0,
+        };
+        passkey
+            .key_pair()
+            .map_err(|_| String::from("That is not a valid P-256 key pair"))?;
+        Ok(passkey)
+    }
+
+    pub fn public_key(&self) -> Result<Vec<u8>, String> {
+        Ok(self.key_pair()?.public_key().as_ref().to_vec())
+    }
+
+    /// Creates a new credential and the attestation a relying party expects back from
+    /// registration. `client_data_hash` is SHA-256 of the clientDataJSON the browser built.
+    pub fn register(
+        rp_id: &str,
+        user_handle: Vec<u8>,
+        user_name: &str,
+        client_data_hash: &[u8],
+        user_verified: bool,
+    ) -> Result<(Passkey, Registration), String> {
+        if rp_id.is_empty() {
+            return Err(String::from("You need a relying party id"));
+        }
+        if user_handle.is_empty() || user_handle.len() > 64 {
+            return Err(String::from("User handles must be 1 to 64 bytes"));
+        }
+        if client_data_hash.len() != 32 {
+            return Err(String::from("Client data hash must be 32 bytes"));
+        }
+
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng)
+            .map_err(|_| String::from("Failed to generate passkey"))?;
+        let credential_id = generate::<[u8; 32]>(&rng).unwrap().expose().to_vec();
+
+        let passkey = Passkey {
+            rp_id: rp_id.to_string(),
+            user_handle,
+            user_name: user_name.to_string(),
+            credential_id,
+            private_key: pkcs8.as_ref().to_vec(),
+            sign_count: 0,
+        };
+        let registration = passkey.registration(user_verified)?;
+
+        Ok((passkey, registration))
+    }
+
+    /// The "none" attestation for a credential that has just been made.
+    fn registration(&self, user_verified: bool) -> Result<Registration, String> {
+        let cose_key = cose_es256_public_key(&self.public_key()?);
+        let attested = attested_credential_data(&AAGUID, &self.credential_id, &cose_key);
+        let auth_data = authenticator_data(
+            &self.rp_id,
+            flags(user_verified),
+            self.sign_count,
+            Some(&attested),
+        );
+
+        Ok(Registration {
+            credential_id: encode_b64(&self.credential_id),
+            authenticator_data: encode_b64(&auth_data),
+            attestation_object: encode_b64(&attestation_object(&auth_data)),
+            public_key: encode_b64(&cose_key),
+        })
+    }
+
+    /// Signs an authentication ceremony, bumping the signature counter so relying parties can
+    /// spot a cloned credential.
+    pub fn assert(
+        &mut self,
+        rp_id: &str,
+        client_data_hash: &[u8],
+        user_verified: bool,
+    ) -> Result<Assertion, String> {
+        if rp_id != self.rp_id {
+            return Err(String::from("That passkey belongs to a different site"));
+        }
+        if client_data_hash.len() != 32 {
+            return Err(String::from("Client data hash must be 32 bytes"));
+        }
+
+        let sign_count = self
+            .sign_count
+            .checked_add(1)
+            .ok_or("Passkey signature counter is exhausted")?;
+        let auth_data = authenticator_data(rp_id, flags(user_verified), sign_count, None);
+
+        let mut message = auth_data.clone();
+        message.extend(client_data_hash);
+        let rng = SystemRandom::new();
+        let signature = self
+            .key_pair()?
+            .sign(&rng, &message)
+            .map_err(|_| String::from("Failed to sign with passkey"))?;
+
+        self.sign_count = sign_count;
+
+        Ok(Assertion {
+            credential_id: encode_b64(&self.credential_id),
+            authenticator_data: encode_b64(&auth_data),
+            signature: encode_b64(signature.as_ref()),
+            user_handle: encode_b64(&self.user_handle),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::signature::{UnparsedPublicKey, ECDSA_P256_SHA256_ASN1, ECDSA_P256_SHA256_FIXED};
+
+    fn hex(input: &str) -> Vec<u8> {
+        (0..input.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&input[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    // P-256 key "11" from RFC 8152 appendix C.7.
+    const RFC8152_D: &str = "57c92077664146e876760c9520d054aa93c3afb04e306705db6090308507b4d3";
+    const RFC8152_X: &str = "bac5b11cad8f99f9c72b05cf4b9e26d244dc189f745228255a219a86d6a09eff";
+    const RFC8152_Y: &str = "20138bf82dc1b6d562be0fa54ab7804a3a64b6d72ccfed6b6fb6ed28bbfc117e";
+
+    fn rfc8152_passkey() -> Passkey {
+        let d: [u8; 32] = hex(RFC8152_D).try_into().unwrap();
+        let mut q = vec![0x04];
+        q.extend(hex(RFC8152_X));
+        q.extend(hex(RFC8152_Y));
+        let q: [u8; 65] = q.try_into().unwrap();
+        Passkey::from_private_key("example.com", vec![1], "user", vec![0x11], &d, &q).unwrap()
+    }
+
+    // Recorded from 1Password's passkey-types 0.4.0 (`AuthenticatorData::to_vec` with a coset
+    // 0.3.8 COSE key), with the attestation object encoded by ciborium 0.2.2 and the signature
+    // made by p256 0.13.2 using RFC 6979 nonces. The inputs were key "11", rp id "example.com",
+    // credential id 00..1f, Ancrypt's AAGUID, and an assertion made with a stored counter of 41
+    // and the user not verified.
+    const RECORDED_AUTH_DATA: &str = concat!(
+        "a379a6f6eeafb9a55e378c118034e2751e682fab9f2d30ab13d2125586ce1947",
+        "4d00000000",
+        "6a1d3c0e528b4f679d2ea7410c853bf2",
+        "0020000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+        "a5010203262001",
+        "215820bac5b11cad8f99f9c72b05cf4b9e26d244dc189f745228255a219a86d6a09eff",
+        "22582020138bf82dc1b6d562be0fa54ab7804a3a64b6d72ccfed6b6fb6ed28bbfc117e",
+    );
+    const RECORDED_ATTESTATION_PREFIX: &str =
+        "a363666d74646e6f6e656761747453746d74a068617574684461746158a4";
+    const RECORDED_GET_CLIENT_DATA: &[u8] = br#"{"type":"webauthn.get","challenge":"AAECAwQFBgcICQoLDA0ODw","origin":"https://example.com"}"#;
+    const RECORDED_CLIENT_DATA_HASH: &str =
+        "99a4f3ced20810438264c252769769cb0ae26e94b286552ba70e6f2e0bcb43f4";
+    const RECORDED_ASSERTION_AUTH_DATA: &str =
+        "a379a6f6eeafb9a55e378c118034e2751e682fab9f2d30ab13d2125586ce1947090000002a";
+    const RECORDED_SIGNATURE: &str = concat!(
+        "3045022100ef8904de7122c87fa1de562b8a1c53a835e75ca32094646c95a02b806e44f86f",
+        "022036e0ae2ee19abe6e67828309ee82b5feb97804936f3a921f180ae4ae41c1fc1a",
+    );
+
+    fn recorded_passkey() -> Passkey {
+        let mut passkey = rfc8152_passkey();
+        passkey.credential_id = (0..32).collect();
+        passkey
+    }
+
+    #[test]
+    fn registration_matches_the_recorded_attestation() {
+        let registration = recorded_passkey().registration(true).unwrap();
+
+        let auth_data = hex(RECORDED_AUTH_DATA);
+        assert_eq!(
+            decode_b64(&registration.authenticator_data).unwrap(),
+            auth_data
+        );
+        let mut attestation = hex(RECORDED_ATTESTATION_PREFIX);
+        attestation.extend(&auth_data);
+        assert_eq!(
+            decode_b64(&registration.attestation_object).unwrap(),
+            attestation
+        );
+        assert_eq!(
+            decode_b64(&registration.public_key).unwrap(),
+            auth_data[87..]
+        );
+    }
+
+    #[test]
+    fn assertion_matches_the_recorded_signature_input() {
+        let client_data_hash = digest(&SHA256, RECORDED_GET_CLIENT_DATA);
+        assert_eq!(client_data_hash.as_ref(), hex(RECORDED_CLIENT_DATA_HASH));
+
+        let mut passkey = recorded_passkey();
+        passkey.sign_count = 41;
+        let assertion = passkey
+            .assert("example.com", client_data_hash.as_ref(), false)
+            .unwrap();
+        assert_eq!(passkey.sign_count, 42);
+
+        let auth_data = decode_b64(&assertion.authenticator_data).unwrap();
+        assert_eq!(auth_data, hex(RECORDED_ASSERTION_AUTH_DATA));
+
+        // ECDSA signatures are randomised, so the recorded one is checked against the message
+        // this authenticator signs and this authenticator's signature against the recorded one.
+        let mut message = auth_data;
+        message.extend(client_data_hash.as_ref());
+        let public_key = passkey.public_key().unwrap();
+        let verifier = UnparsedPublicKey::new(&ECDSA_P256_SHA256_ASN1, &public_key);
+        verifier.verify(&message, &hex(RECORDED_SIGNATURE)).unwrap();
+
+        let mut recorded_message = hex(RECORDED_ASSERTION_AUTH_DATA);
+        recorded_message.extend(hex(RECORDED_CLIENT_DATA_HASH));
+        verifier
+            .verify(
+                &recorded_message,
+                &decode_b64(&assertion.signature).unwrap(),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn cose_key_matches_rfc8152_encoding() {
+        let passkey = rfc8152_passkey();
+        let cose_key = cose_es256_public_key(&passkey.public_key().unwrap());
+
+        // RFC 8152 encodes key "11" with a kid, ours carries alg instead.
+        let expected = hex(concat!(
+            "a5",
+            "0102",
+            "0326",
+            "2001",
+            "215820bac5b11cad8f99f9c72b05cf4b9e26d244dc189f745228255a219a86d6a09eff",
+            "22582020138bf82dc1b6d562be0fa54ab7804a3a64b6d72ccfed6b6fb6ed28bbfc117e",
+        ));
+        assert_eq!(cose_key, expected);
+    }
+
+    #[test]
+    fn recorded_es256_signature_verifies_against_stored_key() {
+        // COSE_Sign1 example from RFC 8152 appendix C.2.1, signed with key "11".
+        let to_be_signed = hex(concat!(
+            "846a5369676e61747572653143a101264054",
+            "546869732069732074686520636f6e74656e742e",
+        ));
+        let signature = hex(concat!(
+            "8eb33e4ca31d1c465ab05aac34cc6b23d58fef5c083106c4d25a91aef0b0117e",
+            "2af9a291aa32e14ab834dc56ed2a223444547e01f11d3b0916e5a4c345cacb36",
+        ));
+
+        let public_key = rfc8152_passkey().public_key().unwrap();
+        UnparsedPublicKey::new(&ECDSA_P256_SHA256_FIXED, &public_key)
+            .verify(&to_be_signed, &signature)
+            .unwrap();
+    }
+
+    #[test]
+    fn assertion_signs_authenticator_data_and_client_data_hash() {
+        let client_data_hash = digest(&SHA256, b"{\"type\":\"webauthn.get\"}");
+        let mut passkey = rfc8152_passkey();
+
+        let first = passkey
+            .assert("example.com", client_data_hash.as_ref(), true)
+            .unwrap();
+        let second = passkey
+            .assert("example.com", client_data_hash.as_ref(), false)
+            .unwrap();
+
+        let auth_data = decode_b64(&first.authenticator_data).unwrap();
+        assert_eq!(&auth_data[..32], digest(&SHA256, b"example.com").as_ref());
+        assert_eq!(
+            auth_data[32],
+            FLAG_USER_PRESENT | FLAG_USER_VERIFIED | FLAG_BACKUP_ELIGIBLE
+        );
+        assert_eq!(auth_data[33..], 1u32.to_be_bytes());
+
+        let auth_data = decode_b64(&second.authenticator_data).unwrap();
+        assert_eq!(auth_data[32], FLAG_USER_PRESENT | FLAG_BACKUP_ELIGIBLE);
+        assert_eq!(auth_data[33..], 2u32.to_be_bytes());
+
+        let mut message = auth_data;
+        message.extend(client_data_hash.as_ref());
+        UnparsedPublicKey::new(&ECDSA_P256_SHA256_ASN1, passkey.public_key().unwrap())
+            .verify(&message, &decode_b64(&second.signature).unwrap())
+            .unwrap();
+
+        assert!(passkey
+            .assert("evil.example", client_data_hash.as_ref(), true)
+            .is_err());
+    }
+
+    #[test]
+    fn registration_attests_the_new_credential() {
+        let client_data_hash = digest(&SHA256, b"{\"type\":\"webauthn.create\"}");
+        let (passkey, registration) = Passkey::register(
+            "example.com",
+            vec![7; 16],
+            "user",
+            client_data_hash.as_ref(),
+            true,
+        )
+        .unwrap();
+
+        let auth_data = decode_b64(&registration.authenticator_data).unwrap();
+        let cose_key = cose_es256_public_key(&passkey.public_key().unwrap());
+        let expected = authenticator_data(
+            "example.com",
+            FLAG_USER_PRESENT | FLAG_USER_VERIFIED | FLAG_BACKUP_ELIGIBLE,
+            0,
+            Some(&attested_credential_data(
+                &AAGUID,
+                &passkey.credential_id,
+                &cose_key,
+            )),
+        );
+        assert_eq!(auth_data, expected);
+
+        let attestation = decode_b64(&registration.attestation_object).unwrap();
+        assert!(attestation.starts_with(&hex(
+            "a363666d74646e6f6e656761747453746d74a068617574684461746158"
+        )));
+        assert!(attestation.ends_with(&auth_data));
+    }
+}
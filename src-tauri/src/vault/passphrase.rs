@@ -0,0 +1,342 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use ring::rand::SystemRandom;
+use serde::{Deserialize, Serialize};
+
+use crate::vault::generator::random_index;
+
+/// Built in list, 2048 short and distinct English words. Lists in the EFF diceware format
+/// can be loaded as custom lists.
+const BUILT_IN_WORDS: &str = include_str!("english_words.txt");
+
+const MAX_WORDS: usize = 40;
+const MAX_DIGITS: usize = 10;
+const MIN_LIST_SIZE: usize = 16;
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Capitalisation {
+    #[default]
+    Lowercase,
+    Uppercase,
+    /// Capitalise the first letter of every word.
+    Title,
+    /// Capitalise each word or not at random, worth one extra bit per word.
+    Random,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct PassphraseOptions {
+    pub word_count: usize,
+    pub separator: String,
+    pub capitalisation: Capitalisation,
+    /// Number of random digits, each appended to a randomly chosen word.
+    pub digits: usize,
+    /// Path to a custom list, one word per line with or without diceware roll numbers.
+    pub word_list: Option<String>,
+}
+
+impl Default for PassphraseOptions {
+    fn default() -> Self {
+        PassphraseOptions {
+            word_count: 6,
+            separator: String::from("-"),
+            capitalisation: Capitalisation::default(),
+            digits: 0,
+            word_list: None,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Passphrase {
+    /// Left out when the passphrase was written straight into an entry.
+    pub passphrase: Option<String>,
+    pub entropy_bits: f64,
+    pub word_list_size: usize,
+}
+
+/// Reads a word list, dropping diceware roll numbers such as `11111\tabacus`, comments and
+/// duplicates.
+pub fn parse_word_list(contents: &str) -> Result<Vec<String>, String> {
+    let mut seen = HashSet::new();
+    let mut words = vec![];
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some(word) = line.split_whitespace().last() else {
+            continue;
+        };
+        if seen.insert(word.to_lowercase()) {
+            words.push(word.to_lowercase());
+        }
+    }
+
+    if words.len() < MIN_LIST_SIZE {
+        return Err(format!(
+            "Word lists need at least {} different words",
+            MIN_LIST_SIZE
+        ));
+    }
+    Ok(words)
+}
+
+pub fn load_word_list(path: &Path) -> Result<Vec<String>, String> {
+    let contents =
+        std::fs::read_to_string(path).map_err(|_| String::from("Could not read that word list"))?;
+    parse_word_list(&contents)
+}
+
+pub fn built_in_words() -> &'static Vec<String> {
+    static WORDS: OnceLock<Vec<String>> = OnceLock::new();
+    WORDS.get_or_init(|| parse_word_list(BUILT_IN_WORDS).expect("Built in word list is valid"))
+}
+
+fn capitalise(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+impl PassphraseOptions {
+    /// Generates the passphrase and the entropy an attacker who knows these options and the
+    /// word list faces.
+    pub fn generate(&self) -> Result<(String, f64, usize), String> {
+        if !(1..=MAX_WORDS).contains(&self.word_count) {
+            return Err(format!("Word count must be between 1 and {}", MAX_WORDS));
+        }
+        if self.digits > MAX_DIGITS {
+            return Err(format!("At most {} digits can be added", MAX_DIGITS));
+        }
+
+        let custom;
+        let list = match &self.word_list {
+            Some(path) => {
+                custom = load_word_list(Path::new(path))?;
+                &custom
+            }
+            None => built_in_words(),
+        };
+        if !self.separator.is_empty() && list.iter().any(|w| w.contains(&self.separator)) {
+            return Err(String::from(
+                "The separator appears inside words of that list",
+            ));
+        }
+
+        let rng = SystemRandom::new();
+        let mut words: Vec<String> = (0..self.word_count)
+            .map(|_| list[random_index(&rng, list.len())].clone())
+            .collect();
+
+        for word in &mut words {
+            *word = match self.capitalisation {
+                Capitalisation::Lowercase => word.clone(),
+                Capitalisation::Uppercase => word.to_uppercase(),
+                Capitalisation::Title => capitalise(word),
+                Capitalisation::Random if random_index(&rng, 2) == 1 => capitalise(word),
+                Capitalisation::Random => word.clone(),
+            };
+        }
+        for _ in 0..self.digits {
+            let word = random_index(&rng, words.len());
+            let digit = random_index(&rng, 10);
+            words[word].push_str(&digit.to_string());
+        }
+
+        let mut entropy = self.word_count as f64 * (list.len() as f64).log2();
+        if self.capitalisation == Capitalisation::Random {
+            entropy += self.word_count as f64;
+        }
+        // Each digit picks a word and a value, less the orderings that give the same result.
+        entropy += self.digits as f64 * (10.0 * self.word_count as f64).log2();
+        entropy -= (1..=self.digits).map(|i| (i as f64).log2()).sum::<f64>();
+
+        Ok((words.join(&self.separator), entropy, list.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn options() -> PassphraseOptions {
+        PassphraseOptions::default()
+    }
+
+    fn dice_list(words: usize) -> String {
+        (0..words)
+            .map(|i| format!("{:05}\tword{}", 11111 + i, i))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn uses_the_word_count_and_separator() {
+        let (passphrase, _, size) = PassphraseOptions {
+            word_count: 8,
+            separator: String::from("."),
+            ..options()
+        }
+        .generate()
+        .unwrap();
+        let words: Vec<&str> = passphrase.split('.').collect();
+        assert_eq!(words.len(), 8);
+        assert_eq!(size, built_in_words().len());
+        assert!(words
+            .iter()
+            .all(|w| built_in_words().contains(&w.to_string())));
+
+        let (passphrase, _, _) = PassphraseOptions {
+            word_count: 3,
+            separator: String::new(),
+            ..options()
+        }
+        .generate()
+        .unwrap();
+        assert!(passphrase.chars().all(|c| c.is_ascii_lowercase()));
+    }
+
+    #[test]
+    fn capitalises_each_word() {
+        let words = |capitalisation| {
+            let (passphrase, _, _) = PassphraseOptions {
+                word_count: 10,
+                capitalisation,
+                ..options()
+            }
+            .generate()
+            .unwrap();
+            passphrase.split('-').map(String::from).collect::<Vec<_>>()
+        };
+
+        assert!(words(Capitalisation::Lowercase)
+            .iter()
+            .all(|w| w.chars().all(|c| c.is_ascii_lowercase())));
+        assert!(words(Capitalisation::Uppercase)
+            .iter()
+            .all(|w| w.chars().all(|c| c.is_ascii_uppercase())));
+        assert!(words(Capitalisation::Title).iter().all(|w| {
+            let mut chars = w.chars();
+            chars.next().unwrap().is_ascii_uppercase() && chars.all(|c| c.is_ascii_lowercase())
+        }));
+        assert!(words(Capitalisation::Random)
+            .iter()
+            .all(|w| w.chars().skip(1).all(|c| c.is_ascii_lowercase())));
+    }
+
+    #[test]
+    fn appends_digits_to_words() {
+        let (passphrase, _, _) = PassphraseOptions {
+            word_count: 4,
+            digits: 5,
+            ..options()
+        }
+        .generate()
+        .unwrap();
+        let words: Vec<&str> = passphrase.split('-').collect();
+        assert_eq!(words.len(), 4);
+        assert_eq!(passphrase.chars().filter(|c| c.is_ascii_digit()).count(), 5);
+        for word in words {
+            let letters = word.trim_end_matches(|c: char| c.is_ascii_digit());
+            assert!(built_in_words().contains(&letters.to_string()), "{word}");
+        }
+    }
+
+    #[test]
+    fn reports_the_entropy_of_the_options() {
+        let bits = |options: PassphraseOptions| options.generate().unwrap().1;
+        let per_word = (built_in_words().len() as f64).log2();
+
+        assert!((bits(options()) - 6.0 * per_word).abs() < 1e-9);
+        assert!(
+            (bits(PassphraseOptions {
+                capitalisation: Capitalisation::Random,
+                ..options()
+            }) - (6.0 * per_word + 6.0))
+                .abs()
+                < 1e-9
+        );
+        // Two digits among six words: 60 choices each, halved for the two orderings.
+        assert!(
+            (bits(PassphraseOptions {
+                digits: 2,
+                ..options()
+            }) - (6.0 * per_word + 2.0 * 60f64.log2() - 1.0))
+                .abs()
+                < 1e-9
+        );
+    }
+
+    #[test]
+    fn rejects_out_of_range_options() {
+        let error = |options: PassphraseOptions| options.generate().unwrap_err();
+        assert_eq!(
+            error(PassphraseOptions {
+                word_count: 0,
+                ..options()
+            }),
+            "Word count must be between 1 and 40"
+        );
+        assert_eq!(
+            error(PassphraseOptions {
+                digits: 11,
+                ..options()
+            }),
+            "At most 10 digits can be added"
+        );
+        assert_eq!(
+            error(PassphraseOptions {
+                separator: String::from("a"),
+                ..options()
+            }),
+            "The separator appears inside words of that list"
+        );
+    }
+
+    #[test]
+    fn parses_custom_word_lists() {
+        let words = parse_word_list(&dice_list(20)).unwrap();
+        assert_eq!(words.len(), 20);
+        assert_eq!(words[0], "word0");
+
+        let plain = "# comment\n\nApple\napple\n".to_string()
+            + &(0..15).map(|i| format!("w{i}\n")).collect::<String>();
+        let words = parse_word_list(&plain).unwrap();
+        assert_eq!(words.len(), 16);
+        assert_eq!(words[0], "apple");
+
+        assert_eq!(
+            parse_word_list(&dice_list(15)).unwrap_err(),
+            "Word lists need at least 16 different words"
+        );
+    }
+
+    #[test]
+    fn generates_from_a_custom_list() {
+        let dir = std::env::temp_dir().join(format!("ancrypt-passphrase-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("dice.txt");
+        fs::write(&path, dice_list(32)).unwrap();
+
+        let (passphrase, bits, size) = PassphraseOptions {
+            word_count: 4,
+            word_list: Some(path.to_string_lossy().into_owned()),
+            ..options()
+        }
+        .generate()
+        .unwrap();
+        assert_eq!(size, 32);
+        assert!((bits - 20.0).abs() < 1e-9);
+        assert!(passphrase.split('-').all(|w| w.starts_with("word")));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
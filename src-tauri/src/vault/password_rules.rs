@@ -0,0 +1,345 @@
+use crate::vault::generator::{longest_run, CustomCharacterSet, PasswordPolicy};
+
+const UPPER: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const LOWER: &str = "abcdefghijklmnopqrstuvwxyz";
+const DIGIT: &str = "0123456789";
+/// Apple's special class, less the space which generated passwords never contain.
+const SPECIAL: &str = "-~!@#$%^&*_+=`|(){}[:;\"'<>,.?]";
+
+/// A site's password requirements, written in the syntax of Apple's `passwordrules` attribute,
+/// for example `minlength: 8; maxlength: 20; required: lower, upper; required: digit;`.
+///
+/// Every `required` property must be met by at least one character from any of its classes,
+/// `allowed` widens the characters that may appear and `max-consecutive` caps runs of the same
+/// character. Unknown properties are ignored so rules written for newer browsers still load.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PasswordRules {
+    pub min_length: Option<usize>,
+    pub max_length: Option<usize>,
+    pub max_consecutive: Option<usize>,
+    pub required: Vec<Vec<char>>,
+    pub allowed: Vec<char>,
+}
+
+fn ascii_printable() -> String {
+    (b'!'..=b'~').map(char::from).collect()
+}
+
+fn class(name: &str) -> Result<String, String> {
+    match name.to_ascii_lowercase().as_str() {
+        "upper" => Ok(UPPER.to_string()),
+        "lower" => Ok(LOWER.to_string()),
+        "digit" => Ok(DIGIT.to_string()),
+        "special" => Ok(SPECIAL.to_string()),
+        // Generated passwords stay within ASCII, so unicode only allows what ASCII does.
+        "ascii-printable" | "unicode" => Ok(ascii_printable()),
+        _ => Err(format!("Unknown character class {}", name)),
+    }
+}
+
+/// Parses a comma separated list of classes and `[...]` sets. A `]` right after the opening
+/// bracket is taken literally, as Apple's parser does.
+fn parse_classes(value: &str) -> Result<Vec<char>, String> {
+    let mut chars = vec![];
+    let mut rest = value.trim();
+
+    while !rest.is_empty() {
+        if let Some(set) = rest.strip_prefix('[') {
+            let end = set
+                .char_indices()
+                .skip(1)
+                .find(|(_, c)| *c == ']')
+                .map(|(i, _)| i)
+                .ok_or_else(|| String::from("A character set is missing its closing ]"))?;
+            chars.extend(set[..end].chars().filter(|c| c.is_ascii_graphic()));
+            rest = set[end + 1..].trim_start();
+        } else {
+            let end = rest.find(',').unwrap_or(rest.len());
+            chars.extend(class(rest[..end].trim())?.chars());
+            rest = &rest[end..];
+        }
+
+        rest = match rest.strip_prefix(',') {
+            Some(next) => next.trim_start(),
+            None if rest.is_empty() => rest,
+            None => {
+                return Err(String::from(
+                    "Character classes must be separated by commas",
+                ))
+            }
+        };
+    }
+
+    chars.sort_unstable();
+    chars.dedup();
+    Ok(chars)
+}
+
+/// Splits on the `;` between properties, skipping any inside a `[...]` set.
+fn split_properties(text: &str) -> impl Iterator<Item = &str> {
+    let mut in_set = false;
+    let mut set_start = false;
+    text.split(move |c| {
+        let opened = set_start;
+        set_start = false;
+        match c {
+            '[' if !in_set => {
+                in_set = true;
+                set_start = true;
+            }
+            // A `]` straight after `[` is a member of the set.
+            ']' if in_set && !opened => in_set = false,
+            ';' if !in_set => return true,
+            _ => {}
+        }
+        false
+    })
+}
+
+fn parse_number(name: &str, value: &str) -> Result<usize, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("{} must be a whole number", name))
+}
+
+impl PasswordRules {
+    pub fn parse(text: &str) -> Result<PasswordRules, String> {
+        let mut rules = PasswordRules::default();
+
+        for property in split_properties(text)
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+        {
+            let (name, value) = property
+                .split_once(':')
+                .ok_or_else(|| format!("Expected a name and value in \"{}\"", property))?;
+            let name = name.trim().to_ascii_lowercase();
+
+            match name.as_str() {
+                "required" => {
+                    let chars = parse_classes(value)?;
+                    if chars.is_empty() {
+                        return Err(String::from("A required set has no characters"));
+                    }
+                    rules.required.push(chars);
+                }
+                "allowed" => rules.allowed.extend(parse_classes(value)?),
+                "minlength" => rules.min_length = Some(parse_number("minlength", value)?),
+                "maxlength" => rules.max_length = Some(parse_number("maxlength", value)?),
+                "max-consecutive" => {
+                    let max = parse_number("max-consecutive", value)?;
+                    if max == 0 {
+                        return Err(String::from("max-consecutive must be at least 1"));
+                    }
+                    // Repeated properties only ever tighten the rule.
+                    rules.max_consecutive = Some(rules.max_consecutive.map_or(max, |m| m.min(max)));
+                }
+                _ => {}
+            }
+        }
+
+        rules.allowed.sort_unstable();
+        rules.allowed.dedup();
+        if let (Some(min), Some(max)) = (rules.min_length, rules.max_length) {
+            if min > max {
+                return Err(String::from("minlength is larger than maxlength"));
+            }
+        }
+        Ok(rules)
+    }
+
+    /// Narrows a policy to what the site accepts. The policy's length is kept where the rules
+    /// allow it and its ambiguity setting still applies, its character classes are replaced.
+    pub fn apply(&self, policy: &PasswordPolicy) -> PasswordPolicy {
+        let mut custom_sets: Vec<CustomCharacterSet> = self
+            .required
+            .iter()
+            .map(|chars| CustomCharacterSet {
+                characters: chars.iter().collect(),
+                min: 1,
+            })
+            .collect();
+        if !self.allowed.is_empty() {
+            custom_sets.push(CustomCharacterSet {
+                characters: self.allowed.iter().collect(),
+                min: 0,
+            });
+        }
+        if custom_sets.is_empty() {
+            custom_sets.push(CustomCharacterSet {
+                characters: ascii_printable(),
+                min: 0,
+            });
+        }
+
+        let mut length = policy.length;
+        if let Some(max) = self.max_length {
+            length = length.min(max);
+        }
+        if let Some(min) = self.min_length {
+            length = length.max(min);
+        }
+
+        PasswordPolicy {
+            length,
+            lowercase: false,
+            uppercase: false,
+            digits: false,
+            symbols: false,
+            custom_sets,
+            max_consecutive: match (policy.max_consecutive, self.max_consecutive) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            },
+            ..policy.clone()
+        }
+    }
+
+    /// Whether a password, generated or typed, satisfies every rule.
+    pub fn check(&self, password: &str) -> bool {
+        let chars: Vec<char> = password.chars().collect();
+
+        let allowed =
+            |c: &char| self.allowed.contains(c) || self.required.iter().any(|set| set.contains(c));
+        let unrestricted = self.allowed.is_empty() && self.required.is_empty();
+
+        self.min_length.is_none_or(|min| chars.len() >= min)
+            && self.max_length.is_none_or(|max| chars.len() <= max)
+            && self
+                .max_consecutive
+                .is_none_or(|max| longest_run(&chars) <= max)
+            && self
+                .required
+                .iter()
+                .all(|set| chars.iter().any(|c| set.contains(c)))
+            && (unrestricted || chars.iter().all(allowed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generate(rule: &str) -> (PasswordRules, String) {
+        let rules = PasswordRules::parse(rule).unwrap();
+        let password = rules.apply(&PasswordPolicy::default()).generate().unwrap();
+        (rules, password)
+    }
+
+    #[test]
+    fn parses_classes_and_custom_sets() {
+        let rules = PasswordRules::parse(
+            "minlength: 8; maxlength: 20; required: lower, upper; required: [-().&@?'#,/\"+]; allowed: DIGIT",
+        )
+        .unwrap();
+
+        assert_eq!(rules.min_length, Some(8));
+        assert_eq!(rules.max_length, Some(20));
+        assert_eq!(rules.required.len(), 2);
+        assert_eq!(rules.required[0].len(), 52);
+        assert_eq!(
+            rules.required[1],
+            "\"#&'()+,-./?@".chars().collect::<Vec<_>>()
+        );
+        assert_eq!(rules.allowed, DIGIT.chars().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn closing_bracket_first_in_a_set_is_literal() {
+        let rules = PasswordRules::parse("required: []ab;]; minlength: 4").unwrap();
+        assert_eq!(rules.required[0], vec![';', ']', 'a', 'b']);
+        assert_eq!(rules.min_length, Some(4));
+    }
+
+    #[test]
+    fn rejects_malformed_rules() {
+        assert!(PasswordRules::parse("required: lower upper").is_err());
+        assert!(PasswordRules::parse("required: [abc").is_err());
+        assert!(PasswordRules::parse("required: emoji").is_err());
+        assert!(PasswordRules::parse("minlength: eight").is_err());
+        assert!(PasswordRules::parse("minlength: 12; maxlength: 8").is_err());
+        assert!(PasswordRules::parse("max-consecutive: 0").is_err());
+        assert!(PasswordRules::parse("required").is_err());
+    }
+
+    #[test]
+    fn ignores_unknown_properties() {
+        let rules = PasswordRules::parse("passwordrules-version: 2; minlength: 6;").unwrap();
+        assert_eq!(rules.min_length, Some(6));
+    }
+
+    #[test]
+    fn honours_apple_rule() {
+        let rule = "minlength: 8; maxlength: 63; required: lower; required: upper; required: digit; allowed: ascii-printable;";
+        for _ in 0..50 {
+            let (rules, password) = generate(rule);
+            assert_eq!(password.len(), 20);
+            assert!(rules.check(&password), "{}", password);
+        }
+    }
+
+    #[test]
+    fn honours_american_express_rule() {
+        let rule = "minlength: 8; maxlength: 20; max-consecutive: 4; required: lower, upper; required: digit; allowed: [%&_?#=];";
+        for _ in 0..50 {
+            let (rules, password) = generate(rule);
+            assert!(rules.check(&password), "{}", password);
+            assert!(password
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "%&_?#=".contains(c)));
+        }
+    }
+
+    #[test]
+    fn honours_chase_rule() {
+        let rule = "minlength: 8; maxlength: 32; max-consecutive: 2; required: lower, upper; required: digit; required: [!#$%+/=@~];";
+        for _ in 0..50 {
+            let (rules, password) = generate(rule);
+            assert!(longest_run(&password.chars().collect::<Vec<_>>()) <= 2);
+            assert!(password.chars().any(|c| "!#$%+/=@~".contains(c)));
+            assert!(rules.check(&password), "{}", password);
+        }
+    }
+
+    #[test]
+    fn caps_length_at_maxlength() {
+        let rule = "minlength: 8; maxlength: 12; required: lower; required: upper; required: digit; allowed: [-@#*()+={}/?~;,._];";
+        for _ in 0..50 {
+            let (rules, password) = generate(rule);
+            assert_eq!(password.len(), 12);
+            assert!(rules.check(&password), "{}", password);
+        }
+    }
+
+    #[test]
+    fn honours_digit_only_pin_rule() {
+        let (rules, password) =
+            generate("minlength: 6; maxlength: 6; required: digit; max-consecutive: 1;");
+        assert_eq!(password.len(), 6);
+        assert!(password.chars().all(|c| c.is_ascii_digit()));
+        assert!(rules.check(&password));
+    }
+
+    #[test]
+    fn empty_rule_allows_ascii_printable() {
+        let (rules, password) = generate("");
+        assert_eq!(password.len(), 20);
+        assert!(password.chars().all(|c| c.is_ascii_graphic()));
+        assert!(rules.check(&password));
+    }
+
+    #[test]
+    fn check_rejects_passwords_outside_the_rule() {
+        let rules = PasswordRules::parse(
+            "minlength: 8; max-consecutive: 2; required: lower; required: digit; allowed: upper;",
+        )
+        .unwrap();
+
+        assert!(rules.check("abcDEF12"));
+        assert!(!rules.check("abcDEF1"));
+        assert!(!rules.check("abcDEFGH"));
+        assert!(!rules.check("aaabcd12"));
+        assert!(!rules.check("abcdef1!"));
+    }
+}
@@ -0,0 +1,395 @@
+use serde::Serialize;
+
+use crate::vault::entry::{Entry, EntryType};
+
+const SCORE_MATCH: i64 = 16;
+const BONUS_BOUNDARY: i64 = 8;
+const BONUS_CONSECUTIVE: i64 = 4;
+const PENALTY_GAP_START: i64 = 3;
+const PENALTY_GAP_EXTENSION: i64 = 1;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchField {
+    Title,
+    Username,
+    Url,
+    Notes,
+    Tag,
+}
+
+/// Where a query matched. `positions` are character indices into `value`.
+#[derive(Serialize, Clone, Debug)]
+pub struct Highlight {
+    pub field: SearchField,
+    pub value: String,
+    pub positions: Vec<usize>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SearchHit {
+    pub name: String,
+    pub kind: EntryType,
+    pub score: i64,
+    pub highlights: Vec<Highlight>,
+}
+
+impl SearchField {
+    fn weight(self) -> i64 {
+        match self {
+            SearchField::Title => 3,
+            SearchField::Username | SearchField::Tag => 2,
+            SearchField::Url | SearchField::Notes => 1,
+        }
+    }
+}
+
+fn is_boundary(previous: Option<char>, current: char) -> bool {
+    match previous {
+        None => true,
+        Some(previous) => {
+            !previous.is_alphanumeric()
+                || (previous.is_lowercase() && current.is_uppercase())
+                || (!previous.is_ascii_digit() && current.is_ascii_digit())
+        }
+    }
+}
+
+/// Lowercases a query term the same way `fuzzy_match` lowercases text.
+pub fn lowercase(term: &str) -> Vec<char> {
+    term.chars().flat_map(char::to_lowercase).collect()
+}
+
+/// Scores `pattern` (lowercased with `lowercase`) as a subsequence of `text`. The match
+/// window is narrowed to the shortest one ending at the first complete match so scattered
+/// hits do not outrank tight ones.
+pub fn fuzzy_match(pattern: &[char], text: &str) -> Option<(i64, Vec<usize>)> {
+    let mut positions = Vec::with_capacity(pattern.len());
+    let score = match_score(pattern, text, Some(&mut positions))?;
+    Some((score, positions))
+}
+
+/// `fuzzy_match` that only records positions when asked to, ranking doesn't need them.
+fn match_score(pattern: &[char], text: &str, positions: Option<&mut Vec<usize>>) -> Option<i64> {
+    // ASCII text can be indexed by byte, which keeps the common case free of allocations.
+    if text.is_ascii() {
+        let bytes = text.as_bytes();
+        let at = |i: usize| {
+            let c = bytes[i] as char;
+            (c, c.to_ascii_lowercase())
+        };
+        return score(pattern, bytes.len(), at, positions);
+    }
+
+    // Some characters lowercase to several, 'İ' to "i̇", so each keeps the index of the
+    // character it came from.
+    let mut lowered = vec![];
+    let mut origins = vec![];
+    for (i, c) in text.chars().enumerate() {
+        for l in c.to_lowercase() {
+            lowered.push((c, l));
+            origins.push(i);
+        }
+    }
+    let mut matched = vec![];
+    let score = score(pattern, lowered.len(), |i| lowered[i], Some(&mut matched))?;
+    if let Some(positions) = positions {
+        positions.extend(matched.into_iter().map(|p| origins[p]));
+        positions.dedup();
+    }
+    Some(score)
+}
+
+/// The highest score a pattern of `len` characters can get, every character matching on a
+/// boundary right after the one before.
+fn best_possible(len: usize) -> i64 {
+    SCORE_MATCH
+        + BONUS_BOUNDARY * 2
+        + (SCORE_MATCH + BONUS_BOUNDARY + BONUS_CONSECUTIVE) * len.saturating_sub(1) as i64
+}
+
+/// `at` gives the character at an index along with its lowercase form.
+fn score(
+    pattern: &[char],
+    len: usize,
+    at: impl Fn(usize) -> (char, char),
+    mut positions: Option<&mut Vec<usize>>,
+) -> Option<i64> {
+    if pattern.is_empty() {
+        return None;
+    }
+
+    let mut next = 0;
+    let mut end = None;
+    for i in 0..len {
+        if at(i).1 == pattern[next] {
+            next += 1;
+            if next == pattern.len() {
+                end = Some(i);
+                break;
+            }
+        }
+    }
+    let end = end?;
+
+    let mut remaining = pattern.len();
+    let mut start = end;
+    for i in (0..=end).rev() {
+        if at(i).1 == pattern[remaining - 1] {
+            remaining -= 1;
+            if remaining == 0 {
+                start = i;
+                break;
+            }
+        }
+    }
+
+    let mut score = 0;
+    let mut p = 0;
+    let mut last: Option<usize> = None;
+    for i in start..=end {
+        if p == pattern.len() {
+            break;
+        }
+        let (current, lowered) = at(i);
+        if lowered != pattern[p] {
+            continue;
+        }
+
+        score += SCORE_MATCH;
+        let previous = if i == 0 { None } else { Some(at(i - 1).0) };
+        if is_boundary(previous, current) {
+            score += if p == 0 {
+                BONUS_BOUNDARY * 2
+            } else {
+                BONUS_BOUNDARY
+            };
+        }
+        match last {
+            Some(last) if last + 1 == i => score += BONUS_CONSECUTIVE,
+            Some(last) => {
+                score -= PENALTY_GAP_START + PENALTY_GAP_EXTENSION * (i - last - 2) as i64;
+            }
+            None => {}
+        }
+
+        if let Some(positions) = positions.as_deref_mut() {
+            positions.push(i);
+        }
+        last = Some(i);
+        p += 1;
+    }
+
+    Some(score)
+}
+
+fn fields<'a>(name: &'a str, entry: &'a Entry) -> impl Iterator<Item = (SearchField, &'a str)> {
+    let username = Some(entry.username.as_str()).filter(|u| !u.is_empty());
+    let notes = Some(entry.notes.as_str()).filter(|n| !n.is_empty());
+
+    std::iter::once((SearchField::Title, name))
+        .chain(username.map(|u| (SearchField::Username, u)))
+        .chain(entry.urls.iter().map(|u| (SearchField::Url, u.as_str())))
+        .chain(notes.map(|n| (SearchField::Notes, n)))
+        .chain(entry.tags.iter().map(|t| (SearchField::Tag, t.as_str())))
+}
+
+/// Every term has to match at least one field, each contributing its best weighted match.
+fn score_entry(
+    terms: &[Vec<char>],
+    name: &str,
+    entry: &Entry,
+    mut highlights: Option<&mut Vec<Highlight>>,
+) -> Option<i64> {
+    let mut total = 0;
+
+    for term in terms {
+        let Some(highlights) = highlights.as_deref_mut() else {
+            // Fields that couldn't beat the best match so far aren't worth scanning.
+            let ceiling = best_possible(term.len());
+            let mut best: Option<i64> = None;
+            for (field, value) in fields(name, entry) {
+                if best.is_some_and(|best| best >= ceiling * field.weight()) {
+                    continue;
+                }
+                if let Some(score) = match_score(term, value, None) {
+                    best = best.max(Some(score * field.weight()));
+                }
+            }
+            total += best?;
+            continue;
+        };
+
+        let (score, field, value, positions) = fields(name, entry)
+            .filter_map(|(field, value)| {
+                fuzzy_match(term, value)
+                    .map(|(score, positions)| (score * field.weight(), field, value, positions))
+            })
+            .max_by_key(|(score, ..)| *score)?;
+        total += score;
+        match highlights
+            .iter_mut()
+            .find(|h| h.field == field && h.value == value)
+        {
+            Some(highlight) => {
+                highlight.positions.extend(positions);
+                highlight.positions.sort_unstable();
+                highlight.positions.dedup();
+            }
+            None => highlights.push(Highlight {
+                field,
+                value: value.to_string(),
+                positions,
+            }),
+        }
+    }
+
+    Some(total)
+}
+
+/// Ranks entries against a whitespace separated query. Only titles and metadata are searched,
+/// never secrets, and highlights are only worked out for the hits that are returned.
+pub fn search<'a>(
+    entries: impl Iterator<Item = (&'a String, &'a Entry)>,
+    query: &str,
+    limit: usize,
+) -> Vec<SearchHit> {
+    let terms: Vec<Vec<char>> = query.split_whitespace().map(lowercase).collect();
+    if terms.is_empty() {
+        return vec![];
+    }
+
+    let mut ranked: Vec<(i64, &String, &Entry)> = entries
+        .filter_map(|(name, entry)| Some((score_entry(&terms, name, entry, None)?, name, entry)))
+        .collect();
+    let order = |a: &(i64, &String, &Entry), b: &(i64, &String, &Entry)| {
+        b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1))
+    };
+    // Only the returned hits need sorting, which matters for broad queries on big vaults.
+    if limit < ranked.len() {
+        ranked.select_nth_unstable_by(limit, order);
+        ranked.truncate(limit);
+    }
+    ranked.sort_unstable_by(order);
+
+    ranked
+        .into_iter()
+        .map(|(score, name, entry)| {
+            let mut highlights = vec![];
+            score_entry(&terms, name, entry, Some(&mut highlights));
+            SearchHit {
+                name: name.clone(),
+                kind: entry.entry_type(),
+                score,
+                highlights,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::time::{Duration, Instant};
+
+    fn entries(names: &[&str]) -> HashMap<String, Entry> {
+        names
+            .iter()
+            .map(|name| (name.to_string(), Entry::login(String::from("secret"))))
+            .collect()
+    }
+
+    fn names(hits: &[SearchHit]) -> Vec<&str> {
+        hits.iter().map(|hit| hit.name.as_str()).collect()
+    }
+
+    #[test]
+    fn tight_and_boundary_matches_rank_first() {
+        let entries = entries(&[
+            "GitHub",
+            "Gmail thing hub",
+            "Good Hosting",
+            "Bank",
+            "Bob and Kate",
+        ]);
+
+        let hits = search(entries.iter(), "gh", 10);
+        assert_eq!(names(&hits), ["GitHub", "Good Hosting", "Gmail thing hub"]);
+
+        let hits = search(entries.iter(), "bank", 10);
+        assert_eq!(names(&hits), ["Bank", "Bob and Kate"]);
+        assert!(hits[0].score > hits[1].score);
+
+        assert_eq!(search(entries.iter(), "gh", 1).len(), 1);
+        assert!(search(entries.iter(), "zzz", 10).is_empty());
+        assert!(search(entries.iter(), "  ", 10).is_empty());
+    }
+
+    #[test]
+    fn every_term_must_match_some_field() {
+        let mut entries = entries(&["GitHub", "GitLab"]);
+        let github = entries.get_mut("GitHub").unwrap();
+        github.username = String::from("alice@example.com");
+        github.tags = vec![String::from("work")];
+
+        assert_eq!(names(&search(entries.iter(), "git work", 10)), ["GitHub"]);
+        assert_eq!(names(&search(entries.iter(), "alice", 10)), ["GitHub"]);
+        assert!(search(entries.iter(), "secret", 10).is_empty());
+    }
+
+    #[test]
+    fn highlights_point_at_matched_characters() {
+        let mut entries = entries(&["My GitHub"]);
+        entries.get_mut("My GitHub").unwrap().tags = vec![String::from("work")];
+
+        let hits = search(entries.iter(), "gh wrk", 10);
+        let highlights = &hits[0].highlights;
+        assert_eq!(highlights.len(), 2);
+        assert_eq!(highlights[0].field, SearchField::Title);
+        assert_eq!(highlights[0].positions, [3, 6]);
+        assert_eq!(highlights[1].field, SearchField::Tag);
+        assert_eq!(highlights[1].positions, [0, 2, 3]);
+    }
+
+    #[test]
+    fn lowercases_text_and_query_alike() {
+        let entries = entries(&["İstanbul Kart", "ΣΟΦΙΑ"]);
+
+        let hits = search(entries.iter(), "İstanbul", 10);
+        assert_eq!(names(&hits), ["İstanbul Kart"]);
+        assert_eq!(hits[0].highlights[0].positions, (0..8).collect::<Vec<_>>());
+
+        assert_eq!(
+            names(&search(entries.iter(), "istanbul", 10)),
+            ["İstanbul Kart"]
+        );
+        assert_eq!(names(&search(entries.iter(), "σοφια", 10)), ["ΣΟΦΙΑ"]);
+    }
+
+    #[test]
+    #[cfg_attr(debug_assertions, ignore = "timing only holds in release builds")]
+    fn searches_ten_thousand_entries_within_ten_milliseconds() {
+        let mut entries = HashMap::new();
+        for i in 0..10_000 {
+            let mut entry = Entry::login(String::from("secret"));
+            entry.username = format!("user{}@mail{}.example.org", i, i % 97);
+            entry.urls = vec![format!("https://service{}.example.com/account/login", i)];
+            entry.notes = String::from("Notes about this account and how to recover it");
+            entry.tags = vec![String::from("personal"), format!("group{}", i % 13)];
+            entries.insert(format!("Service account number {}", i), entry);
+        }
+
+        for query in ["srv acc", "example", "zzz", "group12 login"] {
+            let fastest = (0..5)
+                .map(|_| {
+                    let start = Instant::now();
+                    search(entries.iter(), query, 50);
+                    start.elapsed()
+                })
+                .min()
+                .unwrap();
+            assert!(fastest < Duration::from_millis(10), "{query}: {fastest:?}");
+        }
+    }
+}
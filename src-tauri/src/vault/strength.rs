@@ -0,0 +1,1053 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+use serde::Serialize;
+
+use crate::vault::date::today;
+
+/// Ranked most common first, a word's guesses start at its rank. It only holds the 306 most
+/// common passwords, so anything rarer falls through to the other matchers.
+const COMMON_PASSWORDS: &str = include_str!("common_passwords.txt");
+/// The BIP39 English list, used unranked as a stand in for common words. It has no frequency
+/// order, so every word gets the list's length as its rank, which is too generous for rare
+/// words and too harsh for the most common ones.
+const ENGLISH_WORDS: &str = include_str!("english_words.txt");
+
+const BRUTEFORCE_CARDINALITY: f64 = 10.0;
+const MIN_GUESSES_BEFORE_GROWING_SEQUENCE: f64 = 10_000.0;
+const MIN_SUBMATCH_GUESSES_SINGLE_CHAR: f64 = 10.0;
+const MIN_SUBMATCH_GUESSES_MULTI_CHAR: f64 = 50.0;
+const MIN_YEAR_SPACE: i64 = 20;
+const MAX_ANALYSED_LENGTH: usize = 100;
+const MAX_WORD_LENGTH: usize = 32;
+
+const KEYBOARD_ROWS: [(&str, &str); 4] = [
+    ("`1234567890-=", "~!@#$%^&*()_+"),
+    ("qwertyuiop[]\\", "QWERTYUIOP{}|"),
+    ("asdfghjkl;'", "ASDFGHJKL:\""),
+    ("zxcvbnm,./", "ZXCVBNM<>?"),
+];
+
+const L33T_TABLE: [(char, &[char]); 15] = [
+    ('4', &['a']),
+    ('@', &['a']),
+    ('8', &['b']),
+    ('(', &['c']),
+    ('3', &['e']),
+    ('6', &['g']),
+    ('1', &['i', 'l']),
+    ('!', &['i']),
+    ('|', &['i', 'l']),
+    ('0', &['o']),
+    ('$', &['s']),
+    ('5', &['s']),
+    ('7', &['t']),
+    ('+', &['t']),
+    ('2', &['z']),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Source {
+    Passwords,
+    English,
+    UserInputs,
+}
+
+#[derive(Clone, Debug)]
+enum Pattern {
+    Dictionary {
+        source: Source,
+        rank: usize,
+        reversed: bool,
+        l33t: bool,
+    },
+    Spatial {
+        turns: usize,
+    },
+    Sequence,
+    Repeat {
+        base_length: usize,
+    },
+    Date,
+    Year,
+    Bruteforce,
+}
+
+#[derive(Clone, Debug)]
+struct Match {
+    i: usize,
+    j: usize,
+    pattern: Pattern,
+    guesses: f64,
+}
+
+/// How long an attacker would take at four different guessing rates.
+#[derive(Serialize, Clone, Debug)]
+pub struct CrackTimes {
+    pub online_throttled: String,
+    pub online_unthrottled: String,
+    pub offline_slow_hash: String,
+    pub offline_fast_hash: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Feedback {
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Strength {
+    /// 0 (trivially guessable) to 4 (very unlikely to be guessed).
+    pub score: u8,
+    pub guesses_log10: f64,
+    pub entropy_bits: f64,
+    pub crack_times: CrackTimes,
+    pub feedback: Feedback,
+}
+
+struct Dictionaries {
+    passwords: HashMap<&'static str, usize>,
+    english: HashMap<&'static str, usize>,
+}
+
+fn dictionaries() -> &'static Dictionaries {
+    static DICTIONARIES: OnceLock<Dictionaries> = OnceLock::new();
+
+    DICTIONARIES.get_or_init(|| {
+        let english_rank = ENGLISH_WORDS.lines().count();
+        Dictionaries {
+            passwords: COMMON_PASSWORDS
+                .lines()
+                .enumerate()
+                .map(|(rank, word)| (word, rank + 1))
+                .collect(),
+            english: ENGLISH_WORDS
+                .lines()
+                .map(|word| (word, english_rank))
+                .collect(),
+        }
+    })
+}
+
+fn n_choose_k(n: usize, k: usize) -> f64 {
+    if k > n {
+        return 0.0;
+    }
+    (0..k).fold(1.0, |acc, i| acc * (n - i) as f64 / (i + 1) as f64)
+}
+
+fn factorial(n: usize) -> f64 {
+    (1..=n).fold(1.0, |acc, i| acc * i as f64)
+}
+
+/// Ways of picking which of the `a + b` characters took the rarer form.
+fn variations(a: usize, b: usize) -> f64 {
+    if a == 0 || b == 0 {
+        return 2.0;
+    }
+    (1..=a.min(b)).map(|i| n_choose_k(a + b, i)).sum()
+}
+
+fn uppercase_variations(token: &[char]) -> f64 {
+    let upper = token.iter().filter(|c| c.is_uppercase()).count();
+    let lower = token.iter().filter(|c| c.is_lowercase()).count();
+
+    if upper == 0 {
+        return 1.0;
+    }
+    let first_only = token[0].is_uppercase() && upper == 1;
+    let last_only = token[token.len() - 1].is_uppercase() && upper == 1;
+    if first_only || last_only || lower == 0 {
+        return 2.0;
+    }
+    variations(upper, lower)
+}
+
+fn l33t_variations(token: &[char], plain: &[char]) -> f64 {
+    let mut total = 1.0;
+    let mut seen = vec![];
+
+    for (subbed, original) in token.iter().zip(plain) {
+        let subbed = subbed.to_lowercase().next().unwrap_or(*subbed);
+        if subbed == *original || seen.contains(&(subbed, *original)) {
+            continue;
+        }
+        seen.push((subbed, *original));
+
+        let s = token.iter().filter(|c| **c == subbed).count();
+        let u = token
+            .iter()
+            .filter(|c| c.to_lowercase().next() == Some(*original))
+            .count();
+        total *= match u {
+            0 => 2.0,
+            _ => variations(s, u),
+        };
+    }
+
+    total
+}
+
+fn lookup(word: &str, user_inputs: &HashMap<String, usize>) -> Option<(Source, usize)> {
+    let dictionaries = dictionaries();
+
+    [
+        (Source::UserInputs, user_inputs.get(word).copied()),
+        (Source::Passwords, dictionaries.passwords.get(word).copied()),
+        (Source::English, dictionaries.english.get(word).copied()),
+    ]
+    .into_iter()
+    .filter_map(|(source, rank)| Some((source, rank?)))
+    .min_by_key(|(_, rank)| *rank)
+}
+
+/// The plain spellings a l33t token could stand for, e.g. `p@$$` as `pass`.
+fn unleet(token: &[char]) -> Vec<Vec<char>> {
+    let mut candidates: Vec<Vec<char>> = vec![vec![]];
+
+    for c in token {
+        let lower = c.to_lowercase().next().unwrap_or(*c);
+        let options = L33T_TABLE
+            .iter()
+            .find(|(subbed, _)| *subbed == lower)
+            .map(|(_, letters)| letters.to_vec())
+            .unwrap_or_else(|| vec![lower]);
+
+        if options.len() > 1 && candidates.len() < 8 {
+            candidates = candidates
+                .into_iter()
+                .flat_map(|candidate| {
+                    options.iter().map(move |option| {
+                        let mut next = candidate.clone();
+                        next.push(*option);
+                        next
+                    })
+                })
+                .collect();
+        } else {
+            for candidate in &mut candidates {
+                candidate.push(options[0]);
+            }
+        }
+    }
+
+    candidates
+}
+
+fn dictionary_matches(
+    password: &[char],
+    user_inputs: &HashMap<String, usize>,
+    matches: &mut Vec<Match>,
+) {
+    let lower: Vec<char> = password
+        .iter()
+        .map(|c| c.to_lowercase().next().unwrap_or(*c))
+        .collect();
+    let n = password.len();
+
+    for i in 0..n {
+        for j in i..n.min(i + MAX_WORD_LENGTH) {
+            let token = &password[i..=j];
+            let plain: String = lower[i..=j].iter().collect();
+            let reversed: String = lower[i..=j].iter().rev().collect();
+
+            if let Some((source, rank)) = lookup(&plain, user_inputs) {
+                matches.push(Match {
+                    i,
+                    j,
+                    pattern: Pattern::Dictionary {
+                        source,
+                        rank,
+                        reversed: false,
+                        l33t: false,
+                    },
+                    guesses: rank as f64 * uppercase_variations(token),
+                });
+            }
+
+            if j > i && reversed != plain {
+                if let Some((source, rank)) = lookup(&reversed, user_inputs) {
+                    matches.push(Match {
+                        i,
+                        j,
+                        pattern: Pattern::Dictionary {
+                            source,
+                            rank,
+                            reversed: true,
+                            l33t: false,
+                        },
+                        guesses: rank as f64 * uppercase_variations(token) * 2.0,
+                    });
+                }
+            }
+
+            if j == i || !token.iter().any(|c| L33T_TABLE.iter().any(|(s, _)| s == c)) {
+                continue;
+            }
+            for candidate in unleet(token) {
+                let word: String = candidate.iter().collect();
+                if word == plain {
+                    continue;
+                }
+                if let Some((source, rank)) = lookup(&word, user_inputs) {
+                    matches.push(Match {
+                        i,
+                        j,
+                        pattern: Pattern::Dictionary {
+                            source,
+                            rank,
+                            reversed: false,
+                            l33t: true,
+                        },
+                        guesses: rank as f64
+                            * uppercase_variations(token)
+                            * l33t_variations(token, &candidate),
+                    });
+                }
+            }
+        }
+    }
+}
+
+struct Keyboard {
+    keys: HashMap<char, (usize, usize, bool)>,
+    starting_positions: f64,
+    average_degree: f64,
+}
+
+fn keyboard() -> &'static Keyboard {
+    static KEYBOARD: OnceLock<Keyboard> = OnceLock::new();
+
+    KEYBOARD.get_or_init(|| {
+        let mut keys = HashMap::new();
+        for (row, (plain, shifted)) in KEYBOARD_ROWS.iter().enumerate() {
+            for (col, (p, s)) in plain.chars().zip(shifted.chars()).enumerate() {
+                keys.insert(p, (row, col, false));
+                keys.insert(s, (row, col, true));
+            }
+        }
+
+        let positions: Vec<(usize, usize)> = KEYBOARD_ROWS
+            .iter()
+            .enumerate()
+            .flat_map(|(row, (plain, _))| (0..plain.chars().count()).map(move |col| (row, col)))
+            .collect();
+        let degrees: usize = positions
+            .iter()
+            .map(|a| {
+                positions
+                    .iter()
+                    .filter(|b| direction(*a, **b).is_some())
+                    .count()
+            })
+            .sum();
+
+        Keyboard {
+            keys,
+            starting_positions: positions.len() as f64,
+            average_degree: degrees as f64 / positions.len() as f64,
+        }
+    })
+}
+
+/// Rows are staggered half a key to the right going down, so each key touches two keys in
+/// the rows above and below it.
+fn direction(from: (usize, usize), to: (usize, usize)) -> Option<(i8, i8)> {
+    let dr = to.0 as i64 - from.0 as i64;
+    let dc = to.1 as i64 - from.1 as i64;
+    let adjacent = match dr {
+        0 => dc.abs() == 1,
+        -1 => dc == 0 || dc == 1,
+        1 => dc == -1 || dc == 0,
+        _ => false,
+    };
+    adjacent.then_some((dr as i8, dc as i8))
+}
+
+fn spatial_guesses(length: usize, turns: usize, shifted: usize) -> f64 {
+    let keyboard = keyboard();
+    let mut guesses = 0.0;
+
+    for i in 2..=length {
+        for j in 1..=turns.min(i - 1) {
+            guesses += n_choose_k(i - 1, j - 1)
+                * keyboard.starting_positions
+                * keyboard.average_degree.powi(j as i32);
+        }
+    }
+
+    if shifted > 0 {
+        guesses *= match length - shifted {
+            0 => 2.0,
+            unshifted => variations(shifted, unshifted),
+        };
+    }
+    guesses
+}
+
+fn spatial_matches(password: &[char], matches: &mut Vec<Match>) {
+    let keys = &keyboard().keys;
+    let n = password.len();
+    let mut i = 0;
+
+    while i + 2 < n {
+        let mut j = i;
+        let mut turns = 0;
+        let mut last_direction = None;
+
+        while j + 1 < n {
+            let (Some(a), Some(b)) = (keys.get(&password[j]), keys.get(&password[j + 1])) else {
+                break;
+            };
+            let Some(step) = direction((a.0, a.1), (b.0, b.1)) else {
+                break;
+            };
+            if last_direction != Some(step) {
+                turns += 1;
+                last_direction = Some(step);
+            }
+            j += 1;
+        }
+
+        if j - i >= 2 {
+            let shifted = password[i..=j]
+                .iter()
+                .filter(|c| keys.get(c).is_some_and(|k| k.2))
+                .count();
+            matches.push(Match {
+                i,
+                j,
+                pattern: Pattern::Spatial { turns },
+                guesses: spatial_guesses(j - i + 1, turns, shifted),
+            });
+            i = j + 1;
+        } else {
+            i += 1;
+        }
+    }
+}
+
+fn sequence_matches(password: &[char], matches: &mut Vec<Match>) {
+    let class = |c: char| match c {
+        'a'..='z' => Some(0),
+        'A'..='Z' => Some(1),
+        '0'..='9' => Some(2),
+        _ => None,
+    };
+    let n = password.len();
+    let mut i = 0;
+
+    while i + 2 < n {
+        let delta = password[i + 1] as i64 - password[i] as i64;
+        let same_class =
+            class(password[i]).is_some() && class(password[i]) == class(password[i + 1]);
+        if !same_class || delta == 0 || delta.abs() > 5 {
+            i += 1;
+            continue;
+        }
+
+        let mut j = i + 1;
+        while j + 1 < n
+            && password[j + 1] as i64 - password[j] as i64 == delta
+            && class(password[j + 1]) == class(password[i])
+        {
+            j += 1;
+        }
+
+        if j - i >= 2 {
+            let first = password[i];
+            let base = match first {
+                'a' | 'A' | 'z' | 'Z' | '0' | '1' | '9' => 4.0,
+                '0'..='9' => 10.0,
+                _ => 26.0,
+            };
+            let direction = if delta > 0 { 1.0 } else { 2.0 };
+            matches.push(Match {
+                i,
+                j,
+                pattern: Pattern::Sequence,
+                guesses: base * (j - i + 1) as f64 * direction,
+            });
+            i = j + 1;
+        } else {
+            i += 1;
+        }
+    }
+}
+
+fn repeat_matches(
+    password: &[char],
+    user_inputs: &HashMap<String, usize>,
+    matches: &mut Vec<Match>,
+) {
+    let n = password.len();
+    let mut i = 0;
+
+    while i + 1 < n {
+        let mut best: Option<(usize, usize)> = None;
+
+        for unit in 1..=(n - i) / 2 {
+            let base = &password[i..i + unit];
+            let mut count = 1;
+            while i + (count + 1) * unit <= n
+                && &password[i + count * unit..i + (count + 1) * unit] == base
+            {
+                count += 1;
+            }
+            let span = unit * count;
+            if count > 1 && best.is_none_or(|(_, best_span)| span > best_span) {
+                best = Some((unit, span));
+            }
+        }
+
+        match best {
+            Some((unit, span)) if span >= 3 => {
+                let base = &password[i..i + unit];
+                let base_guesses = estimate_guesses(base, user_inputs).0;
+                matches.push(Match {
+                    i,
+                    j: i + span - 1,
+                    pattern: Pattern::Repeat { base_length: unit },
+                    guesses: base_guesses * (span / unit) as f64,
+                });
+                i += span;
+            }
+            _ => i += 1,
+        }
+    }
+}
+
+fn year_space(year: i64) -> f64 {
+    ((year - today().year as i64).abs().max(MIN_YEAR_SPACE)) as f64
+}
+
+fn valid_date(day: i64, month: i64, year: i64) -> Option<i64> {
+    let year = match year {
+        0..=50 => year + 2000,
+        51..=99 => year + 1900,
+        1000..=2050 => year,
+        _ => return None,
+    };
+    ((1..=12).contains(&month) && (1..=31).contains(&day)).then_some(year)
+}
+
+/// Tries every way of reading `digits` as day, month and year in either order.
+fn parse_date(parts: &[&str]) -> Option<i64> {
+    let numbers: Vec<i64> = parts
+        .iter()
+        .map(|p| p.parse().ok())
+        .collect::<Option<_>>()?;
+    let [a, b, c] = numbers[..] else {
+        return None;
+    };
+
+    [(a, b, c), (b, a, c), (b, c, a), (c, b, a)]
+        .into_iter()
+        .find_map(|(day, month, year)| valid_date(day, month, year))
+}
+
+fn date_matches(password: &[char], matches: &mut Vec<Match>) {
+    let n = password.len();
+
+    for i in 0..n {
+        for j in (i + 3)..n.min(i + 10) {
+            let token: String = password[i..=j].iter().collect();
+            let length = j - i + 1;
+
+            if token.chars().all(|c| c.is_ascii_digit()) {
+                if length == 4 {
+                    if let Ok(year @ 1900..=2050) = token.parse::<i64>() {
+                        matches.push(Match {
+                            i,
+                            j,
+                            pattern: Pattern::Year,
+                            guesses: year_space(year),
+                        });
+                    }
+                }
+                if length > 8 {
+                    continue;
+                }
+
+                let year = (1..length - 1).find_map(|first| {
+                    (first + 1..length).find_map(|second| {
+                        parse_date(&[&token[..first], &token[first..second], &token[second..]])
+                            .filter(|_| first <= 4 && second - first <= 2 && length - second <= 4)
+                    })
+                });
+                if let Some(year) = year {
+                    matches.push(Match {
+                        i,
+                        j,
+                        pattern: Pattern::Date,
+                        guesses: 365.0 * year_space(year),
+                    });
+                }
+                continue;
+            }
+
+            let Some(separator) = token.chars().find(|c| " /\\_.-".contains(*c)) else {
+                continue;
+            };
+            let parts: Vec<&str> = token.split(separator).collect();
+            if parts.len() != 3 || parts.iter().any(|p| p.is_empty() || p.len() > 4) {
+                continue;
+            }
+            if let Some(year) = parse_date(&parts) {
+                matches.push(Match {
+                    i,
+                    j,
+                    pattern: Pattern::Date,
+                    guesses: 365.0 * year_space(year) * 4.0,
+                });
+            }
+        }
+    }
+}
+
+fn bruteforce(i: usize, j: usize) -> Match {
+    Match {
+        i,
+        j,
+        pattern: Pattern::Bruteforce,
+        guesses: BRUTEFORCE_CARDINALITY.powi((j - i + 1) as i32),
+    }
+}
+
+/// Finds the cheapest way to cover the password with matches, penalising long sequences of
+/// small matches the same way zxcvbn does. Returns the guesses and the matches used.
+fn estimate_guesses(password: &[char], user_inputs: &HashMap<String, usize>) -> (f64, Vec<Match>) {
+    let n = password.len();
+    if n == 0 {
+        return (1.0, vec![]);
+    }
+
+    let mut matches = vec![];
+    dictionary_matches(password, user_inputs, &mut matches);
+    spatial_matches(password, &mut matches);
+    sequence_matches(password, &mut matches);
+    repeat_matches(password, user_inputs, &mut matches);
+    date_matches(password, &mut matches);
+
+    for m in &mut matches {
+        let minimum = match m.j - m.i + 1 {
+            length if length == n => 1.0,
+            1 => MIN_SUBMATCH_GUESSES_SINGLE_CHAR,
+            _ => MIN_SUBMATCH_GUESSES_MULTI_CHAR,
+        };
+        m.guesses = m.guesses.max(minimum);
+    }
+
+    let mut by_end: Vec<Vec<Match>> = vec![vec![]; n];
+    for m in matches {
+        by_end[m.j].push(m);
+    }
+
+    // best[k][l] is the cheapest cover of password[..=k] using l matches: (total, product,
+    // last match).
+    let mut best: Vec<HashMap<usize, (f64, f64, Match)>> = vec![HashMap::new(); n];
+    let update =
+        |best: &mut Vec<HashMap<usize, (f64, f64, Match)>>, m: Match, l: usize, product: f64| {
+            let k = m.j;
+            let product = product * m.guesses;
+            let total =
+                factorial(l) * product + MIN_GUESSES_BEFORE_GROWING_SEQUENCE.powi(l as i32 - 1);
+            if best[k]
+                .iter()
+                .any(|(other_l, (other, ..))| *other_l <= l && *other <= total)
+            {
+                return;
+            }
+            best[k].insert(l, (total, product, m));
+        };
+
+    for (k, ending) in by_end.iter().enumerate() {
+        for m in ending {
+            if m.i == 0 {
+                update(&mut best, m.clone(), 1, 1.0);
+                continue;
+            }
+            let previous: Vec<(usize, f64)> = best[m.i - 1]
+                .iter()
+                .map(|(l, (_, product, _))| (*l, *product))
+                .collect();
+            for (l, product) in previous {
+                update(&mut best, m.clone(), l + 1, product);
+            }
+        }
+
+        update(&mut best, bruteforce(0, k), 1, 1.0);
+        for i in 1..=k {
+            let previous: Vec<(usize, f64)> = best[i - 1]
+                .iter()
+                .filter(|(_, (_, _, last))| !matches!(last.pattern, Pattern::Bruteforce))
+                .map(|(l, (_, product, _))| (*l, *product))
+                .collect();
+            for (l, product) in previous {
+                update(&mut best, bruteforce(i, k), l + 1, product);
+            }
+        }
+    }
+
+    let Some((&l, &(guesses, ..))) = best[n - 1].iter().min_by(|a, b| a.1 .0.total_cmp(&b.1 .0))
+    else {
+        return (1.0, vec![]);
+    };
+
+    let mut sequence = vec![];
+    let (mut k, mut l) = (n as isize - 1, l);
+    while k >= 0 {
+        let m = best[k as usize][&l].2.clone();
+        k = m.i as isize - 1;
+        l -= 1;
+        sequence.push(m);
+    }
+    sequence.reverse();
+
+    (guesses, sequence)
+}
+
+fn display_time(seconds: f64) -> String {
+    const MINUTE: f64 = 60.0;
+    const HOUR: f64 = MINUTE * 60.0;
+    const DAY: f64 = HOUR * 24.0;
+    const MONTH: f64 = DAY * 31.0;
+    const YEAR: f64 = MONTH * 12.0;
+    const CENTURY: f64 = YEAR * 100.0;
+
+    let (amount, unit) = match seconds {
+        s if s < 1.0 => return String::from("less than a second"),
+        s if s < MINUTE => (s, "second"),
+        s if s < HOUR => (s / MINUTE, "minute"),
+        s if s < DAY => (s / HOUR, "hour"),
+        s if s < MONTH => (s / DAY, "day"),
+        s if s < YEAR => (s / MONTH, "month"),
+        s if s < CENTURY => (s / YEAR, "year"),
+        _ => return String::from("centuries"),
+    };
+
+    let amount = amount.round() as u64;
+    match amount {
+        1 => format!("1 {}", unit),
+        _ => format!("{} {}s", amount, unit),
+    }
+}
+
+fn score(guesses: f64) -> u8 {
+    const DELTA: f64 = 5.0;
+    match guesses {
+        g if g < 1e3 + DELTA => 0,
+        g if g < 1e6 + DELTA => 1,
+        g if g < 1e8 + DELTA => 2,
+        g if g < 1e10 + DELTA => 3,
+        _ => 4,
+    }
+}
+
+fn dictionary_warning(
+    source: Source,
+    rank: usize,
+    sole: bool,
+    l33t: bool,
+    reversed: bool,
+) -> Option<String> {
+    let warning = match source {
+        Source::Passwords if sole && !l33t && !reversed && rank <= 10 => {
+            "This is a top-10 common password"
+        }
+        Source::Passwords if sole && !l33t && !reversed && rank <= 100 => {
+            "This is a top-100 common password"
+        }
+        Source::Passwords if sole => "This is a very common password",
+        Source::Passwords => "This is similar to a commonly used password",
+        Source::English if sole => "A word by itself is easy to guess",
+        Source::UserInputs => "Avoid using the entry or vault name in the password",
+        Source::English => return None,
+    };
+    Some(String::from(warning))
+}
+
+fn feedback(password: &[char], score: u8, sequence: &[Match]) -> Feedback {
+    let mut suggestions = vec![];
+
+    if password.is_empty() {
+        return Feedback {
+            warning: None,
+            suggestions: vec![
+                String::from("Use a few words, avoid common phrases"),
+                String::from("No need for symbols, digits, or uppercase letters"),
+            ],
+        };
+    }
+    if score > 2 {
+        return Feedback {
+            warning: None,
+            suggestions,
+        };
+    }
+
+    suggestions.push(String::from(
+        "Add another word or two. Uncommon words are better.",
+    ));
+    let Some(longest) = sequence.iter().max_by_key(|m| m.j - m.i) else {
+        return Feedback {
+            warning: None,
+            suggestions,
+        };
+    };
+
+    let warning = match &longest.pattern {
+        Pattern::Dictionary {
+            source,
+            rank,
+            reversed,
+            l33t,
+        } => {
+            let token = &password[longest.i..=longest.j];
+            if token[0].is_uppercase() && token.iter().skip(1).all(|c| !c.is_uppercase()) {
+                suggestions.push(String::from("Capitalization doesn't help very much"));
+            } else if token.iter().all(|c| !c.is_lowercase())
+                && token.iter().any(|c| c.is_uppercase())
+            {
+                suggestions.push(String::from(
+                    "All-uppercase is almost as easy to guess as all-lowercase",
+                ));
+            }
+            if *reversed {
+                suggestions.push(String::from("Reversed words aren't much harder to guess"));
+            }
+            if *l33t {
+                suggestions.push(String::from(
+                    "Predictable substitutions like '@' instead of 'a' don't help very much",
+                ));
+            }
+            dictionary_warning(*source, *rank, sequence.len() == 1, *l33t, *reversed)
+        }
+        Pattern::Spatial { turns } => {
+            suggestions.push(String::from(
+                "Use a longer keyboard pattern with more turns",
+            ));
+            Some(String::from(match turns {
+                1 => "Straight rows of keys are easy to guess",
+                _ => "Short keyboard patterns are easy to guess",
+            }))
+        }
+        Pattern::Repeat { base_length } => {
+            suggestions.push(String::from("Avoid repeated words and characters"));
+            Some(String::from(match base_length {
+                1 => "Repeats like \"aaa\" are easy to guess",
+                _ => "Repeats like \"abcabcabc\" are only slightly harder to guess than \"abc\"",
+            }))
+        }
+        Pattern::Sequence => {
+            suggestions.push(String::from("Avoid sequences"));
+            Some(String::from("Sequences like abc or 6543 are easy to guess"))
+        }
+        Pattern::Date | Pattern::Year => {
+            suggestions.push(String::from(
+                "Avoid dates and years that are associated with you",
+            ));
+            Some(String::from("Dates are often easy to guess"))
+        }
+        Pattern::Bruteforce => None,
+    };
+
+    Feedback {
+        warning,
+        suggestions,
+    }
+}
+
+/// Estimates how many guesses an attacker needs by covering the password with dictionary
+/// words, keyboard walks, sequences, repeats and dates. `user_inputs` such as the entry or
+/// vault name are treated as the most likely words of all.
+pub fn estimate(password: &str, user_inputs: &[&str]) -> Strength {
+    let password: Vec<char> = password.chars().take(MAX_ANALYSED_LENGTH).collect();
+    let user_inputs: HashMap<String, usize> = user_inputs
+        .iter()
+        .flat_map(|input| input.split_whitespace())
+        .map(|input| input.to_lowercase())
+        .filter(|input| input.chars().count() > 2)
+        .enumerate()
+        .map(|(rank, input)| (input, rank + 1))
+        .collect();
+
+    let (guesses, sequence) = estimate_guesses(&password, &user_inputs);
+    let score = score(guesses);
+
+    Strength {
+        score,
+        guesses_log10: guesses.log10(),
+        entropy_bits: guesses.log2(),
+        crack_times: CrackTimes {
+            online_throttled: display_time(guesses / (100.0 / 3600.0)),
+            online_unthrottled: display_time(guesses / 10.0),
+            offline_slow_hash: display_time(guesses / 1e4),
+            offline_fast_hash: display_time(guesses / 1e10),
+        },
+        feedback: feedback(&password, score, &sequence),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sequence(password: &str, user_inputs: &[&str]) -> Vec<Pattern> {
+        let password: Vec<char> = password.chars().collect();
+        let user_inputs = user_inputs
+            .iter()
+            .enumerate()
+            .map(|(rank, input)| (input.to_lowercase(), rank + 1))
+            .collect();
+        let (_, sequence) = estimate_guesses(&password, &user_inputs);
+        sequence.into_iter().map(|m| m.pattern).collect()
+    }
+
+    fn warning(password: &str) -> Option<String> {
+        estimate(password, &[]).feedback.warning
+    }
+
+    #[test]
+    fn golden_scores() {
+        for (password, score) in [
+            ("", 0),
+            ("password", 0),
+            ("P@ssw0rd", 0),
+            ("qwertyuiop", 0),
+            ("aaaaaaaaaa", 0),
+            ("1990", 0),
+            ("zxcvbnm,./", 1),
+            ("13/05/1990", 1),
+            ("Tr0ub4dour&3", 4),
+            ("correcthorsebatterystaple", 4),
+            ("kX9#mq2!vLp7@Zr", 4),
+        ] {
+            assert_eq!(estimate(password, &[]).score, score, "{password}");
+        }
+    }
+
+    #[test]
+    fn common_passwords_and_words() {
+        assert_eq!(
+            warning("password").as_deref(),
+            Some("This is a top-10 common password")
+        );
+        assert!(matches!(
+            sequence("password", &[])[..],
+            [Pattern::Dictionary {
+                source: Source::Passwords,
+                rank: 1..=10,
+                reversed: false,
+                l33t: false,
+            }]
+        ));
+
+        // "battery" and "staple" aren't in the word list, "correct" and "horse" are.
+        let words = sequence("correcthorsebatterystaple", &[]);
+        assert!(matches!(
+            words[..2],
+            [
+                Pattern::Dictionary {
+                    source: Source::English,
+                    ..
+                },
+                Pattern::Dictionary {
+                    source: Source::English,
+                    ..
+                }
+            ]
+        ));
+    }
+
+    #[test]
+    fn l33t_and_reversed_words_are_still_dictionary_words() {
+        assert!(matches!(
+            sequence("P@ssw0rd", &[])[..],
+            [Pattern::Dictionary { l33t: true, .. }]
+        ));
+        let feedback = estimate("P@ssw0rd", &[]).feedback;
+        assert!(feedback
+            .suggestions
+            .iter()
+            .any(|s| s.starts_with("Predictable substitutions")));
+
+        assert!(matches!(
+            sequence("drowssap", &[])[..],
+            [Pattern::Dictionary { reversed: true, .. }]
+        ));
+    }
+
+    #[test]
+    fn keyboard_walks_sequences_and_repeats() {
+        assert!(matches!(
+            sequence("zxcvbnm,./", &[])[..],
+            [Pattern::Spatial { turns: 1 }]
+        ));
+        assert_eq!(
+            warning("zxcvbnm,./").as_deref(),
+            Some("Straight rows of keys are easy to guess")
+        );
+
+        assert!(matches!(sequence("abcdefgh", &[])[..], [Pattern::Sequence]));
+        assert!(matches!(sequence("97531", &[])[..], [Pattern::Sequence]));
+        assert!(matches!(
+            sequence("abcabcabcabc", &[])[..],
+            [Pattern::Repeat { base_length: 3 }]
+        ));
+        assert!(matches!(
+            sequence("aaaaaaaaaa", &[])[..],
+            [Pattern::Repeat { base_length: 1 }]
+        ));
+    }
+
+    #[test]
+    fn dates_and_years() {
+        for date in ["13/05/1990", "19900513", "13.5.90"] {
+            assert!(
+                sequence(date, &[])
+                    .iter()
+                    .any(|p| matches!(p, Pattern::Date)),
+                "{date}"
+            );
+        }
+        assert!(matches!(sequence("1990", &[])[..], [Pattern::Year]));
+        assert_eq!(
+            warning("13/05/1990").as_deref(),
+            Some("Dates are often easy to guess")
+        );
+    }
+
+    #[test]
+    fn user_inputs_count_against_the_password() {
+        let without = estimate("mybank2024", &[]);
+        let with = estimate("mybank2024", &["MyBank"]);
+        assert_eq!((without.score, with.score), (3, 1));
+        assert!(sequence("mybank2024", &["mybank"]).iter().any(|p| matches!(
+            p,
+            Pattern::Dictionary {
+                source: Source::UserInputs,
+                ..
+            }
+        )));
+        assert_eq!(
+            with.feedback.warning.as_deref(),
+            Some("Avoid using the entry or vault name in the password")
+        );
+    }
+
+    #[test]
+    fn random_characters_fall_back_to_bruteforce() {
+        let strength = estimate("kX9#mq2!vLp7@Zr", &[]);
+        assert_eq!(strength.score, 4);
+        assert_eq!(strength.feedback.warning, None);
+        assert!(strength.entropy_bits > 45.0);
+        assert!(sequence("kX9#mq2!vLp7@Zr", &[])
+            .iter()
+            .any(|p| matches!(p, Pattern::Bruteforce)));
+
+        // Only the first characters are analysed so long inputs stay fast.
+        let long = "a1b2c3d4e5".repeat(50);
+        assert!(estimate(&long, &[]).guesses_log10.is_finite());
+    }
+}
@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+
+use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+
+use crate::vault::date::Date;
+
+#[derive(Clone, Copy, Encode, Decode, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldType {
+    Text,
+    /// Never sent back to the frontend, only copied to the clipboard.
+    Protected,
+    Url,
+    Date,
+    /// A TOTP secret, either base32 or an `otpauth://` URI.
+    Otp,
+}
+
+#[derive(Clone, Encode, Decode, Serialize, Deserialize, Debug)]
+pub struct TemplateField {
+    pub name: String,
+    pub field_type: FieldType,
+    pub required: bool,
+    pub default: Option<String>,
+}
+
+#[derive(Clone, Encode, Decode, Serialize, Deserialize, Debug)]
+pub struct Template {
+    pub name: String,
+    pub fields: Vec<TemplateField>,
+}
+
+#[derive(Clone, Encode, Decode, Debug)]
+pub struct CustomField {
+    pub name: String,
+    pub field_type: FieldType,
+    pub value: String,
+}
+
+/// A field of a template entry as shown to the frontend, protected values are withheld.
+#[derive(Serialize, Clone, Debug)]
+pub struct CustomFieldView {
+    pub name: String,
+    pub field_type: FieldType,
+    pub value: Option<String>,
+}
+
+pub fn is_url(value: &str) -> bool {
+    let Some((scheme, rest)) = value.split_once("://") else {
+        return false;
+    };
+    let host = rest.split(['/', '?', '#']).next().unwrap_or("");
+
+    !scheme.is_empty()
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+        && !host.is_empty()
+        && !host.chars().any(char::is_whitespace)
+}
+
+fn is_otp_secret(value: &str) -> bool {
+    if value.starts_with("otpauth://") {
+        return value.contains("secret=");
+    }
+    let secret: String = value
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .trim_end_matches('=')
+        .to_uppercase();
+    secret.len() >= 16 && secret.chars().all(|c| matches!(c, 'A'..='Z' | '2'..='7'))
+}
+
+impl FieldType {
+    fn label(&self) -> &'static str {
+        match self {
+            FieldType::Text => "text",
+            FieldType::Protected => "protected",
+            FieldType::Url => "URL",
+            FieldType::Date => "date",
+            FieldType::Otp => "OTP secret",
+        }
+    }
+
+    pub fn validate(&self, field: &str, value: &str) -> Result<(), String> {
+        let valid = match self {
+            FieldType::Text | FieldType::Protected => true,
+            FieldType::Url => is_url(value),
+            FieldType::Date => Date::parse(value).is_ok(),
+            FieldType::Otp => is_otp_secret(value),
+        };
+
+        match valid {
+            true => Ok(()),
+            false => Err(format!("{} is not a valid {}", field, self.label())),
+        }
+    }
+}
+
+impl Template {
+    pub fn new(name: &str, fields: Vec<TemplateField>) -> Result<Template, String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(String::from("You need a template name"));
+        }
+        if fields.is_empty() {
+            return Err(String::from("Templates need at least one field"));
+        }
+
+        let mut seen = vec![];
+        for field in &fields {
+            if field.name.trim().is_empty() {
+                return Err(String::from("Every template field needs a name"));
+            }
+            if seen.contains(&field.name) {
+                return Err(format!("Field {} is defined twice", field.name));
+            }
+            if let Some(default) = &field.default {
+                field.field_type.validate(&field.name, default)?;
+            }
+            seen.push(field.name.clone());
+        }
+
+        Ok(Template {
+            name: name.to_string(),
+            fields,
+        })
+    }
+
+    /// Fills the template from user supplied values, falling back to defaults and rejecting
+    /// unknown fields or missing required ones.
+    pub fn build(&self, mut values: HashMap<String, String>) -> Result<Vec<CustomField>, String> {
+        let mut fields = vec![];
+
+        for field in &self.fields {
+            let value = values
+                .remove(&field.name)
+                .filter(|v| !v.trim().is_empty())
+                .or_else(|| field.default.clone())
+                .unwrap_or_default();
+
+            if value.is_empty() {
+                if field.required {
+                    return Err(format!("{} is required", field.name));
+                }
+            } else {
+                field.field_type.validate(&field.name, &value)?;
+            }
+
+            fields.push(CustomField {
+                name: field.name.clone(),
+                field_type: field.field_type,
+                value,
+            });
+        }
+
+        if let Some(unknown) = values.keys().next() {
+            return Err(format!("{} is not a field of {}", unknown, self.name));
+        }
+
+        Ok(fields)
+    }
+
+    /// Refits an existing entry to this template when it is redefined. Values for fields the
+    /// template no longer has are refused rather than silently dropped.
+    pub fn rebuild(&self, fields: &[CustomField]) -> Result<Vec<CustomField>, String> {
+        let mut values = HashMap::new();
+
+        for field in fields {
+            if self.fields.iter().any(|t| t.name == field.name) {
+                values.insert(field.name.clone(), field.value.clone());
+            } else if !field.value.is_empty() {
+                return Err(format!("{} still has a value stored in it", field.name));
+            }
+        }
+
+        self.build(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(
+        name: &str,
+        field_type: FieldType,
+        required: bool,
+        default: Option<&str>,
+    ) -> TemplateField {
+        TemplateField {
+            name: name.to_string(),
+            field_type,
+            required,
+            default: default.map(String::from),
+        }
+    }
+
+    fn database() -> Template {
+        Template::new(
+            "Database",
+            vec![
+                field("host", FieldType::Url, true, None),
+                field("password", FieldType::Protected, true, None),
+                field("port", FieldType::Text, false, Some("5432")),
+                field("otp", FieldType::Otp, false, None),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn values(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn rejects_malformed_templates() {
+        assert!(Template::new(" ", vec![field("a", FieldType::Text, false, None)]).is_err());
+        assert!(Template::new("T", vec![]).is_err());
+        assert!(Template::new("T", vec![field(" ", FieldType::Text, false, None)]).is_err());
+
+        let twice = vec![
+            field("a", FieldType::Text, false, None),
+            field("a", FieldType::Url, false, None),
+        ];
+        assert_eq!(
+            Template::new("T", twice).unwrap_err(),
+            "Field a is defined twice"
+        );
+
+        let bad_default = vec![field("when", FieldType::Date, false, Some("tomorrow"))];
+        assert_eq!(
+            Template::new("T", bad_default).unwrap_err(),
+            "when is not a valid date"
+        );
+    }
+
+    #[test]
+    fn builds_fields_with_defaults_and_validation() {
+        let template = database();
+        let mut input = values(&[("host", "postgres://db.example.com")]);
+        assert_eq!(
+            template.build(input.clone()).unwrap_err(),
+            "password is required"
+        );
+
+        input.insert("password".to_string(), "hunter2".to_string());
+        let fields = template.build(input.clone()).unwrap();
+        assert_eq!(fields.len(), 4);
+        assert_eq!(fields[2].value, "5432");
+        assert_eq!(fields[3].value, "");
+
+        input.insert("otp".to_string(), "not base32!".to_string());
+        assert_eq!(
+            template.build(input.clone()).unwrap_err(),
+            "otp is not a valid OTP secret"
+        );
+        input.insert("otp".to_string(), "JBSW Y3DP EHPK 3PXP".to_string());
+        assert!(template.build(input.clone()).is_ok());
+        input.insert(
+            "otp".to_string(),
+            "otpauth://totp/x?secret=JBSWY3DPEHPK3PXP".to_string(),
+        );
+        assert!(template.build(input.clone()).is_ok());
+
+        input.insert("host".to_string(), "db.example.com".to_string());
+        assert_eq!(
+            template.build(input.clone()).unwrap_err(),
+            "host is not a valid URL"
+        );
+        input.insert("host".to_string(), "https://db.example.com".to_string());
+
+        input.insert("colour".to_string(), "blue".to_string());
+        assert_eq!(
+            template.build(input).unwrap_err(),
+            "colour is not a field of Database"
+        );
+    }
+
+    #[test]
+    fn rebuild_refuses_to_drop_stored_values() {
+        let fields = database()
+            .build(values(&[
+                ("host", "https://db.example.com"),
+                ("password", "hunter2"),
+            ]))
+            .unwrap();
+
+        let smaller =
+            Template::new("Database", vec![field("host", FieldType::Url, true, None)]).unwrap();
+        assert_eq!(
+            smaller.rebuild(&fields).unwrap_err(),
+            "password still has a value stored in it"
+        );
+
+        let reshaped = Template::new(
+            "Database",
+            vec![
+                field("host", FieldType::Url, true, None),
+                field("password", FieldType::Protected, true, None),
+                field("otp", FieldType::Otp, false, None),
+                field("database", FieldType::Text, false, Some("main")),
+            ],
+        )
+        .unwrap();
+        assert!(reshaped.rebuild(&fields).is_err());
+
+        let mut without_port = fields.clone();
+        without_port.retain(|f| f.name != "port");
+        let rebuilt = reshaped.rebuild(&without_port).unwrap();
+        assert_eq!(rebuilt[3].value, "main");
+    }
+}
@@ -0,0 +1,446 @@
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    sync::{Mutex, OnceLock},
+};
+
+use bincode::{Decode, Encode};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use url::{Host, Url};
+
+use crate::vault::entry::{Entry, EntryType};
+
+/// Snapshot of https://publicsuffix.org/list/, both the ICANN and private sections.
+const PUBLIC_SUFFIX_LIST: &str = include_str!("public_suffix_list.dat");
+/// Compiled patterns kept before the cache is emptied, far more than a vault holds.
+const MAX_CACHED_PATTERNS: usize = 1024;
+
+/// How the URLs stored on an entry are compared against the page being visited.
+#[derive(Clone, Copy, Encode, Decode, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UrlMatch {
+    /// Same registrable domain, so `login.example.co.uk` matches `example.co.uk`.
+    #[default]
+    BaseDomain,
+    /// Same host and port.
+    ExactHost,
+    /// The visited URL starts with the stored one.
+    StartsWith,
+    /// Stored URLs are regular expressions run against the normalised visited URL.
+    Regex,
+    Never,
+}
+
+/// Best first, so sorting ascending ranks the strongest matches at the top.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchQuality {
+    Exact,
+    Host,
+    Prefix,
+    Domain,
+    Pattern,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct UrlMatchHit {
+    pub name: String,
+    pub kind: EntryType,
+    pub url: String,
+    pub quality: MatchQuality,
+}
+
+struct SuffixList {
+    rules: HashSet<String>,
+    /// `*.ck` is stored as `ck`.
+    wildcards: HashSet<String>,
+    /// `!www.ck` is stored as `www.ck`.
+    exceptions: HashSet<String>,
+}
+
+fn to_ascii(domain: &str) -> Option<String> {
+    if domain.is_ascii() {
+        return Some(domain.to_ascii_lowercase());
+    }
+    match Host::parse(domain) {
+        Ok(Host::Domain(ascii)) => Some(ascii),
+        _ => None,
+    }
+}
+
+fn suffix_list() -> &'static SuffixList {
+    static LIST: OnceLock<SuffixList> = OnceLock::new();
+
+    LIST.get_or_init(|| {
+        let mut list = SuffixList {
+            rules: HashSet::new(),
+            wildcards: HashSet::new(),
+            exceptions: HashSet::new(),
+        };
+
+        for line in PUBLIC_SUFFIX_LIST.lines() {
+            let Some(rule) = line.split_whitespace().next() else {
+                continue;
+            };
+            if rule.starts_with("//") {
+                continue;
+            }
+
+            if let Some(exception) = rule.strip_prefix('!') {
+                list.exceptions.extend(to_ascii(exception));
+            } else if let Some(parent) = rule.strip_prefix("*.") {
+                list.wildcards.extend(to_ascii(parent));
+            } else {
+                list.rules.extend(to_ascii(rule));
+            }
+        }
+
+        list
+    })
+}
+
+/// The public suffix plus one label, e.g. `example.co.uk` for `login.example.co.uk`. Returns
+/// `None` when the host is itself a public suffix.
+pub fn registrable_domain(host: &str) -> Option<&str> {
+    let host = host.trim_end_matches('.');
+    let list = suffix_list();
+
+    let mut starts = vec![0];
+    starts.extend(host.match_indices('.').map(|(i, _)| i + 1));
+
+    let mut suffix_label = starts.len() - 1;
+    for (label, &start) in starts.iter().enumerate() {
+        let candidate = &host[start..];
+        if list.exceptions.contains(candidate) {
+            return Some(candidate);
+        }
+
+        let parent = starts.get(label + 1).map(|&next| &host[next..]);
+        if list.rules.contains(candidate) || parent.is_some_and(|p| list.wildcards.contains(p)) {
+            suffix_label = label;
+            break;
+        }
+    }
+
+    match suffix_label {
+        0 => None,
+        label => Some(&host[starts[label - 1]..]),
+    }
+}
+
+/// Parses a URL the way a user would type it, assuming `https://` when there is no scheme
+/// and dropping credentials and fragments.
+pub fn normalise(input: &str) -> Result<Url, String> {
+    let input = input.trim();
+    let mut url = match Url::parse(input) {
+        Ok(url) if url.has_host() => url,
+        _ => Url::parse(&format!("https://{}", input))
+            .ok()
+            .filter(Url::has_host)
+            .ok_or_else(|| format!("{} is not a valid URL", input))?,
+    };
+
+    url.set_fragment(None);
+    let _ = url.set_username("");
+    let _ = url.set_password(None);
+    Ok(url)
+}
+
+fn site(url: &Url) -> Option<String> {
+    match url.host()? {
+        Host::Domain(domain) => {
+            Some(registrable_domain(domain).unwrap_or(domain).to_string())
+        }
+        host => Some(host.to_string()),
+    }
+}
+
+/// The registrable domain of a URL as a user would type it, or its host when it has none.
+pub fn site_of(input: &str) -> Option<String> {
+    site(&normalise(input).ok()?)
+}
+
+fn same_host(a: &Url, b: &Url) -> bool {
+    a.host() == b.host() && a.port_or_known_default() == b.port_or_known_default()
+}
+
+/// Compiles each stored pattern once rather than on every lookup. `Regex` clones share the
+/// compiled program.
+fn compiled(pattern: &str) -> Option<Regex> {
+    static CACHE: OnceLock<Mutex<HashMap<String, Regex>>> = OnceLock::new();
+
+    let mut cache = CACHE.get_or_init(Default::default).lock().ok()?;
+    if let Some(regex) = cache.get(pattern) {
+        return Some(regex.clone());
+    }
+
+    let regex = Regex::new(pattern).ok()?;
+    if cache.len() >= MAX_CACHED_PATTERNS {
+        cache.clear();
+    }
+    cache.insert(pattern.to_string(), regex.clone());
+    Some(regex)
+}
+
+/// Rejects regular expressions that do not compile so a bad pattern is caught when it is
+/// saved rather than silently never matching.
+pub fn validate(mode: UrlMatch, urls: &[String]) -> Result<(), String> {
+    if mode == UrlMatch::Regex {
+        for url in urls {
+            Regex::new(url).map_err(|_| format!("{} is not a valid regular expression", url))?;
+        }
+    }
+    Ok(())
+}
+
+fn match_url(mode: UrlMatch, stored: &str, target: &Url) -> Option<MatchQuality> {
+    match mode {
+        UrlMatch::Never => return None,
+        UrlMatch::Regex => {
+            let pattern = compiled(stored)?;
+            return pattern
+                .is_match(target.as_str())
+                .then_some(MatchQuality::Pattern);
+        }
+        _ => {}
+    }
+
+    let stored = normalise(stored).ok()?;
+    if stored == *target {
+        return Some(MatchQuality::Exact);
+    }
+
+    match mode {
+        UrlMatch::BaseDomain | UrlMatch::ExactHost if same_host(&stored, target) => {
+            Some(MatchQuality::Host)
+        }
+        UrlMatch::BaseDomain => {
+            let stored_site = site(&stored)?;
+            (Some(stored_site) == site(target)).then_some(MatchQuality::Domain)
+        }
+        UrlMatch::StartsWith if target.as_str().starts_with(stored.as_str()) => {
+            Some(MatchQuality::Prefix)
+        }
+        _ => None,
+    }
+}
+
+/// Entries with a stored URL matching `target` under their own match mode, ranked by match
+/// quality and then by how specific the stored URL is.
+pub fn find<'a>(
+    entries: impl Iterator<Item = (&'a String, &'a Entry)>,
+    target: &str,
+) -> Result<Vec<UrlMatchHit>, String> {
+    let target = normalise(target)?;
+
+    let mut hits = vec![];
+    for (name, entry) in entries {
+        let best = entry
+            .urls
+            .iter()
+            .filter_map(|url| Some((match_url(entry.url_match, url, &target)?, url)))
+            .min_by_key(|(quality, url)| (*quality, Reverse(url.len())));
+
+        if let Some((quality, url)) = best {
+            hits.push(UrlMatchHit {
+                name: name.clone(),
+                kind: entry.entry_type(),
+                url: url.clone(),
+                quality,
+            });
+        }
+    }
+
+    hits.sort_by(|a, b| {
+        (a.quality, Reverse(a.url.len()), &a.name).cmp(&(b.quality, Reverse(b.url.len()), &b.name))
+    });
+    Ok(hits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn entries(urls: &[(&str, &str, UrlMatch)]) -> HashMap<String, Entry> {
+        urls.iter()
+            .map(|&(name, url, mode)| {
+                let mut entry = Entry::login(String::from("secret"));
+                entry.urls = vec![url.to_string()];
+                entry.url_match = mode;
+                (name.to_string(), entry)
+            })
+            .collect()
+    }
+
+    fn matches(entries: &HashMap<String, Entry>, target: &str) -> Vec<(String, MatchQuality)> {
+        find(entries.iter(), target)
+            .unwrap()
+            .into_iter()
+            .map(|hit| (hit.name, hit.quality))
+            .collect()
+    }
+
+    #[test]
+    fn registrable_domain_follows_the_public_suffix_list() {
+        assert_eq!(registrable_domain("a.b.co.uk"), Some("b.co.uk"));
+        assert_eq!(
+            registrable_domain("login.example.co.uk"),
+            Some("example.co.uk")
+        );
+        assert_eq!(registrable_domain("example.com."), Some("example.com"));
+        assert_eq!(registrable_domain("co.uk"), None);
+        assert_eq!(registrable_domain("localhost"), None);
+        // Private section, wildcard and exception rules.
+        assert_eq!(
+            registrable_domain("foo.bar.github.io"),
+            Some("bar.github.io")
+        );
+        assert_eq!(
+            registrable_domain("a.b.c.kawasaki.jp"),
+            Some("b.c.kawasaki.jp")
+        );
+        assert_eq!(
+            registrable_domain("city.kawasaki.jp"),
+            Some("city.kawasaki.jp")
+        );
+        assert_eq!(registrable_domain("a.www.ck"), Some("www.ck"));
+        // Unlisted TLDs are treated as public suffixes of one label.
+        assert_eq!(
+            registrable_domain("example.unlisted"),
+            Some("example.unlisted")
+        );
+        // IDN rules are stored in punycode.
+        assert_eq!(
+            registrable_domain("www.xn--85x722f.xn--55qx5d.cn"),
+            Some("xn--85x722f.xn--55qx5d.cn")
+        );
+    }
+
+    #[test]
+    fn normalise_drops_credentials_and_fragments() {
+        let url = normalise(" https://user:pw@Example.COM:443/path#top ").unwrap();
+        assert_eq!(url.as_str(), "https://example.com/path");
+        assert_eq!(
+            normalise("example.com").unwrap().as_str(),
+            "https://example.com/"
+        );
+        assert_eq!(
+            normalise("bücher.de").unwrap().host_str(),
+            Some("xn--bcher-kva.de")
+        );
+        assert!(normalise("").is_err());
+        assert!(normalise("not a url").is_err());
+    }
+
+    #[test]
+    fn base_domain_matches_across_subdomains_only() {
+        let entries = entries(&[
+            ("site", "example.co.uk", UrlMatch::BaseDomain),
+            ("sibling", "https://other.co.uk", UrlMatch::BaseDomain),
+            ("idn", "bücher.de", UrlMatch::BaseDomain),
+            ("ip", "http://192.168.1.1", UrlMatch::BaseDomain),
+        ]);
+
+        assert_eq!(
+            matches(&entries, "https://login.example.co.uk/path"),
+            [(String::from("site"), MatchQuality::Domain)]
+        );
+        assert!(matches(&entries, "https://example.com").is_empty());
+        assert_eq!(
+            matches(&entries, "https://shop.xn--bcher-kva.de"),
+            [(String::from("idn"), MatchQuality::Domain)]
+        );
+        // IP addresses have no registrable domain, only the same address matches.
+        assert_eq!(
+            matches(&entries, "http://192.168.1.1:8080/admin"),
+            [(String::from("ip"), MatchQuality::Domain)]
+        );
+        assert!(matches(&entries, "http://192.168.1.2").is_empty());
+    }
+
+    #[test]
+    fn exact_host_compares_host_and_port() {
+        let entries = entries(&[
+            ("login", "https://login.example.com", UrlMatch::ExactHost),
+            ("admin", "https://example.com:8443", UrlMatch::ExactHost),
+        ]);
+
+        assert_eq!(
+            matches(&entries, "https://user@login.example.com:443/account"),
+            [(String::from("login"), MatchQuality::Host)]
+        );
+        assert!(matches(&entries, "https://www.example.com").is_empty());
+        assert!(matches(&entries, "https://example.com").is_empty());
+        assert_eq!(
+            matches(&entries, "https://example.com:8443/"),
+            [(String::from("admin"), MatchQuality::Exact)]
+        );
+    }
+
+    #[test]
+    fn starts_with_regex_and_never() {
+        let entries = entries(&[
+            (
+                "prefix",
+                "https://example.com/account",
+                UrlMatch::StartsWith,
+            ),
+            (
+                "pattern",
+                r"^https://[a-z]+\.example\.com/",
+                UrlMatch::Regex,
+            ),
+            ("never", "https://www.example.com/account", UrlMatch::Never),
+        ]);
+
+        assert_eq!(
+            matches(&entries, "https://example.com/account/settings"),
+            [(String::from("prefix"), MatchQuality::Prefix)]
+        );
+        // A plain string prefix, not a path prefix.
+        assert_eq!(
+            matches(&entries, "https://example.com/accounts"),
+            [(String::from("prefix"), MatchQuality::Prefix)]
+        );
+        assert!(matches(&entries, "https://example.com/login").is_empty());
+        assert_eq!(
+            matches(&entries, "https://www.example.com/account"),
+            [(String::from("pattern"), MatchQuality::Pattern)]
+        );
+
+        assert!(validate(UrlMatch::Regex, &[String::from("(")]).is_err());
+        assert!(validate(UrlMatch::StartsWith, &[String::from("(")]).is_ok());
+    }
+
+    #[test]
+    fn ranks_by_quality_then_specificity() {
+        let entries = entries(&[
+            ("base", "example.co.uk", UrlMatch::BaseDomain),
+            ("host", "https://login.example.co.uk", UrlMatch::ExactHost),
+            (
+                "prefix",
+                "https://login.example.co.uk/pa",
+                UrlMatch::StartsWith,
+            ),
+            (
+                "exact",
+                "https://u:p@LOGIN.example.co.uk/path#x",
+                UrlMatch::BaseDomain,
+            ),
+            (
+                "pattern",
+                r"^https://[a-z]+\.example\.co\.uk/",
+                UrlMatch::Regex,
+            ),
+        ]);
+
+        let names: Vec<String> = matches(&entries, "https://login.example.co.uk/path")
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, ["exact", "host", "prefix", "base", "pattern"]);
+        assert!(find(entries.iter(), "").is_err());
+    }
+}
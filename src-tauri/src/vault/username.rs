@@ -0,0 +1,252 @@
+use ring::rand::SystemRandom;
+use serde::Deserialize;
+use url::Host;
+
+use crate::vault::generator::random_index;
+use crate::vault::passphrase::built_in_words;
+use crate::vault::url_match::registrable_domain;
+
+const HANDLE_FIRST: &str = "abcdefghijklmnopqrstuvwxyz";
+const HANDLE_REST: &str = "abcdefghijklmnopqrstuvwxyz0123456789";
+/// Length of the random tag or local part in generated email aliases.
+const ALIAS_LENGTH: usize = 8;
+
+const MAX_WORDS: usize = 5;
+const MAX_DIGITS: usize = 6;
+const MIN_HANDLE: usize = 4;
+const MAX_HANDLE: usize = 64;
+
+fn default_word_count() -> usize {
+    2
+}
+
+fn default_handle_length() -> usize {
+    12
+}
+
+/// How a username is made up.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "style", rename_all = "snake_case")]
+pub enum UsernameStyle {
+    /// Random words run together, e.g. `QuietHarbor42`.
+    Words {
+        #[serde(default = "default_word_count")]
+        word_count: usize,
+        #[serde(default)]
+        separator: String,
+        #[serde(default)]
+        capitalise: bool,
+        #[serde(default)]
+        digits: usize,
+    },
+    /// Random letters and digits that start with a letter.
+    Handle {
+        #[serde(default = "default_handle_length")]
+        length: usize,
+    },
+    /// `user+tag@example.com` on the user's own address. A random tag is used when none is
+    /// given.
+    PlusAddress { email: String, tag: Option<String> },
+    /// A fresh address on a catch-all domain the user owns, optionally starting with a prefix
+    /// such as the site's name.
+    CatchAll {
+        domain: String,
+        prefix: Option<String>,
+    },
+}
+
+impl Default for UsernameStyle {
+    fn default() -> Self {
+        UsernameStyle::Words {
+            word_count: default_word_count(),
+            separator: String::new(),
+            capitalise: true,
+            digits: 2,
+        }
+    }
+}
+
+fn random_string(rng: &SystemRandom, characters: &str, length: usize) -> String {
+    let chars: Vec<char> = characters.chars().collect();
+    (0..length)
+        .map(|_| chars[random_index(rng, chars.len())])
+        .collect()
+}
+
+/// Lowercases and keeps only characters that are safe in the local part of any address.
+fn clean_local_part(input: &str) -> String {
+    input
+        .trim()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+        .map(|c| c.to_ascii_lowercase())
+        .collect::<String>()
+        .trim_matches('.')
+        .to_string()
+}
+
+/// Checks a domain is a real registrable name and returns it in lowercase ASCII.
+fn clean_domain(input: &str) -> Result<String, String> {
+    let domain = match Host::parse(input.trim().trim_end_matches('.')) {
+        Ok(Host::Domain(domain)) => domain,
+        _ => return Err(String::from("That is not a valid domain")),
+    };
+    if registrable_domain(&domain).is_none() {
+        return Err(String::from("That domain is a public suffix"));
+    }
+    Ok(domain)
+}
+
+impl UsernameStyle {
+    pub fn generate(&self) -> Result<String, String> {
+        let rng = SystemRandom::new();
+
+        match self {
+            UsernameStyle::Words {
+                word_count,
+                separator,
+                capitalise,
+                digits,
+            } => {
+                if !(1..=MAX_WORDS).contains(word_count) {
+                    return Err(format!("Word count must be between 1 and {}", MAX_WORDS));
+                }
+                if *digits > MAX_DIGITS {
+                    return Err(format!("At most {} digits can be added", MAX_DIGITS));
+                }
+
+                let list = built_in_words();
+                let words: Vec<String> = (0..*word_count)
+                    .map(|_| {
+                        let word = &list[random_index(&rng, list.len())];
+                        let mut chars = word.chars();
+                        match (capitalise, chars.next()) {
+                            (true, Some(first)) => first.to_uppercase().chain(chars).collect(),
+                            _ => word.clone(),
+                        }
+                    })
+                    .collect();
+
+                Ok(words.join(separator) + &random_string(&rng, "0123456789", *digits))
+            }
+            UsernameStyle::Handle { length } => {
+                if !(MIN_HANDLE..=MAX_HANDLE).contains(length) {
+                    return Err(format!(
+                        "Length must be between {} and {}",
+                        MIN_HANDLE, MAX_HANDLE
+                    ));
+                }
+                Ok(random_string(&rng, HANDLE_FIRST, 1)
+                    + &random_string(&rng, HANDLE_REST, length - 1))
+            }
+            UsernameStyle::PlusAddress { email, tag } => {
+                let (local, domain) = email
+                    .trim()
+                    .rsplit_once('@')
+                    .ok_or_else(|| String::from("That is not an email address"))?;
+                // Replace any tag already on the address rather than stacking a second one.
+                let local = local.split('+').next().unwrap_or_default();
+                if local.is_empty() {
+                    return Err(String::from("That is not an email address"));
+                }
+                let domain = clean_domain(domain)?;
+
+                let tag = match tag.as_deref().map(clean_local_part) {
+                    Some(tag) if !tag.is_empty() => tag,
+                    _ => random_string(&rng, HANDLE_REST, ALIAS_LENGTH),
+                };
+                Ok(format!("{}+{}@{}", local, tag, domain))
+            }
+            UsernameStyle::CatchAll { domain, prefix } => {
+                let domain = clean_domain(domain)?;
+                let random = random_string(&rng, HANDLE_REST, ALIAS_LENGTH);
+
+                Ok(match prefix.as_deref().map(clean_local_part) {
+                    Some(prefix) if !prefix.is_empty() => {
+                        format!("{}.{}@{}", prefix, random, domain)
+                    }
+                    _ => format!("{}@{}", random, domain),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plus_address(email: &str, tag: Option<&str>) -> Result<String, String> {
+        UsernameStyle::PlusAddress {
+            email: email.to_string(),
+            tag: tag.map(String::from),
+        }
+        .generate()
+    }
+
+    fn catch_all(domain: &str, prefix: Option<&str>) -> Result<String, String> {
+        UsernameStyle::CatchAll {
+            domain: domain.to_string(),
+            prefix: prefix.map(String::from),
+        }
+        .generate()
+    }
+
+    #[test]
+    fn generates_words_and_handles() {
+        let style: UsernameStyle =
+            serde_json::from_str(r#"{"style":"words","word_count":3,"separator":"_"}"#).unwrap();
+        let username = style.generate().unwrap();
+        assert_eq!(username.split('_').count(), 3);
+        assert!(username
+            .split('_')
+            .all(|w| built_in_words().contains(&w.to_string())));
+
+        let handle = UsernameStyle::Handle { length: 10 }.generate().unwrap();
+        assert_eq!(handle.len(), 10);
+        assert!(handle.starts_with(|c: char| c.is_ascii_lowercase()));
+        assert!(UsernameStyle::Handle { length: 3 }.generate().is_err());
+    }
+
+    #[test]
+    fn plus_addressing_replaces_an_existing_tag() {
+        assert_eq!(
+            plus_address("Me+old@Example.COM", Some("Shop Site")).unwrap(),
+            "Me+shopsite@example.com"
+        );
+        assert_eq!(
+            plus_address("me+a+b@example.com", Some("new")).unwrap(),
+            "me+new@example.com"
+        );
+
+        let random = plus_address("me+old@example.com", None).unwrap();
+        let tag = random
+            .strip_prefix("me+")
+            .and_then(|rest| rest.strip_suffix("@example.com"))
+            .unwrap();
+        assert_eq!(tag.len(), ALIAS_LENGTH);
+        assert!(tag.chars().all(|c| HANDLE_REST.contains(c)));
+
+        assert!(plus_address("nope", None).is_err());
+        assert!(plus_address("+tag@example.com", None).is_err());
+    }
+
+    #[test]
+    fn catch_all_needs_a_registrable_domain() {
+        let address = catch_all("Mail.Example.org", Some("GitHub")).unwrap();
+        assert!(address.starts_with("github."));
+        assert!(address.ends_with("@mail.example.org"));
+
+        let address = catch_all("example.org", None).unwrap();
+        assert_eq!(address.len(), ALIAS_LENGTH + "@example.org".len());
+
+        assert_eq!(
+            catch_all("co.uk", None).unwrap_err(),
+            "That domain is a public suffix"
+        );
+        assert_eq!(
+            catch_all("not a domain", None).unwrap_err(),
+            "That is not a valid domain"
+        );
+    }
+}
@@ -4,12 +4,38 @@ use bincode::{
     config::{self, Configuration},
     decode_from_slice, encode_to_vec, Decode, Encode,
 };
-use std::env::var_os;
 use std::fs;
-use std::{collections::HashMap, marker::PhantomData, path::PathBuf};
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+    path::Path,
+    sync::Arc,
+};
 
+use crate::storage;
+use crate::store::store::VaultStore;
+use crate::vault::attachment::{self, blob_path, new_attachment_id, Attachment};
+use crate::vault::breach::{self, BreachedSecret, HashFile};
+use crate::vault::date::now_secs;
+use crate::vault::encrypted_password::EncryptedPasswords;
+use crate::vault::entry::{
+    Entry, EntryField, EntryKind, EntrySummary, EntryWarning, ExpiringEntry, Expiry,
+};
+use crate::vault::health::{self, HealthReport};
+use crate::vault::journal::{self, Change};
+use crate::vault::passkey::{encode_b64, Assertion, PasskeySummary};
+use crate::vault::password_rules::PasswordRules;
+use crate::vault::search::{self, SearchHit};
+use crate::vault::template::{CustomFieldView, FieldType, Template};
+use crate::vault::url_match::{self, UrlMatch, UrlMatchHit};
+use crate::vault::verify::{self, Opened, RepairOutcome, Stage, VerifyReport};
+use crate::vault::metadata::{self, MetadataSettings, VaultHeader, VaultMetadata};
 use crate::vault::master_password::{empty_master_password, init_master_password, Pbkdf2Component};
 
+/// Marks a decrypted payload as typed entries, templates and when the vault was created.
+/// Payloads with no marker predate entry kinds and hold a plain name to password map.
+const PAYLOAD_MAGIC: &[u8; 4] = b"ANE1";
+
 pub enum RetrieveResult {
     Success,
     Failure,
@@ -38,32 +64,136 @@ impl LockState for Unlocked {
     }
 }
 
-#[derive(Clone, Encode, Decode, Debug)]
+#[derive(Clone, Debug)]
 pub struct Vault<State = Pending> {
-    passwords: HashMap<String, String>,
+    passwords: HashMap<String, Entry>,
+    templates: HashMap<String, Template>,
     pbkdf2_component: Pbkdf2Component,
+    store: Arc<dyn VaultStore>,
+    /// Sealed changes made since the vault file was last written, replayed over it on unlock.
+    journal: Vec<Vec<u8>>,
+    metadata: VaultMetadata,
+    /// Unix time in seconds the vault was made, unknown for vaults made before it was kept.
+    /// Only known once unlocked.
+    created: Option<u64>,
     state: PhantomData<State>,
 }
 
 impl Vault {
-    pub fn new() -> Self {
+    pub fn new(store: Arc<dyn VaultStore>) -> Self {
         Vault {
             passwords: HashMap::new(),
+            templates: HashMap::new(),
             state: PhantomData::<Pending>,
             pbkdf2_component: empty_master_password(),
+            store,
+            journal: vec![],
+            metadata: VaultMetadata::default(),
+            created: None,
         }
     }
 }
 
+/// Layout of a vault on disk. Older versions kept a plaintext copy of every password in
+/// `legacy_passwords`, it is still read so those vaults can be opened but is always written empty.
+#[derive(Encode, Decode)]
+pub(crate) struct VaultFile {
+    pub(crate) legacy_passwords: HashMap<String, String>,
+    pub(crate) pbkdf2_component: Pbkdf2Component,
+}
+
+/// What is encrypted in a vault file.
+#[derive(Encode, Decode, Default)]
+pub(crate) struct Payload {
+    pub(crate) entries: HashMap<String, Entry>,
+    pub(crate) templates: HashMap<String, Template>,
+    pub(crate) created: Option<u64>,
+}
+
+fn encode_payload(
+    entries: &HashMap<String, Entry>,
+    templates: &HashMap<String, Template>,
+    created: Option<u64>,
+) -> Vec<u8> {
+    let mut payload = PAYLOAD_MAGIC.to_vec();
+    payload.extend(encode_to_vec((entries, templates, created), config::standard()).unwrap());
+    payload
+}
+
+/// Decodes a decrypted payload, saying which layout it failed to decode as. An empty payload
+/// is a vault that was never saved with entries.
+pub(crate) fn try_decode_payload(payload: &[u8]) -> Result<Payload, String> {
+    let config = config::standard();
+
+    if let Some(encoded) = payload.strip_prefix(PAYLOAD_MAGIC) {
+        return match decode_from_slice::<Payload, Configuration>(encoded, config) {
+            Ok((payload, _)) => Ok(payload),
+            Err(e) => Err(format!("Entries and templates could not be decoded: {}", e)),
+        };
+    }
+
+    if payload.is_empty() {
+        return Ok(Payload::default());
+    }
+    match decode_from_slice::<HashMap<String, String>, Configuration>(payload, config) {
+        Ok((passwords, _)) => Ok(Payload {
+            entries: passwords
+                .into_iter()
+                .map(|(name, password)| {
+                    let mut entry = Entry::login(password);
+                    entry.rate_strength(&name);
+                    (name, entry)
+                })
+                .collect(),
+            ..Default::default()
+        }),
+        Err(e) => Err(format!("Passwords from an older version could not be decoded: {}", e)),
+    }
+}
+
 impl Vault<Locked> {
-    fn unlock(self, password: &str) -> Vault<Unlocked> {
-        let pbkdf2_component = init_master_password(password);
+    fn from_file(file: &[u8], store: Arc<dyn VaultStore>) -> Option<Vault<Locked>> {
+        let (header, decoded) = decode_file(file).ok()?;
+        Some(Vault {
+            passwords: decoded
+                .legacy_passwords
+                .into_iter()
+                .map(|(name, password)| (name, Entry::login(password)))
+                .collect(),
+            templates: HashMap::new(),
+            pbkdf2_component: decoded.pbkdf2_component,
+            store,
+            journal: vec![],
+            metadata: header.metadata,
+            created: None,
+            state: PhantomData::<Locked>,
+        })
+    }
 
-        Vault {
-            passwords: self.passwords,
-            pbkdf2_component: pbkdf2_component,
+    fn unlock(self, password: &str) -> Result<Vault<Unlocked>, String> {
+        let mut pbkdf2_component = self.pbkdf2_component;
+        pbkdf2_component.derive_key(password);
+
+        let decrypted = pbkdf2_component
+            .encrypted_passwords
+            .clone()
+            .decrypt(pbkdf2_component.derived_key)?;
+
+        let payload = try_decode_payload(&decrypted)?;
+        let mut passwords = self.passwords;
+        passwords.extend(payload.entries);
+        replay(&mut passwords, &pbkdf2_component, &self.journal);
+
+        Ok(Vault {
+            passwords,
+            templates: payload.templates,
+            pbkdf2_component,
+            store: self.store,
+            journal: self.journal,
+            metadata: self.metadata,
+            created: payload.created,
             state: PhantomData::<Unlocked>,
-        }
+        })
     }
 
     pub fn retrieve_from_file(&mut self) -> RetrieveResult {
@@ -82,7 +212,22 @@ impl Vault<Locked> {
         // let (decoded, _) = decode_from_slice::<HashMap<String, String>, Configuration>(&passwords, config).unwrap();
         // self.passwords = decoded;
 
-        return RetrieveResult::Success;
+        RetrieveResult::Success
+    }
+}
+
+impl Vault<Unlocked> {
+    fn from_opened(opened: Opened, store: Arc<dyn VaultStore>) -> Vault<Unlocked> {
+        Vault {
+            passwords: opened.entries,
+            templates: opened.templates,
+            pbkdf2_component: opened.pbkdf2_component,
+            store,
+            journal: vec![],
+            metadata: opened.metadata,
+            created: opened.created,
+            state: PhantomData::<Unlocked>,
+        }
     }
 }
 
@@ -90,8 +235,13 @@ impl Clone for Vault<Unlocked> {
     fn clone(&self) -> Self {
         Self {
             passwords: self.passwords.clone(),
+            templates: self.templates.clone(),
             pbkdf2_component: self.pbkdf2_component.clone(),
-            state: self.state.clone(),
+            store: self.store.clone(),
+            journal: self.journal.clone(),
+            metadata: self.metadata.clone(),
+            created: self.created,
+            state: self.state,
         }
     }
 }
@@ -100,47 +250,68 @@ impl Clone for Vault<Locked> {
     fn clone(&self) -> Self {
         Self {
             passwords: self.passwords.clone(),
+            templates: self.templates.clone(),
             pbkdf2_component: self.pbkdf2_component.clone(),
-            state: self.state.clone(),
+            store: self.store.clone(),
+            journal: self.journal.clone(),
+            metadata: self.metadata.clone(),
+            created: self.created,
+            state: self.state,
         }
     }
 }
 
 impl Vault<Pending> {
-    pub fn create_new(self, password: &str, vault_name: &str) -> Vault<Unlocked> {
+    pub fn create_new(
+        self,
+        password: &str,
+        vault_name: &str,
+    ) -> Result<Vault<Unlocked>, String> {
         let pbkdf2_component = init_master_password(password);
 
         let mut passwords = Vault {
             passwords: HashMap::new(),
+            templates: HashMap::new(),
             pbkdf2_component,
+            store: self.store,
+            journal: vec![],
+            metadata: self.metadata,
+            created: Some(now_secs()),
             state: PhantomData::<Unlocked>,
         };
 
-        passwords.save_to_file(vault_name);
+        passwords.save_to_file(vault_name)?;
 
-        passwords
+        Ok(passwords)
     }
 
-    fn retrieve_from_file(&mut self, vault_name: &str) {
-        let path = get_data_path(vault_name);
-        let file = fs::read(path).unwrap();
-        let config = config::standard();
+    fn retrieve_from_file(&mut self, vault_name: &str) -> Result<(), String> {
+        let file = self.store.read(vault_name)?.unwrap_or_default();
 
-        let (decoded, _) = match decode_from_slice(&file, config) {
-            Ok(r) => r,
-            Err(_) => {
-                println!("Current save file is probabily incompatible with this version");
-                panic!()
-            }
-        };
+        let (header, decoded) = decode_file(&file).map_err(|_| {
+            String::from("Current save file is probably incompatible with this version")
+        })?;
 
-        *self = decoded;
+        self.passwords = decoded
+            .legacy_passwords
+            .into_iter()
+            .map(|(name, password)| (name, Entry::login(password)))
+            .collect();
+        self.pbkdf2_component = decoded.pbkdf2_component;
+        self.journal = self.store.journal(vault_name)?;
+        self.metadata = header.metadata;
+        Ok(())
     }
 
     fn retrieved(self) -> Vault<Locked> {
         Vault {
             passwords: self.passwords,
+            templates: self.templates,
             pbkdf2_component: self.pbkdf2_component,
+            store: self.store,
+            journal: self.journal,
+            metadata: self.metadata,
+            created: self.created,
             state: PhantomData::<Locked>,
         }
     }
@@ -148,14 +319,61 @@ impl Vault<Pending> {
 
 impl Vault<Unlocked> {
     pub fn retrieve_password(&self, name: &str) -> Result<String, &str> {
-        match self.passwords.contains_key(name) {
-            true => {
-                return Ok(self.passwords.get(name).unwrap().to_string());
-            }
-            false => {
-                return Err("No entry of that name found");
+        match self.passwords.get(name).map(|entry| &entry.kind) {
+            Some(EntryKind::Login { password }) => Ok(password.to_string()),
+            Some(_) => Err("That entry does not hold a password"),
+            None => Err("No entry of that name found"),
+        }
+    }
+
+    pub fn retrieve_secure_note(&self, name: &str) -> Result<String, &str> {
+        match self.passwords.get(name).map(|entry| &entry.kind) {
+            Some(EntryKind::SecureNote { text }) => Ok(text.to_string()),
+            Some(_) => Err("That entry is not a secure note"),
+            None => Err("No entry of that name found"),
+        }
+    }
+
+    pub fn retrieve_field(&self, name: &str, field: EntryField) -> Result<String, &str> {
+        match self.passwords.get(name) {
+            Some(entry) => entry.field(field),
+            None => Err("No entry of that name found"),
+        }
+    }
+
+    pub fn list_warnings(&self) -> Vec<EntryWarning> {
+        let mut warnings = vec![];
+
+        for (key, entry) in &self.passwords {
+            for message in entry.warnings() {
+                warnings.push(EntryWarning {
+                    name: key.to_string(),
+                    message,
+                });
             }
         }
+
+        warnings.sort_by(|a, b| a.name.cmp(&b.name));
+        warnings
+    }
+
+    /// Checks every stored password and protected template field against a local Have I Been
+    /// Pwned download.
+    pub fn check_breaches(&self, hash_file: &Path) -> Result<Vec<BreachedSecret>, String> {
+        let mut hash_file = HashFile::open(hash_file)?;
+
+        let secrets = self.passwords.iter().flat_map(|(key, entry)| {
+            entry
+                .secrets()
+                .into_iter()
+                .map(move |(field, secret)| (key.as_str(), field, secret))
+        });
+
+        breach::check(&mut hash_file, secrets)
+    }
+
+    pub fn health_report(&self) -> HealthReport {
+        health::audit(&self.passwords)
     }
 
     pub fn insert_password(
@@ -163,164 +381,1137 @@ impl Vault<Unlocked> {
         name: String,
         password: String,
         vault_name: &str,
-    ) -> Result<(), &str> {
+    ) -> Result<(), String> {
+        self.insert_entry(name, Entry::login(password), vault_name)
+    }
+
+    pub fn insert_entry(
+        &mut self,
+        name: String,
+        mut entry: Entry,
+        vault_name: &str,
+    ) -> Result<(), String> {
         match self.passwords.contains_key(&name) {
-            true => {
-                return Err("Name already in use");
-            }
+            true => Err(String::from("Name already in use")),
             false => {
-                self.passwords.insert(name, password);
-                self.save_to_file(vault_name);
-                return Ok(());
+                entry.rate_strength(&name);
+                self.passwords.insert(name.clone(), entry);
+                self.save_entry(&name, vault_name)
+            }
+        }
+    }
+
+    pub fn update_secure_note(
+        &mut self,
+        name: &str,
+        text: String,
+        vault_name: &str,
+    ) -> Result<(), String> {
+        let entry = self.passwords.get_mut(name).ok_or("No entry of that name found")?;
+        match &mut entry.kind {
+            EntryKind::SecureNote { text: stored } => {
+                *stored = text;
             }
+            _ => return Err(String::from("That entry is not a secure note")),
         }
+        entry.touch();
+        self.save_entry(name, vault_name)
+    }
+
+    pub fn update_password(
+        &mut self,
+        name: &str,
+        password: String,
+        vault_name: &str,
+    ) -> Result<(), String> {
+        let entry = self.passwords.get_mut(name).ok_or("No entry of that name found")?;
+        match &mut entry.kind {
+            EntryKind::Login { password: stored } => {
+                *stored = password;
+            }
+            _ => return Err(String::from("That entry does not hold a password")),
+        }
+        entry.touch();
+        entry.rate_strength(name);
+        self.save_entry(name, vault_name)
+    }
+
+    pub fn set_expiry(
+        &mut self,
+        name: &str,
+        expiry: Option<Expiry>,
+        vault_name: &str,
+    ) -> Result<(), String> {
+        let entry = self.passwords.get_mut(name).ok_or("No entry of that name found")?;
+        entry.expiry = expiry;
+        self.save_entry(name, vault_name)
+    }
+
+    /// Replaces the searchable metadata of an entry. Blank URLs and tags are dropped and tags
+    /// are deduplicated.
+    pub fn update_details(
+        &mut self,
+        name: &str,
+        username: &str,
+        urls: Vec<String>,
+        notes: &str,
+        tags: Vec<String>,
+        vault_name: &str,
+    ) -> Result<(), String> {
+        let entry = self
+            .passwords
+            .get_mut(name)
+            .ok_or_else(|| String::from("No entry of that name found"))?;
+
+        let mut cleaned_tags: Vec<String> = vec![];
+        for tag in tags.iter().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+            if !cleaned_tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                cleaned_tags.push(tag.to_string());
+            }
+        }
+
+        let urls: Vec<String> = urls
+            .iter()
+            .map(|u| u.trim().to_string())
+            .filter(|u| !u.is_empty())
+            .collect();
+        url_match::validate(entry.url_match, &urls)?;
+
+        entry.username = username.trim().to_string();
+        entry.urls = urls;
+        entry.notes = notes.to_string();
+        entry.tags = cleaned_tags;
+        entry.rate_strength(name);
+        self.save_entry(name, vault_name)
+    }
+
+    pub fn set_url_match(
+        &mut self,
+        name: &str,
+        mode: UrlMatch,
+        vault_name: &str,
+    ) -> Result<(), String> {
+        let entry = self
+            .passwords
+            .get_mut(name)
+            .ok_or_else(|| String::from("No entry of that name found"))?;
+        url_match::validate(mode, &entry.urls)?;
+
+        entry.url_match = mode;
+        self.save_entry(name, vault_name)
+    }
+
+    pub fn set_username(
+        &mut self,
+        name: &str,
+        username: &str,
+        vault_name: &str,
+    ) -> Result<(), String> {
+        let entry = self.passwords.get_mut(name).ok_or("No entry of that name found")?;
+        entry.username = username.trim().to_string();
+        entry.rate_strength(name);
+        self.save_entry(name, vault_name)
+    }
+
+    /// Stores a site's password rule on an entry, a blank rule removes it.
+    pub fn set_password_rule(
+        &mut self,
+        name: &str,
+        rule: Option<String>,
+        vault_name: &str,
+    ) -> Result<(), String> {
+        let entry = self
+            .passwords
+            .get_mut(name)
+            .ok_or_else(|| String::from("No entry of that name found"))?;
+
+        let rule = rule
+            .map(|r| r.trim().to_string())
+            .filter(|r| !r.is_empty());
+        if let Some(rule) = &rule {
+            PasswordRules::parse(rule)?;
+        }
+
+        entry.password_rule = rule;
+        self.save_entry(name, vault_name)
+    }
+
+    pub fn password_rules(&self, name: &str) -> Result<Option<PasswordRules>, String> {
+        let entry = self
+            .passwords
+            .get(name)
+            .ok_or_else(|| String::from("No entry of that name found"))?;
+        entry.password_rule.as_deref().map(PasswordRules::parse).transpose()
+    }
+
+    pub fn match_url(&self, url: &str) -> Result<Vec<UrlMatchHit>, String> {
+        url_match::find(self.passwords.iter(), url)
+    }
+
+    pub fn search_entries(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        search::search(self.passwords.iter(), query, limit)
+    }
+
+    /// Entries that are overdue or will be within `within_days`, soonest first.
+    pub fn list_expiring(&self, within_days: u32) -> Vec<ExpiringEntry> {
+        let mut expiring = vec![];
+
+        for (key, entry) in &self.passwords {
+            let (Some(due), Some(days_left)) = (entry.due_date(), entry.days_until_due()) else {
+                continue;
+            };
+            if days_left <= within_days as i64 {
+                expiring.push(ExpiringEntry {
+                    name: key.to_string(),
+                    due: due.to_string(),
+                    days_left,
+                    expired: days_left < 0,
+                });
+            }
+        }
+
+        expiring.sort_by(|a, b| a.days_left.cmp(&b.days_left).then(a.name.cmp(&b.name)));
+        expiring
+    }
+
+    /// Recomputes a derived entry's password. The master password is checked first so a typo
+    /// can't quietly produce a different password.
+    pub fn derive_password(&self, name: &str, master_password: &str) -> Result<String, String> {
+        let profile = match self.passwords.get(name).map(|entry| &entry.kind) {
+            Some(EntryKind::Derived(profile)) => profile,
+            Some(_) => return Err(String::from("That entry is not a derived password")),
+            None => return Err(String::from("No entry of that name found")),
+        };
+
+        self.pbkdf2_component
+            .verify_password(master_password)
+            .map_err(|_| String::from("Wrong master password"))?;
+        profile.derive(master_password)
+    }
+
+    /// Moves a derived entry on to its next password and returns the new counter.
+    pub fn rotate_derived_password(
+        &mut self,
+        name: &str,
+        vault_name: &str,
+    ) -> Result<u32, String> {
+        let entry = self
+            .passwords
+            .get_mut(name)
+            .ok_or_else(|| String::from("No entry of that name found"))?;
+        let EntryKind::Derived(profile) = &mut entry.kind else {
+            return Err(String::from("That entry is not a derived password"));
+        };
+
+        profile.counter = profile
+            .counter
+            .checked_add(1)
+            .ok_or_else(|| String::from("That entry cannot be rotated any further"))?;
+        let counter = profile.counter;
+        entry.touch();
+        self.save_entry(name, vault_name)?;
+        Ok(counter)
     }
 
     pub fn delete_password(
         &mut self,
         name: String,
         vault_name: &str
-    ) -> Result<(), &str> {
+    ) -> Result<(), String> {
         match self.passwords.contains_key(&name) {
             true => {
-                self.passwords.remove(&name).unwrap();
-                self.save_to_file(vault_name);
-                return Ok(());
+                let entry = self.passwords.remove(&name).unwrap();
+                self.save_entry(&name, vault_name)?;
+                let dir = storage::attachment_dir(vault_name);
+                for attachment in entry.attachments {
+                    let _ = fs::remove_file(blob_path(&dir, &attachment.id));
+                }
+                Ok(())
             },
-            false => {
-                return Err("That's not an existing password")
+            false => Err(String::from("That's not an existing password")),
+        }
+    }
+
+    pub fn list_attachments(&self, name: &str) -> Result<Vec<Attachment>, &str> {
+        match self.passwords.get(name) {
+            Some(entry) => Ok(entry.attachments.clone()),
+            None => Err("No entry of that name found"),
+        }
+    }
+
+    pub fn attach_file(
+        &mut self,
+        name: &str,
+        source: &Path,
+        vault_name: &str,
+    ) -> Result<Attachment, String> {
+        if !self.passwords.contains_key(name) {
+            return Err(String::from("No entry of that name found"));
+        }
+
+        let file_name = source
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or("That path is not a file")?
+            .to_string();
+
+        let dir = storage::attachment_dir(vault_name);
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+
+        let id = new_attachment_id();
+        let destination = blob_path(&dir, &id);
+        let key = &self.pbkdf2_component.derived_key;
+        let size = attachment::seal_file(key, source, &destination)?;
+
+        let attachment = Attachment {
+            id,
+            file_name,
+            size,
+        };
+        self.passwords
+            .get_mut(name)
+            .unwrap()
+            .attachments
+            .push(attachment.clone());
+        self.save_entry(name, vault_name)?;
+
+        Ok(attachment)
+    }
+
+    pub fn export_attachment(
+        &self,
+        name: &str,
+        id: &str,
+        destination: &Path,
+        vault_name: &str,
+    ) -> Result<(), String> {
+        let entry = self.passwords.get(name).ok_or("No entry of that name found")?;
+        if !entry.attachments.iter().any(|a| a.id == id) {
+            return Err(String::from("That entry has no such attachment"));
+        }
+
+        let source = blob_path(&storage::attachment_dir(vault_name), id);
+        attachment::open_file(&self.pbkdf2_component.derived_key, &source, destination)
+    }
+
+    pub fn delete_attachment(
+        &mut self,
+        name: &str,
+        id: &str,
+        vault_name: &str,
+    ) -> Result<(), String> {
+        let entry = self.passwords.get_mut(name).ok_or("No entry of that name found")?;
+        let position = entry
+            .attachments
+            .iter()
+            .position(|a| a.id == id)
+            .ok_or("That entry has no such attachment")?;
+
+        entry.attachments.remove(position);
+        self.save_entry(name, vault_name)?;
+
+        match fs::remove_file(blob_path(&storage::attachment_dir(vault_name), id)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn list_passkeys(&self, rp_id: Option<&str>) -> Vec<PasskeySummary> {
+        let mut passkeys = vec![];
+
+        for (key, entry) in &self.passwords {
+            if let EntryKind::Passkey(passkey) = &entry.kind {
+                if rp_id.is_some_and(|rp_id| rp_id != passkey.rp_id) {
+                    continue;
+                }
+                passkeys.push(PasskeySummary {
+                    name: key.to_string(),
+                    rp_id: passkey.rp_id.clone(),
+                    user_name: passkey.user_name.clone(),
+                    credential_id: encode_b64(&passkey.credential_id),
+                });
+            }
+        }
+
+        passkeys.sort_by(|a, b| a.name.cmp(&b.name));
+        passkeys
+    }
+
+    pub fn sign_with_passkey(
+        &mut self,
+        name: &str,
+        rp_id: &str,
+        client_data_hash: &[u8],
+        user_verified: bool,
+        vault_name: &str,
+    ) -> Result<Assertion, String> {
+        let assertion = match self.passwords.get_mut(name).map(|entry| &mut entry.kind) {
+            Some(EntryKind::Passkey(passkey)) => {
+                passkey.assert(rp_id, client_data_hash, user_verified)?
+            }
+            Some(_) => return Err(String::from("That entry is not a passkey")),
+            None => return Err(String::from("No entry of that name found")),
+        };
+
+        self.save_entry(name, vault_name)?;
+        Ok(assertion)
+    }
+
+    pub fn list_templates(&self) -> Vec<Template> {
+        let mut templates: Vec<Template> = self.templates.values().cloned().collect();
+        templates.sort_by(|a, b| a.name.cmp(&b.name));
+        templates
+    }
+
+    /// Adds a template or redefines an existing one, refitting every entry made from it so
+    /// they keep satisfying the new definition.
+    pub fn save_template(&mut self, template: Template, vault_name: &str) -> Result<(), String> {
+        let mut refitted = vec![];
+        for (key, entry) in &self.passwords {
+            if let EntryKind::Custom { template: used, fields } = &entry.kind {
+                if *used == template.name {
+                    let fields = template
+                        .rebuild(fields)
+                        .map_err(|e| format!("{} would no longer be valid: {}", key, e))?;
+                    refitted.push((key.clone(), fields));
+                }
             }
         }
+
+        for (key, fields) in refitted {
+            if let Some(entry) = self.passwords.get_mut(&key) {
+                entry.kind = EntryKind::Custom {
+                    template: template.name.clone(),
+                    fields,
+                };
+            }
+        }
+        self.templates.insert(template.name.clone(), template);
+        // Templates aren't journaled, a refit can touch any number of entries anyway.
+        self.save_to_file(vault_name)
+    }
+
+    pub fn delete_template(&mut self, name: &str, vault_name: &str) -> Result<(), String> {
+        if !self.templates.contains_key(name) {
+            return Err(String::from("No template of that name found"));
+        }
+
+        let in_use = self.passwords.values().any(|entry| {
+            matches!(&entry.kind, EntryKind::Custom { template, .. } if template == name)
+        });
+        if in_use {
+            return Err(String::from("That template is still used by entries"));
+        }
+
+        self.templates.remove(name);
+        self.save_to_file(vault_name)
+    }
+
+    pub fn insert_from_template(
+        &mut self,
+        name: String,
+        template: &str,
+        values: HashMap<String, String>,
+        vault_name: &str,
+    ) -> Result<(), String> {
+        let template = self
+            .templates
+            .get(template)
+            .ok_or("No template of that name found")?;
+        let fields = template.build(values)?;
+        let entry = Entry::custom(template.name.clone(), fields);
+
+        self.insert_entry(name, entry, vault_name)
+    }
+
+    /// Updates some fields of a template entry, the result is validated against the template
+    /// so required fields cannot be cleared.
+    pub fn update_template_entry(
+        &mut self,
+        name: &str,
+        values: HashMap<String, String>,
+        vault_name: &str,
+    ) -> Result<(), String> {
+        let entry = self.passwords.get(name).ok_or("No entry of that name found")?;
+        let EntryKind::Custom { template, fields } = &entry.kind else {
+            return Err(String::from("That entry was not made from a template"));
+        };
+        let template = self
+            .templates
+            .get(template)
+            .ok_or("The template for that entry no longer exists")?;
+
+        let mut merged: HashMap<String, String> = fields
+            .iter()
+            .map(|f| (f.name.clone(), f.value.clone()))
+            .collect();
+        merged.extend(values);
+        let fields = template.build(merged)?;
+
+        let entry = self.passwords.get_mut(name).unwrap();
+        entry.kind = EntryKind::Custom {
+            template: template.name.clone(),
+            fields,
+        };
+        entry.touch();
+        self.save_entry(name, vault_name)
+    }
+
+    pub fn view_template_entry(&self, name: &str) -> Result<Vec<CustomFieldView>, &str> {
+        match self.passwords.get(name).map(|entry| &entry.kind) {
+            Some(EntryKind::Custom { fields, .. }) => Ok(fields
+                .iter()
+                .map(|f| CustomFieldView {
+                    name: f.name.clone(),
+                    field_type: f.field_type,
+                    value: match f.field_type {
+                        FieldType::Protected | FieldType::Otp => None,
+                        _ => Some(f.value.clone()),
+                    },
+                })
+                .collect()),
+            Some(_) => Err("That entry was not made from a template"),
+            None => Err("No entry of that name found"),
+        }
     }
 
-    pub fn list_password(&self) -> Vec<String> {
+    pub fn retrieve_custom_field(&self, name: &str, field: &str) -> Result<String, &str> {
+        match self.passwords.get(name).map(|entry| &entry.kind) {
+            Some(EntryKind::Custom { fields, .. }) => fields
+                .iter()
+                .find(|f| f.name == field)
+                .map(|f| f.value.clone())
+                .filter(|v| !v.is_empty())
+                .ok_or("That field is empty"),
+            Some(_) => Err("That entry was not made from a template"),
+            None => Err("No entry of that name found"),
+        }
+    }
+
+    pub fn metadata_settings(&self) -> MetadataSettings {
+        self.metadata.settings(self.created)
+    }
+
+    /// Changes what the vault picker can show about this vault without unlocking it.
+    pub fn set_metadata(
+        &mut self,
+        settings: MetadataSettings,
+        vault_name: &str,
+    ) -> Result<(), String> {
+        self.metadata
+            .apply(settings, &self.pbkdf2_component.describe(), self.created)?;
+        self.save_to_file(vault_name)
+    }
+
+    pub fn list_password(&self) -> Vec<EntrySummary> {
         let mut vector = vec![];
 
-        for (key, _) in &self.passwords {
-            vector.push(key.to_string());
+        for (key, entry) in &self.passwords {
+            vector.push(EntrySummary {
+                name: key.to_string(),
+                kind: entry.entry_type(),
+                strength: entry.strength,
+            });
         }
 
+        vector.sort_by(|a, b| a.name.cmp(&b.name));
         vector
     }
 
-    pub fn retrieve_from_file(&mut self, vault_name: &str) {
-        let path = get_data_path(vault_name);
-        let file = fs::read(path).unwrap();
-        let config = config::standard();
+    pub fn retrieve_from_file(&mut self, vault_name: &str) -> Result<(), String> {
+        let file = self.store.read(vault_name)?.unwrap_or_default();
 
-        let decoded = match decode_from_slice::<Vault<Unlocked>, Configuration>(&file, config) {
-            Ok((mut result, _)) => {
+        let decoded = match decode_file(&file) {
+            Ok((header, mut result)) => {
+                let derived_key = self.pbkdf2_component.derived_key;
                 let decrypted_data = result
                     .pbkdf2_component
                     .encrypted_passwords
-                    .decrypt(result.pbkdf2_component.derived_key);
-                let decoded = match decode_from_slice::<HashMap<String, String>, Configuration>(
-                    &decrypted_data,
-                    config,
-                ) {
-                    Ok(r) => r.0,
-                    Err(_) => HashMap::new(),
-                };
-                let data = Vault::<Unlocked> {
-                    passwords: decoded,
+                    .clone()
+                    .decrypt(derived_key)?;
+                result.pbkdf2_component.derived_key = derived_key;
+                let payload = try_decode_payload(&decrypted_data)?;
+                let mut passwords = payload.entries;
+                let journal = self.store.journal(vault_name)?;
+                replay(&mut passwords, &result.pbkdf2_component, &journal);
+                Vault::<Unlocked> {
+                    passwords,
+                    templates: payload.templates,
                     pbkdf2_component: result.pbkdf2_component,
+                    store: self.store.clone(),
+                    journal,
+                    metadata: header.metadata,
+                    created: payload.created,
                     state: PhantomData::<Unlocked>,
-                };
-                data
+                }
             }
             Err(_) => self.clone(),
         };
 
         *self = decoded;
+        Ok(())
+    }
+
+    /// Writes the vault out whole if the journal has changes, which also backs it up. Done
+    /// when a vault is opened for writing and when it is locked, so every session's changes
+    /// are in a backup.
+    pub fn compact(&mut self, vault_name: &str) -> Result<(), String> {
+        if self.journal.is_empty() || self.damaged_changes() > 0 {
+            return Ok(());
+        }
+        self.save_to_file(vault_name)
     }
 
     pub fn lock(self) -> Vault<Locked> {
         let pbkdf2_component = self.pbkdf2_component.sanitise();
         Vault {
             passwords: HashMap::new(),
-            pbkdf2_component: pbkdf2_component,
+            templates: HashMap::new(),
+            pbkdf2_component,
+            store: self.store,
+            journal: vec![],
+            metadata: self.metadata,
+            created: None,
             state: PhantomData::<Locked>,
         }
     }
 }
 
-impl<State: LockState> Vault<State>
-where
-    Self: bincode::Encode,
-{
+impl<State: LockState> Vault<State> {
     pub fn check_lock(&self) -> bool {
         State::is_locked()
     }
 
-    fn save_to_file(&mut self, vault_name: &str) {
-        let path = get_data_path(vault_name);
+    fn save_to_file(&mut self, vault_name: &str) -> Result<(), String> {
         let config = config::standard();
-        let mut to_encrypt = Vault::<Locked> {
-            passwords: self.passwords.clone(),
-            pbkdf2_component: self.pbkdf2_component.clone(),
-            state: PhantomData::<Locked>,
-        };
-        let encoded = encode_to_vec(&self.passwords, config).unwrap();
-        to_encrypt.pbkdf2_component.encrypted_passwords = to_encrypt
+        let encoded = encode_payload(&self.passwords, &self.templates, self.created);
+        self.pbkdf2_component.encrypted_passwords = self
             .pbkdf2_component
             .encrypted_passwords
             .encrypt_data(self.pbkdf2_component.derived_key, encoded);
-        let encoded = encode_to_vec(&to_encrypt, config).unwrap();
-        fs::write(&path, encoded).unwrap();
+        let to_write = VaultFile {
+            legacy_passwords: HashMap::new(),
+            pbkdf2_component: self.pbkdf2_component.for_storage(),
+        };
+        self.metadata
+            .refresh(&self.pbkdf2_component.describe(), self.created);
+        let encoded = metadata::join(&self.metadata, &encode_to_vec(&to_write, config).unwrap());
+        self.store.write(vault_name, &encoded)?;
+        self.journal.clear();
+        Ok(())
     }
-}
 
-pub fn check_file(vault_name: &str) -> RetrieveResult {
-    let exists = fs::exists(get_data_path(vault_name)).expect("Should work");
+    /// Changes in the journal that are damaged. They are left in the journal for
+    /// `verify_vault` to report and `repair_vault` to drop, compacting would lose them quietly.
+    pub fn damaged_changes(&self) -> usize {
+        let snapshot = self.pbkdf2_component.encrypted_passwords.snapshot_id();
+        let key = &self.pbkdf2_component.derived_key;
+        self.journal
+            .iter()
+            .filter(|record| journal::open(key, &snapshot, record).is_err())
+            .count()
+    }
 
-    match exists {
-        true => {
-            return RetrieveResult::Success;
+    /// Saves a change to one entry by adding it to the journal, the whole vault is only
+    /// written once the journal is due to be compacted.
+    fn save_entry(&mut self, name: &str, vault_name: &str) -> Result<(), String> {
+        if self.journal.len() >= journal::COMPACT_AFTER && self.damaged_changes() == 0 {
+            return self.save_to_file(vault_name);
         }
-        false => {
-            return RetrieveResult::Failure;
+
+        let change = match self.passwords.get(name) {
+            Some(entry) => Change::Put {
+                name: name.to_string(),
+                entry: Box::new(entry.clone()),
+            },
+            None => Change::Remove {
+                name: name.to_string(),
+            },
+        };
+        let record = journal::seal(
+            &self.pbkdf2_component.derived_key,
+            &self.pbkdf2_component.encrypted_passwords.snapshot_id(),
+            &change,
+        );
+        self.store.append(vault_name, &record)?;
+        self.journal.push(record);
+        Ok(())
+    }
+}
+
+/// Applies the journal over the entries read from the vault file. Records made against an
+/// earlier vault file are already part of this one, damaged ones are skipped here and
+/// reported by `damaged_changes`.
+fn replay(
+    entries: &mut HashMap<String, Entry>,
+    pbkdf2_component: &Pbkdf2Component,
+    records: &[Vec<u8>],
+) {
+    let snapshot = pbkdf2_component.encrypted_passwords.snapshot_id();
+    for record in records {
+        if let Ok(Some(change)) = journal::open(&pbkdf2_component.derived_key, &snapshot, record) {
+            change.apply(entries);
         }
     }
 }
 
-pub fn init(vault_name: &str) -> Option<Vault<Locked>> {
-    let exists = fs::exists(get_data_path(vault_name)).expect("Should work");
-    let mut passwords = Vault::new();
-    if exists == false {
-        return None;
+/// Decodes what follows the header of a vault file.
+pub(crate) fn decode_body(body: &[u8]) -> Result<VaultFile, String> {
+    decode_from_slice::<VaultFile, Configuration>(body, config::standard())
+        .map(|(decoded, _)| decoded)
+        .map_err(|e| e.to_string())
+}
+
+pub(crate) fn decode_file(file: &[u8]) -> Result<(VaultHeader, VaultFile), String> {
+    let (header, body) = metadata::split(file)?;
+    Ok((header, decode_body(body)?))
+}
+
+/// The header of a vault, readable without unlocking it. Vaults from before headers, and ones
+/// whose header can't be read, get an empty one.
+pub fn read_header(store: &dyn VaultStore, vault_name: &str) -> VaultHeader {
+    let file = store.read(vault_name).ok().flatten().unwrap_or_default();
+    metadata::split(&file)
+        .map(|(header, _)| header)
+        .unwrap_or_default()
+}
+
+/// Whether `file` is laid out like a vault file, without opening it.
+pub fn is_vault_file(file: &[u8]) -> bool {
+    decode_file(file).is_ok()
+}
+
+pub fn check_file(store: &dyn VaultStore, vault_name: &str) -> Result<RetrieveResult, String> {
+    let exists = store.read(vault_name)?.is_some();
+
+    match exists {
+        true => Ok(RetrieveResult::Success),
+        false => Ok(RetrieveResult::Failure),
+    }
+}
+
+pub fn init(
+    store: Arc<dyn VaultStore>,
+    vault_name: &str,
+) -> Result<Option<Vault<Locked>>, String> {
+    // A file that doesn't decode is left for `verify_vault` to explain.
+    let exists = store
+        .read(vault_name)?
+        .is_some_and(|file| is_vault_file(&file));
+    let mut passwords = Vault::new(store);
+    if !exists {
+        Ok(None)
     } else {
-        passwords.retrieve_from_file(vault_name);
-        return Some(passwords.retrieved());
+        passwords.retrieve_from_file(vault_name)?;
+        Ok(Some(passwords.retrieved()))
     }
 }
 
-fn get_data_path(vault_name: &str) -> PathBuf {
-    if let Some(roaming) = var_os("APPDATA") {
-        let mut dir = PathBuf::from(roaming);
-        dir.push("Ancrypt");
-        dir.push("Vaults");
-        fs::create_dir_all(&dir).unwrap();
+pub fn attempt_unlock(pass: Vault<Locked>, password: &str) -> Result<Vault<Unlocked>, String> {
+    match pass.pbkdf2_component.verify_password(password) {
+        Ok(()) => pass.unlock(password),
+        Err(_) => Err(String::from("Incorrect Password")),
+    }
+}
 
-        let mut file_path = dir;
-        file_path.push(format!("{}.ANCRYPT", vault_name));
-        return file_path;
+impl Vault<Unlocked> {
+    /// Saves a vault restored or repaired from a file that may be older than the newest save.
+    /// Its nonce counter can be behind ones already used under this key, so it is encrypted
+    /// afresh with a new nonce prefix rather than counting on.
+    fn save_restored(&mut self, vault_name: &str) -> Result<(), String> {
+        self.pbkdf2_component.encrypted_passwords = EncryptedPasswords::new();
+        self.save_to_file(vault_name)
     }
-    PathBuf::from("./Vault.pass")
 }
 
-pub fn attempt_unlock(
-    pass: Vault<Locked>,
-    password: &str,
-) -> Result<Vault<Unlocked>, Vault<Locked>> {
-    match pass.pbkdf2_component.verify_password(password) {
-        Ok(()) => Ok(pass.unlock(password)),
-        Err(_) => Err(pass),
+/// Rolls a vault back to one of its backups, which must open with `master_password`. The
+/// restored version is saved like any other, so the restore can itself be undone.
+pub fn restore_backup(
+    store: Arc<dyn VaultStore>,
+    vault_name: &str,
+    id: &str,
+    master_password: &str,
+) -> Result<Vault<Unlocked>, String> {
+    let file = store.read_backup(vault_name, id)?;
+    let locked = Vault::<Locked>::from_file(&file, store.clone())
+        .ok_or_else(|| String::from("That backup is damaged"))?;
+    if locked.pbkdf2_component.verify_password(master_password).is_err() {
+        return Err(String::from(
+            "That backup does not open with this master password",
+        ));
     }
+    let mut unlocked = locked.unlock(master_password)?;
+
+    unlocked.save_restored(vault_name)?;
+
+    Ok(unlocked)
+}
+
+pub fn verify_vault(
+    store: &dyn VaultStore,
+    vault_name: &str,
+    master_password: &str,
+) -> Result<VerifyReport, String> {
+    let file = store.read(vault_name)?;
+    let journal = store.journal(vault_name)?;
+    let (report, _) = verify::verify(file.as_deref(), &journal, master_password);
+    Ok(report)
+}
+
+/// Rewrites a vault from what still opens with `master_password`: the vault file with every
+/// journal change that can be read, or failing that the newest backup that verifies.
+pub fn repair_vault(
+    store: Arc<dyn VaultStore>,
+    vault_name: &str,
+    master_password: &str,
+) -> Result<(RepairOutcome, Vault<Unlocked>), String> {
+    let file = store.read(vault_name)?;
+    let journal = store.journal(vault_name)?;
+    let (report, opened) = verify::verify(file.as_deref(), &journal, master_password);
+
+    if let Some(opened) = opened {
+        let skipped_changes = opened.skipped_changes;
+        let mut vault = Vault::from_opened(opened, store);
+        vault.save_restored(vault_name)?;
+        let outcome = RepairOutcome::Salvaged {
+            entries: vault.passwords.len(),
+            skipped_changes,
+        };
+        return Ok((outcome, vault));
+    }
+    // Backups are no use when it is the password that is wrong.
+    if report.failed_at() == Some(Stage::MasterPassword) {
+        return Err(String::from("The master password does not match this vault"));
+    }
+
+    for backup in store.backups(vault_name) {
+        let Ok(file) = store.read_backup(vault_name, &backup.id) else {
+            continue;
+        };
+        let (_, Some(opened)) = verify::verify(Some(&file), &[], master_password) else {
+            continue;
+        };
+
+        let mut vault = Vault::from_opened(opened, store);
+        vault.save_restored(vault_name)?;
+        let outcome = RepairOutcome::RestoredBackup {
+            id: backup.id,
+            entries: vault.passwords.len(),
+        };
+        return Ok((outcome, vault));
+    }
+
+    Err(String::from(
+        "Nothing could be recovered, neither the vault file nor any backup opens",
+    ))
 }
 
 pub fn delete_vault(
+    store: &dyn VaultStore,
     vault_name: &str
 ) -> Result<(), String> {
-    let path = get_data_path(vault_name);
-    fs::remove_file(path).map_err(|e| e.to_string())
+    store.delete(vault_name)?;
+
+    // Attachments stay in the vault folder whichever store holds the vault.
+    let attachments = storage::attachment_dir(vault_name);
+    if attachments.exists() {
+        fs::remove_dir_all(attachments).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    use crate::store::memory::MemoryStore;
+    use crate::vault::date::{today, Date};
+
+    const VAULT: &str = "vault";
+
+    fn new_vault() -> (Arc<dyn VaultStore>, Vault<Unlocked>) {
+        let store: Arc<dyn VaultStore> = Arc::new(MemoryStore::default());
+        let vault = Vault::new(store.clone())
+            .create_new("master password", VAULT)
+            .unwrap();
+        (store, vault)
+    }
+
+    fn reopen(store: &Arc<dyn VaultStore>) -> Vault<Unlocked> {
+        let locked = init(store.clone(), VAULT).unwrap().unwrap();
+        attempt_unlock(locked, "master password").ok().unwrap()
+    }
+
+    fn insert(vault: &mut Vault<Unlocked>, name: &str, password: &str) {
+        vault
+            .insert_password(name.to_string(), password.to_string(), VAULT)
+            .unwrap();
+    }
+
+    #[test]
+    fn lists_entries_due_within_the_window() {
+        let (_, mut vault) = new_vault();
+        for name in ["soon", "later", "overdue", "never"] {
+            vault
+                .insert_password(name.to_string(), "hunter2".to_string(), VAULT)
+                .unwrap();
+        }
+
+        let in_days = |days: i64| Expiry::On(Date::from_days(today().to_days() + days));
+        vault.set_expiry("soon", Some(in_days(3)), VAULT).unwrap();
+        vault.set_expiry("later", Some(in_days(90)), VAULT).unwrap();
+        vault
+            .set_expiry("overdue", Some(Expiry::Every { days: 0 }), VAULT)
+            .unwrap();
+        vault.passwords.get_mut("overdue").unwrap().modified -= 86_400;
+        assert!(vault.set_expiry("missing", None, VAULT).is_err());
+
+        let expiring = vault.list_expiring(30);
+        let names: Vec<&str> = expiring.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["overdue", "soon"]);
+        assert!(expiring[0].expired);
+        assert_eq!(expiring[0].days_left, -1);
+        assert!(!expiring[1].expired);
+        assert_eq!(expiring[1].days_left, 3);
+
+        vault.set_expiry("later", None, VAULT).unwrap();
+        assert_eq!(vault.list_expiring(365).len(), 2);
+    }
+
+    #[test]
+    fn created_time_is_recorded_and_only_exposed_when_chosen() {
+        let (store, mut vault) = new_vault();
+        let created = vault.created.unwrap();
+        assert!(read_header(store.as_ref(), VAULT)
+            .metadata
+            .created
+            .is_none());
+
+        let mut settings = vault.metadata_settings();
+        settings.show_created = true;
+        vault.set_metadata(settings, VAULT).unwrap();
+        assert_eq!(
+            read_header(store.as_ref(), VAULT).metadata.created,
+            Some(created)
+        );
+
+        let reopened = reopen(&store);
+        assert_eq!(reopened.created, Some(created));
+        assert!(reopened.metadata_settings().show_created);
+    }
+
+    #[test]
+    fn payloads_from_before_entry_kinds_are_read_as_logins() {
+        let passwords = HashMap::from([(String::from("bank"), String::from("hunter2"))]);
+        let payload = encode_to_vec(&passwords, config::standard()).unwrap();
+
+        let decoded = try_decode_payload(&payload).unwrap();
+        assert!(matches!(
+            &decoded.entries["bank"].kind,
+            EntryKind::Login { password } if password == "hunter2"
+        ));
+        assert!(decoded.templates.is_empty());
+        assert!(decoded.created.is_none());
+    }
+
+    #[test]
+    fn a_payload_that_does_not_decode_fails_to_unlock() {
+        let (store, mut vault) = new_vault();
+        insert(&mut vault, "bank", "one");
+        let key = vault.pbkdf2_component.derived_key;
+        vault.pbkdf2_component.encrypted_passwords = vault
+            .pbkdf2_component
+            .encrypted_passwords
+            .encrypt_data(key, [PAYLOAD_MAGIC.as_slice(), b"garbage"].concat());
+        let to_write = VaultFile {
+            legacy_passwords: HashMap::new(),
+            pbkdf2_component: vault.pbkdf2_component.for_storage(),
+        };
+        let body = encode_to_vec(&to_write, config::standard()).unwrap();
+        store
+            .write(VAULT, &metadata::join(&vault.metadata, &body))
+            .unwrap();
+        let before = store.read(VAULT).unwrap();
+
+        let locked = init(store.clone(), VAULT).unwrap().unwrap();
+        assert!(attempt_unlock(locked, "master password").is_err());
+        assert!(vault.retrieve_from_file(VAULT).is_err());
+        assert_eq!(store.read(VAULT).unwrap(), before);
+    }
+
+    #[test]
+    fn journal_changes_replay_over_the_vault_file() {
+        let (store, mut vault) = new_vault();
+        insert(&mut vault, "bank", "one");
+        insert(&mut vault, "mail", "two");
+        vault
+            .update_password("bank", "three".to_string(), VAULT)
+            .unwrap();
+        vault.delete_password("mail".to_string(), VAULT).unwrap();
+        assert_eq!(store.journal(VAULT).unwrap().len(), 4);
+
+        let reopened = reopen(&store);
+        assert_eq!(reopened.retrieve_password("bank").unwrap(), "three");
+        assert!(reopened.retrieve_password("mail").is_err());
+        assert_eq!(reopened.damaged_changes(), 0);
+    }
+
+    #[test]
+    fn journal_is_compacted_after_the_limit() {
+        let (store, mut vault) = new_vault();
+        for i in 0..journal::COMPACT_AFTER {
+            insert(&mut vault, &format!("entry {}", i), "password");
+        }
+        assert_eq!(store.journal(VAULT).unwrap().len(), journal::COMPACT_AFTER);
+
+        insert(&mut vault, "one more", "password");
+        assert!(store.journal(VAULT).unwrap().is_empty());
+        assert!(vault.journal.is_empty());
+        assert_eq!(reopen(&store).passwords.len(), journal::COMPACT_AFTER + 1);
+
+        insert(&mut vault, "after", "password");
+        vault.compact(VAULT).unwrap();
+        assert!(store.journal(VAULT).unwrap().is_empty());
+        assert!(reopen(&store).retrieve_password("after").is_ok());
+    }
+
+    #[test]
+    fn changes_from_an_earlier_vault_file_are_ignored() {
+        let (store, mut vault) = new_vault();
+        insert(&mut vault, "bank", "old");
+        let stale = store.journal(VAULT).unwrap().remove(0);
+        vault
+            .update_password("bank", "new".to_string(), VAULT)
+            .unwrap();
+        vault.compact(VAULT).unwrap();
+
+        // A crash between writing the vault and emptying the journal leaves records like this.
+        store.append(VAULT, &stale).unwrap();
+        let reopened = reopen(&store);
+        assert_eq!(reopened.retrieve_password("bank").unwrap(), "new");
+        assert_eq!(reopened.damaged_changes(), 0);
+        let report = verify_vault(&*store, VAULT, "master password").unwrap();
+        assert!(report.healthy);
+    }
+
+    #[test]
+    fn damaged_changes_are_reported_and_kept() {
+        let (store, mut vault) = new_vault();
+        insert(&mut vault, "bank", "one");
+        let mut damaged = store.journal(VAULT).unwrap().remove(0);
+        *damaged.last_mut().unwrap() ^= 1;
+        store.append(VAULT, &damaged).unwrap();
+
+        let mut reopened = reopen(&store);
+        assert_eq!(reopened.retrieve_password("bank").unwrap(), "one");
+        assert_eq!(reopened.damaged_changes(), 1);
+        reopened.compact(VAULT).unwrap();
+        assert_eq!(store.journal(VAULT).unwrap().len(), 2);
+
+        let report = verify_vault(&*store, VAULT, "master password").unwrap();
+        assert!(!report.healthy);
+        assert_eq!(report.failed_at(), Some(Stage::Journal));
+    }
+
+    /// The nonce of every version of the vault the store has kept.
+    fn nonces(store: &Arc<dyn VaultStore>) -> Vec<[u8; 12]> {
+        store
+            .backups(VAULT)
+            .iter()
+            .map(|backup| {
+                let file = store.read_backup(VAULT, &backup.id).unwrap();
+                let (_, decoded) = decode_file(&file).unwrap();
+                decoded.pbkdf2_component.encrypted_passwords.nonce()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn restoring_a_backup_never_reuses_a_nonce() {
+        let (store, mut vault) = new_vault();
+        insert(&mut vault, "bank", "one");
+        vault.compact(VAULT).unwrap();
+        let before = store.backups(VAULT)[0].id.clone();
+        insert(&mut vault, "mail", "two");
+        vault.compact(VAULT).unwrap();
+
+        let mut restored =
+            restore_backup(store.clone(), VAULT, &before, "master password").unwrap();
+        assert!(restored.retrieve_password("mail").is_err());
+        insert(&mut restored, "shop", "three");
+        restored.compact(VAULT).unwrap();
+        assert_eq!(reopen(&store).retrieve_password("shop").unwrap(), "three");
+
+        let nonces = nonces(&store);
+        let unique: HashSet<_> = nonces.iter().collect();
+        assert_eq!(unique.len(), nonces.len());
+
+        assert!(restore_backup(store.clone(), VAULT, &before, "wrong").is_err());
+        assert!(restore_backup(store.clone(), VAULT, "99", "master password").is_err());
+    }
+
+    #[test]
+    fn repair_drops_damaged_journal_changes() {
+        let (store, mut vault) = new_vault();
+        insert(&mut vault, "bank", "one");
+        store.append(VAULT, b"not a journal record").unwrap();
+        assert_eq!(
+            verify_vault(&*store, VAULT, "master password")
+                .unwrap()
+                .failed_at(),
+            Some(Stage::Journal)
+        );
+
+        let (outcome, repaired) = repair_vault(store.clone(), VAULT, "master password").unwrap();
+        assert!(matches!(
+            outcome,
+            RepairOutcome::Salvaged {
+                entries: 1,
+                skipped_changes: 1
+            }
+        ));
+        assert_eq!(repaired.retrieve_password("bank").unwrap(), "one");
+        assert!(store.journal(VAULT).unwrap().is_empty());
+        assert!(
+            verify_vault(&*store, VAULT, "master password")
+                .unwrap()
+                .healthy
+        );
+    }
+
+    #[test]
+    fn repair_falls_back_to_the_newest_good_backup() {
+        let (store, mut vault) = new_vault();
+        insert(&mut vault, "bank", "one");
+        vault.compact(VAULT).unwrap();
+        insert(&mut vault, "mail", "two");
+        vault.compact(VAULT).unwrap();
+
+        // The memory store backs up every write, so the damaged file is the newest backup too.
+        let mut damaged = store.read(VAULT).unwrap().unwrap();
+        *damaged.last_mut().unwrap() ^= 1;
+        store.write(VAULT, &damaged).unwrap();
+        let report = verify_vault(&*store, VAULT, "master password").unwrap();
+        assert_eq!(report.failed_at(), Some(Stage::Authentication));
+        assert!(init(store.clone(), VAULT).unwrap().is_some());
+        assert!(attempt_unlock(
+            init(store.clone(), VAULT).unwrap().unwrap(),
+            "master password"
+        )
+        .is_err());
+
+        assert!(repair_vault(store.clone(), VAULT, "wrong").is_err());
+        let (outcome, repaired) = repair_vault(store.clone(), VAULT, "master password").unwrap();
+        assert!(matches!(
+            outcome,
+            RepairOutcome::RestoredBackup { entries: 2, .. }
+        ));
+        assert_eq!(repaired.retrieve_password("mail").unwrap(), "two");
+        assert!(
+            verify_vault(&*store, VAULT, "master password")
+                .unwrap()
+                .healthy
+        );
+    }
 }
@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::vault::entry::Entry;
+use crate::vault::journal;
+use crate::vault::master_password::Pbkdf2Component;
+use crate::vault::metadata::{self, VaultMetadata};
+use crate::vault::template::Template;
+use crate::vault::vault::{decode_body, try_decode_payload};
+
+/// The steps of opening a vault, in the order they are checked.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// The store has a file for the vault.
+    Present,
+    /// The metadata header, if the file has one, is a format this version reads.
+    Header,
+    /// The file decodes as a vault file.
+    Layout,
+    /// The stored key derivation settings are ones a vault is made with.
+    KeyDerivation,
+    MasterPassword,
+    /// The encrypted entries decrypt under the derived key unaltered.
+    Authentication,
+    /// The decrypted entries decode.
+    Payload,
+    /// Every change in the journal made after this vault file decrypts.
+    Journal,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct StageReport {
+    pub stage: Stage,
+    pub passed: bool,
+    pub detail: String,
+}
+
+/// How far a vault got towards opening. Checking stops at the first failed stage, except for
+/// the journal, whose damaged changes are counted and skipped.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct VerifyReport {
+    pub stages: Vec<StageReport>,
+    pub entries: usize,
+    pub healthy: bool,
+}
+
+/// What a repair wrote back to the store.
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RepairOutcome {
+    /// The vault file opened, it was rewritten with every journal change that could be read and
+    /// the damaged ones were dropped.
+    Salvaged {
+        entries: usize,
+        skipped_changes: usize,
+    },
+    /// The vault file was past saving and the newest backup that verifies replaced it.
+    RestoredBackup { id: String, entries: usize },
+}
+
+/// A vault that made it through every stage up to the journal.
+pub struct Opened {
+    /// Holds the key derived from the master password.
+    pub pbkdf2_component: Pbkdf2Component,
+    pub entries: HashMap<String, Entry>,
+    pub templates: HashMap<String, Template>,
+    pub metadata: VaultMetadata,
+    pub created: Option<u64>,
+    pub skipped_changes: usize,
+}
+
+impl VerifyReport {
+    fn pass(&mut self, stage: Stage, detail: String) {
+        self.stages.push(StageReport {
+            stage,
+            passed: true,
+            detail,
+        });
+    }
+
+    fn fail(&mut self, stage: Stage, detail: String) {
+        self.stages.push(StageReport {
+            stage,
+            passed: false,
+            detail,
+        });
+    }
+
+    fn failed(mut self, stage: Stage, detail: String) -> (VerifyReport, Option<Opened>) {
+        self.fail(stage, detail);
+        (self, None)
+    }
+
+    pub fn failed_at(&self) -> Option<Stage> {
+        self.stages.iter().find(|s| !s.passed).map(|s| s.stage)
+    }
+}
+
+/// Opens `file` and its journal step by step, reporting each stage. The vault comes back as
+/// far as it could be read whenever the vault file itself is sound.
+pub fn verify(
+    file: Option<&[u8]>,
+    records: &[Vec<u8>],
+    master_password: &str,
+) -> (VerifyReport, Option<Opened>) {
+    let mut report = VerifyReport::default();
+
+    let Some(file) = file else {
+        return report.failed(
+            Stage::Present,
+            String::from("There is no file for this vault"),
+        );
+    };
+    report.pass(Stage::Present, format!("{} bytes", file.len()));
+
+    let (header, body) = match metadata::split(file) {
+        Ok(split) => split,
+        Err(e) => return report.failed(Stage::Header, e),
+    };
+    let detail = match header.format_version {
+        0 => String::from("Format 0, written before vault files had a header"),
+        version => format!("Format {} with a metadata header", version),
+    };
+    report.pass(Stage::Header, detail);
+
+    let decoded = match decode_body(body) {
+        Ok(decoded) => decoded,
+        Err(e) => {
+            return report.failed(
+                Stage::Layout,
+                format!("The file is not laid out like a vault: {}", e),
+            )
+        }
+    };
+    report.pass(Stage::Layout, String::from("The file decodes as a vault"));
+
+    let mut pbkdf2_component = decoded.pbkdf2_component;
+    if let Err(e) = pbkdf2_component.check_parameters() {
+        return report.failed(Stage::KeyDerivation, e);
+    }
+    report.pass(Stage::KeyDerivation, pbkdf2_component.describe());
+
+    if pbkdf2_component.verify_password(master_password).is_err() {
+        return report.failed(
+            Stage::MasterPassword,
+            String::from("The master password does not match this vault"),
+        );
+    }
+    report.pass(
+        Stage::MasterPassword,
+        String::from("The master password matches"),
+    );
+
+    pbkdf2_component.derive_key(master_password);
+    let payload = match pbkdf2_component
+        .encrypted_passwords
+        .clone()
+        .try_decrypt(pbkdf2_component.derived_key)
+    {
+        Ok(payload) => payload,
+        Err(_) => {
+            return report.failed(
+                Stage::Authentication,
+                String::from("The encrypted entries have been altered or damaged"),
+            )
+        }
+    };
+    report.pass(
+        Stage::Authentication,
+        String::from("The encrypted entries are intact"),
+    );
+
+    let payload = match try_decode_payload(&payload) {
+        Ok(payload) => payload,
+        Err(e) => return report.failed(Stage::Payload, e),
+    };
+    let mut entries: HashMap<String, Entry> = decoded
+        .legacy_passwords
+        .into_iter()
+        .map(|(name, password)| (name, Entry::login(password)))
+        .collect();
+    entries.extend(payload.entries);
+    report.pass(
+        Stage::Payload,
+        format!(
+            "{} entries and {} templates",
+            entries.len(),
+            payload.templates.len()
+        ),
+    );
+
+    let snapshot = pbkdf2_component.encrypted_passwords.snapshot_id();
+    let (mut replayed, mut stale, mut skipped_changes) = (0, 0, 0);
+    for record in records {
+        match journal::open(&pbkdf2_component.derived_key, &snapshot, record) {
+            Ok(Some(change)) => {
+                change.apply(&mut entries);
+                replayed += 1;
+            }
+            Ok(None) => stale += 1,
+            Err(_) => skipped_changes += 1,
+        }
+    }
+    if skipped_changes == 0 {
+        report.pass(
+            Stage::Journal,
+            format!(
+                "{} changes replayed, {} already part of the vault file",
+                replayed, stale
+            ),
+        );
+    } else {
+        report.fail(
+            Stage::Journal,
+            format!(
+                "{} of {} changes are damaged and could not be read",
+                skipped_changes,
+                records.len()
+            ),
+        );
+    }
+
+    report.entries = entries.len();
+    report.healthy = skipped_changes == 0;
+    let opened = Opened {
+        pbkdf2_component,
+        entries,
+        templates: payload.templates,
+        metadata: header.metadata,
+        created: payload.created,
+        skipped_changes,
+    };
+    (report, Some(opened))
+}
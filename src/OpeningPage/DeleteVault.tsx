@@ -70,10 +70,14 @@ export default function DeleteVault({change, id, name}: props) {
 
         invoke("request_delete_vault", {
             vault: id
+        }).then(() => {
+            change(prev => prev + 1);
+            setOpen(false);
+            setValidationCode(0);
+            setErrorMessage("");
+        }).catch((e: string) => {
+            setErrorMessage(e);
         });
-        setOpen(false);
-        setValidationCode(0);
-        setErrorMessage("");
     }
 
     const handleValidationCodeChange = (e: React.ChangeEvent<HTMLInputElement>) => {
@@ -10,6 +10,13 @@ import DeleteVault from "./DeleteVault";
 type FileName = {
   name: string;
   id: number;
+  format_version: number;
+  display_name: string | null;
+  icon: string | null;
+  colour: string | null;
+  created: number | null;
+  modified: number | null;
+  kdf: string | null;
 };
 
 export default function Login() {
@@ -17,6 +24,14 @@ export default function Login() {
   const [change, setChange] = useState(0);
   const [disclaimer, setDisclaimer] = useState(false);
 
+  useEffect(() => {
+    invoke<string[]>("request_notices").then((notices) => {
+      if (notices.length > 0) {
+        alert(notices.join("\n"));
+      }
+    });
+  }, []);
+
   useEffect(() => {
     invoke<FileName[]>("request_vaults").then((e) => {
       setFiles(e);
@@ -44,7 +59,7 @@ export default function Login() {
       <h1 className="LoginTitle">Ancrypt</h1>
       <div className="LoginContainer">
         {files && files.length > 0 ? (
-          files.map((data) => <FileCard key={data.id} name={data.name} id={data.id} change={setChange} />)
+          files.map((data) => <FileCard key={data.id} file={data} name={data.name} id={data.id} change={setChange} />)
         ) : (
           <p>You have no vaults, create a new vault below</p>
         )}
@@ -89,16 +104,25 @@ export default function Login() {
 }
 
 interface FileCardProps {
+  file: FileName,
   name: string,
   id: number,
   change: React.Dispatch<React.SetStateAction<number>>
 }
 
 function FileCard(props: FileCardProps) {
+  const { file } = props;
+  const date = (seconds: number) => new Date(seconds * 1000).toLocaleString();
+
   return (
-    <div className="FileCard">
-      <h3 className="FileCardName">{props.name}</h3>
-      <OpenVault {...props} />
+    <div className="FileCard" style={file.colour ? { borderLeft: `6px solid ${file.colour}` } : undefined}>
+      <h3 className="FileCardName">
+        {file.icon ? `${file.icon} ` : ""}{file.display_name ?? props.name}
+      </h3>
+      {file.created !== null && <p className="FileCardDetail">Created {date(file.created)}</p>}
+      {file.modified !== null && <p className="FileCardDetail">Modified {date(file.modified)}</p>}
+      {file.kdf !== null && <p className="FileCardDetail">{file.kdf}</p>}
+      <OpenVault id={props.id} name={props.name} />
       <DeleteVault change={props.change} id={props.id} name={props.name} />
     </div>
   );
@@ -18,6 +18,7 @@ interface OpenVaultProps {
 type OpenVaultResponse = {
   success: boolean;
   message?: string;
+  read_only: boolean;
 };
 
 export default function OpenVault({ name, id }: OpenVaultProps) {
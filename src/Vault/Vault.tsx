@@ -5,8 +5,20 @@ import "./Vault.css";
 import { useNavigate } from "react-router";
 import DeletePassword from "./DeletePassword";
 
+interface EntrySummary {
+  name: string;
+  kind: "login" | "secure_note" | "payment_card" | "identity" | "ssh_key" | "passkey" | "custom" | "derived";
+  strength: number | null;
+}
+
+const copyField: Partial<Record<EntrySummary["kind"], string>> = {
+  payment_card: "number",
+  identity: "full_name",
+  ssh_key: "private_key",
+};
+
 export default function Vault() {
-  const [passwordList, setPasswordList] = useState<null | string[]>(null);
+  const [passwordList, setPasswordList] = useState<null | EntrySummary[]>(null);
   const [change, setChange] = useState(0);
   const [newPassword, setNewPassword] = useState("");
   const [newName, setNewName] = useState("");
@@ -29,7 +41,10 @@ export default function Vault() {
   }
 
   const handleLock = () => {
-    invoke("lock_vault").then(() => {
+    invoke<string | null>("lock_vault").then((warning) => {
+        if (warning) {
+            alert(warning);
+        }
         setPasswordList(null);
         setNewPassword("");
         setNewName("");
@@ -56,7 +71,7 @@ export default function Vault() {
   }
 
   useEffect(() => {
-    invoke<string[]>("retrieve_password_list").then((e) => {
+    invoke<EntrySummary[]>("retrieve_password_list").then((e) => {
       setPasswordList(e);
     });
   }, [change]);
@@ -97,7 +112,7 @@ export default function Vault() {
         <div className="VaultSideContainer">
           <div className="PasswordPanelContainer">
             {passwordList && passwordList.length > 0 ? (
-              passwordList.map((data, index) => <PasswordPanel key={index} password={data} change={setChange}  />)
+              passwordList.map((data, index) => <PasswordPanel key={index} entry={data} change={setChange}  />)
             ) : (
               <p>No passwords yet, create some passwords to store</p>
             )}
@@ -163,18 +178,39 @@ export default function Vault() {
 }
 
 interface PasswordPanelProps {
-  password: string;
+  entry: EntrySummary;
   change: React.Dispatch<React.SetStateAction<number>>;
 }
 
-function PasswordPanel({ password, change }: PasswordPanelProps) {
+function PasswordPanel({ entry, change }: PasswordPanelProps) {
+    const [note, setNote] = useState<null | string>(null);
+    const password = entry.name;
 
     const copy = () => {
-        invoke("copy_to_clipboard", {
-            password: password
+        if (entry.kind === "login") {
+            invoke("copy_to_clipboard", {
+                password: password
+            });
+            return;
+        }
+        invoke("copy_entry_field", {
+            name: password,
+            field: copyField[entry.kind]
         });
     }
 
+    const reveal = () => {
+        if (note !== null) {
+            setNote(null);
+            return;
+        }
+        invoke<string>("reveal_secure_note", {
+            name: password
+        }).then((e) => {
+            setNote(e);
+        })
+    }
+
   return (
     <div className="PasswordPanel">
       {
@@ -191,14 +227,30 @@ function PasswordPanel({ password, change }: PasswordPanelProps) {
         gap: "10px",
       }}
       >
-        <Button 
-      sx={{
-        color: "white",
-        backgroundColor: "rgba(0, 128, 255, 0.47)"
-      }}
-      onClick={copy}>Copy to clipboard</Button>
+        {
+        entry.kind === "secure_note" ? (
+            <Button
+          sx={{
+            color: "white",
+            backgroundColor: "rgba(0, 128, 255, 0.47)"
+          }}
+          onClick={reveal}>{note === null ? "Reveal note" : "Hide note"}</Button>
+        ) : (entry.kind === "login" || copyField[entry.kind]) && (
+            <Button 
+          sx={{
+            color: "white",
+            backgroundColor: "rgba(0, 128, 255, 0.47)"
+          }}
+          onClick={copy}>Copy to clipboard</Button>
+        )
+      }
       <DeletePassword change={change} password={password}/>
       </div>
+      {
+        note !== null && (
+            <p style={{ whiteSpace: "pre-wrap" }}>{note}</p>
+        )
+      }
     </div>
   );
 }